@@ -0,0 +1,7 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+  // Purchase service API, the loyalty messages are shared with gzlib
+  tonic_build::configure()
+    .extern_path(".loyalty", "::gzlib::proto::loyalty")
+    .compile(&["proto/purchase.proto"], &["proto"])?;
+  Ok(())
+}
@@ -0,0 +1,103 @@
+// Removed carts
+// Carts are never deleted, they are archived here
+// for loss-prevention reports
+// UPLs of removed carts are kept till they are reshelved
+
+use crate::cart::{Cart, CartMethods};
+use crate::error::DomainError;
+use chrono::prelude::*;
+use packman::VecPackMember;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AbandonedCart {
+  pub id: Uuid,                            // Same as the removed cart ID
+  pub cart: Cart,                          // Cart as it was at the time of removing
+  pub reason: RemoveReason,                // Why it was removed
+  pub removed_by: RemovedBy,               // Shop assistant or the system
+  pub removed_at: DateTime<Utc>,           // When cart removed
+  pub upls_to_release: Vec<String>,        // UPL IDs reserved by the cart at the time of removing
+  pub upls_released: Option<UplsReleased>, // Some(_) if the UPLs are reshelved
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RemoveReason {
+  Manual(String), // Given by the shop assistant
+  Expired,        // Idle for longer than max_idle
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RemovedBy {
+  User(u32), // UID
+  System,    // Background expiry task
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UplsReleased {
+  pub released_by: u32,           // UID
+  pub released_at: DateTime<Utc>, // When UPLs reshelved
+}
+
+impl AbandonedCart {
+  pub fn new(cart: Cart, reason: String, removed_by: u32) -> Self {
+    Self {
+      id: cart.id,
+      upls_to_release: cart.get_upl_ids(),
+      cart,
+      reason: RemoveReason::Manual(reason),
+      removed_by: RemovedBy::User(removed_by),
+      removed_at: Utc::now(),
+      upls_released: None,
+    }
+  }
+  /// Archive a cart removed by the system as idle
+  pub fn expired(cart: Cart) -> Self {
+    Self {
+      id: cart.id,
+      upls_to_release: cart.get_upl_ids(),
+      cart,
+      reason: RemoveReason::Expired,
+      removed_by: RemovedBy::System,
+      removed_at: Utc::now(),
+      upls_released: None,
+    }
+  }
+  /// Check if UPLs are still waiting for reshelving
+  pub fn has_upls_to_release(&self) -> bool {
+    self.upls_released.is_none() && !self.upls_to_release.is_empty()
+  }
+  /// Mark UPLs as reshelved
+  pub fn set_upls_released(&mut self, released_by: u32) -> Result<&Self, DomainError> {
+    if self.upls_released.is_some() {
+      return Err(DomainError::UplsAlreadyReleased);
+    }
+    self.upls_released = Some(UplsReleased {
+      released_by,
+      released_at: Utc::now(),
+    });
+    Ok(self)
+  }
+}
+
+impl Default for AbandonedCart {
+  fn default() -> Self {
+    Self {
+      id: Uuid::default(),
+      cart: Cart::default(),
+      reason: RemoveReason::Manual(String::default()),
+      removed_by: RemovedBy::User(0),
+      removed_at: Utc::now(),
+      upls_to_release: Vec::new(),
+      upls_released: None,
+    }
+  }
+}
+
+impl VecPackMember for AbandonedCart {
+  type Out = Uuid;
+
+  fn get_id(&self) -> &Self::Out {
+    &self.id
+  }
+}
@@ -3,17 +3,72 @@
 
 use std::ops::Mul;
 
+use crate::error::DomainError;
+use crate::event::{CartEvent, CartEventKind, EventTotals};
+use crate::promotion::{Promotion, PromotionLine};
+use crate::rounding::{allocate, div_round, percent_of};
+use crate::unit;
+pub use crate::unit::Unit;
+use crate::validation::CloseIssue;
 use chrono::{prelude::*, Duration};
 use packman::VecPackMember;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+// Payment term in days for transfer payments
+// if the customer has no own term
+pub const DEFAULT_PAYMENT_TERM: u32 = 30;
+
+// UPLs are flagged this many days
+// before their best before date by default
+pub const UPL_NEAR_EXPIRY_DAYS: i64 = 3;
+
+// How many days the invoice completion date
+// can be set before or after today by default
+pub const DATE_COMPLETION_MAX_DAYS_BACK: i64 = 15;
+pub const DATE_COMPLETION_MAX_DAYS_FORWARD: i64 = 30;
+
+/// Days the invoice completion date
+/// can be set before or after today
+#[derive(Clone, Copy)]
+pub struct DateCompletionLimits {
+  pub max_days_back: i64,
+  pub max_days_forward: i64,
+}
+
+impl Default for DateCompletionLimits {
+  fn default() -> Self {
+    Self {
+      max_days_back: DATE_COMPLETION_MAX_DAYS_BACK,
+      max_days_forward: DATE_COMPLETION_MAX_DAYS_FORWARD,
+    }
+  }
+}
+
 pub trait CartMethods
 where
   Self: Sized,
 {
   /// Create new Cart
   fn new(owner_uid: u32, store_id: Option<u32>, created_by: u32) -> Self;
+  /// Apply a modification and record it in the audit trail
+  /// with the acting user and the totals before and after it
+  fn record<F>(&mut self, created_by: u32, kind: CartEventKind, f: F) -> Result<&Self, DomainError>
+  where
+    F: FnOnce(&mut Self) -> Result<(), DomainError>;
+  /// Optimistic concurrency check before a modification
+  /// 0 means the client does not ask for version check
+  fn check_version(&mut self, expected_version: u32) -> Result<&mut Self, DomainError>;
+  /// Get totals snapshot for the audit trail
+  fn get_event_totals(&self) -> EventTotals;
+  /// Revert the most recent modification
+  /// Supported: add_upl, set_sku_piece, add_commitment, burn_points
+  /// transaction_id is the loyalty transaction giving back burned points
+  fn undo_last(
+    &mut self,
+    created_by: u32,
+    transaction_id: Option<Uuid>,
+  ) -> Result<&Self, DomainError>;
   /// Add Customer to a cart
   fn add_customer(&mut self, customer: Option<Customer>) -> &Self;
   /// Add SKU to cart; Normal sku
@@ -27,21 +82,72 @@ where
     unit_retail_price_gross: u32,
   ) -> &Self;
   /// Try to remove SKU
-  fn remove_sku(&mut self, sku: u32) -> Result<&Self, String>;
+  fn remove_sku(&mut self, sku: u32) -> Result<&Self, DomainError>;
   /// Try to update SKU piece in shopping list
-  fn set_sku_piece(&mut self, sku: u32, piece: u32) -> Result<&Self, String>;
+  fn set_sku_piece(&mut self, sku: u32, piece: u32) -> Result<&Self, DomainError>;
   /// Try to add UPL to cart
-  fn add_upl(&mut self, upl: UplInfoObject) -> Result<&Self, String>;
+  /// In a pre-order it is attached to the ordered SKU piece
+  fn add_upl(&mut self, upl: UplInfoObject) -> Result<&Self, DomainError>;
+  /// Add UPL to cart without the expiry check
+  /// Used for sold UPLs that passed it at sale time
+  fn attach_upl(&mut self, upl: UplInfoObject) -> Result<&Self, DomainError>;
   /// Try to remove UPL from cart
-  fn remove_upl(&mut self, upl_id: String) -> Result<&Self, String>;
+  fn remove_upl(&mut self, upl_id: String) -> Result<&Self, DomainError>;
+  /// Take back an added UPL
+  /// with the piece it added to the shopping list
+  fn undo_add_upl(&mut self, upl_id: String) -> Result<&Self, DomainError>;
+  /// Get the SKU piece covered by UPLs
+  fn get_upl_piece(&self, sku: u32) -> u32;
   /// Set if invoice need
   fn set_document(&mut self, document_kind: DocumentKind) -> &Self;
   /// Set payment kind
   fn set_payment(&mut self, payment_kind: PaymentKind) -> &Self;
+  /// Get payment term in days
+  /// customer specific if any, otherwise the default one
+  fn get_payment_term(&self) -> u32;
+  /// Set invoice completion date
+  /// payment duedate is recalculated from it
+  fn set_date_completion(
+    &mut self,
+    date_completion: DateTime<Utc>,
+    limits: DateCompletionLimits,
+  ) -> Result<&Self, DomainError>;
+  /// Set invoice completion date and payment duedate
+  fn set_invoice_dates(
+    &mut self,
+    date_completion: DateTime<Utc>,
+    payment_duedate: DateTime<Utc>,
+    limits: DateCompletionLimits,
+  ) -> Result<&Self, DomainError>;
   /// Get the payments total
   fn get_payment_total(&self) -> i32;
+  /// Get the payments total paid by the given method
+  fn get_payment_total_by_kind(&self, payment_kind: PaymentKind) -> i32;
   /// Add payment to Cart
   fn add_payment(&mut self, payment: Payment) -> &Self;
+  /// Add cash payment to Cart by the tendered amount
+  /// change is calculated from the rounded payable
+  fn add_cash_payment(&mut self, payment_id: String, tendered: i32) -> Result<&Self, DomainError>;
+  /// Reverse a payment partially by adding a negative payment
+  /// referencing the original one
+  fn reverse_payment(
+    &mut self,
+    payment_id: String,
+    reversal_id: String,
+    amount: i32,
+    reason: String,
+    created_by: u32,
+  ) -> Result<&Self, DomainError>;
+  /// Void a payment by reversing its remaining amount
+  fn void_payment(
+    &mut self,
+    payment_id: String,
+    reversal_id: String,
+    reason: String,
+    created_by: u32,
+  ) -> Result<&Self, DomainError>;
+  /// Get the not yet reversed amount of a payment
+  fn get_payment_reversible_amount(&self, payment_id: &str) -> Result<i32, DomainError>;
   /// Set owner to cart
   fn set_owner(&mut self, owner_uid: u32) -> &Self;
   /// Set store id; where the cart physically located
@@ -52,22 +158,62 @@ where
   fn get_balance(&self) -> i32;
   /// Get cart current profit
   fn get_profit_net(&self) -> i32;
+  /// Compute totals (net, vat, gross)
+  /// from items and discounts
+  fn compute_totals(&self) -> (u32, u32, u32);
+  /// Net, VAT and gross subtotals per VAT rate
+  fn compute_vat_totals(&self) -> Vec<VatTotal>;
   // Recalculate totals
   fn calculate_totals(&mut self);
   /// Close cart.sum::<i32>()
   /// If
   ///   SKU / UPL ok
   ///   Payment OK (Cash / Card and Payment OK)
-  fn close_cart(&mut self) -> Result<&Self, String>;
+  fn close_cart(&mut self) -> Result<&Self, DomainError>;
+  /// Collect every issue blocking cart close
+  /// Empty if the cart can be closed
+  fn validate_close(&self) -> Vec<CloseIssue>;
+  /// Check if cart can be removed
+  /// If
+  ///   No payment on it
+  ///   No burned loyalty points on it
+  fn check_removable(&self) -> Result<&Self, DomainError>;
+  /// Park cart with a label
+  /// while the customer steps away
+  fn park(&mut self, label: String, parked_by: u32) -> Result<&Self, DomainError>;
+  /// Resume parked cart
+  /// the resuming shop assistant takes ownership
+  fn resume(&mut self, owner_uid: u32) -> Result<&Self, DomainError>;
+  /// Check if cart is parked longer than max_parked
+  fn is_parked_overdue(&self, max_parked: Duration) -> bool;
+  /// Check if cart has no recorded event for longer than max_idle
+  fn is_idle(&self, max_idle: Duration) -> bool;
+  /// Get all the UPL IDs reserved by the cart
+  fn get_upl_ids(&self) -> Vec<String>;
+  /// Turn cart into a pre-order
+  /// SKUs can be sold without UPLs till the stock arrives
+  fn set_preorder(
+    &mut self,
+    expected_arrival: DateTime<Utc>,
+    deposit: i32,
+    created_by: u32,
+  ) -> Result<&Self, DomainError>;
+  /// Turn pre-order back into a normal cart
+  fn remove_preorder(&mut self) -> Result<&Self, DomainError>;
+  /// Get the deposit amount not paid yet
+  fn get_deposit_due(&self) -> i32;
+  /// Mark pre-order stock as arrived
+  /// UPLs then can be attached and the rest settled
+  fn fulfil_preorder(&mut self, fulfilled_by: u32) -> Result<&Self, DomainError>;
   /// Add loyalty card to the cart
   fn add_loyalty_card(
     &mut self,
     account_id: Uuid,
     card_id: String,
     loyalty_level: LoyaltyLevel,
-  ) -> Result<&Self, String>;
+  ) -> Result<&Self, DomainError>;
   /// Try to remove loyalty card
-  fn remove_loyalty_card(&mut self) -> Result<&Self, String>;
+  fn remove_loyalty_card(&mut self) -> Result<&Self, DomainError>;
   /// Get burned loyalty points balance
   fn get_burned_points_balance(&self) -> u32;
   /// Burn points
@@ -76,15 +222,48 @@ where
     loyalty_account_id: Uuid,
     transaction_id: Uuid,
     points_to_burn: i32,
-  ) -> Result<&Self, String>;
+  ) -> Result<&Self, DomainError>;
   /// Add commitment to cart
   fn add_commitment(
     &mut self,
     commitment_id: Uuid,
     discount_percentage: u32,
-  ) -> Result<&Self, String>;
+  ) -> Result<&Self, DomainError>;
   /// Remove commitment from cart
-  fn remove_commitment(&mut self) -> Result<&Self, String>;
+  fn remove_commitment(&mut self) -> Result<&Self, DomainError>;
+  /// Override the unit price of a shopping list item
+  fn set_sku_price_override(
+    &mut self,
+    sku: u32,
+    unit_price_net: u32,
+    unit_price_gross: u32,
+    reason: PriceOverrideReason,
+    authorized_by: u32,
+  ) -> Result<&Self, DomainError>;
+  /// Remove price override from a shopping list item
+  fn remove_sku_price_override(&mut self, sku: u32) -> Result<&Self, DomainError>;
+  /// Override the price of a unique UPL
+  fn set_upl_price_override(
+    &mut self,
+    upl_id: String,
+    price_net: u32,
+    price_gross: u32,
+    reason: PriceOverrideReason,
+    authorized_by: u32,
+  ) -> Result<&Self, DomainError>;
+  /// Remove price override from a unique UPL
+  fn remove_upl_price_override(&mut self, upl_id: String) -> Result<&Self, DomainError>;
+  /// Set the promotion rules the cart is evaluated against
+  /// Cart keeps this snapshot, new promotions apply after a refresh only
+  /// The rules are evaluated at the current time again
+  fn set_promotions(&mut self, promotions: Vec<Promotion>) -> &Self;
+  /// Remove a deleted promotion rule from the snapshot
+  /// Carts with a fixed promotion time (restored ones) keep it
+  fn remove_promotion(&mut self, promotion_id: Uuid) -> &Self;
+  /// Get the applied promotions net discount total
+  fn get_promotion_discount_net(&self) -> u32;
+  /// Get the applied promotions gross discount total
+  fn get_promotion_discount_gross(&self) -> u32;
   /// Get the given discount based on the commitment
   fn get_commitment_discount_value(&self) -> u32;
   fn get_items_total_net(&self) -> u32;
@@ -92,7 +271,7 @@ where
   fn get_items_total_vat(&self) -> u32;
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Copy)]
+#[derive(Serialize, Deserialize, Clone, Debug, Copy, PartialEq)]
 pub enum VAT {
   AAM,
   FAD,
@@ -109,7 +288,7 @@ impl Default for VAT {
 }
 
 impl VAT {
-  pub fn from_str(str: &str) -> Result<VAT, String> {
+  pub fn from_str(str: &str) -> Result<VAT, DomainError> {
     match str {
       "AAM" => Ok(VAT::AAM),
       "aam" => Ok(VAT::AAM),
@@ -120,9 +299,31 @@ impl VAT {
       "5" => Ok(VAT::_5),
       "18" => Ok(VAT::_18),
       "27" => Ok(VAT::_27),
-      _ => Err("Nem megfelelő Áfa formátum! 5, 18, 27, AAM, TAM, FAD".into()),
+      _ => Err(DomainError::InvalidVat),
     }
   }
+  /// VAT rate in percent, 0 for the exempt kinds
+  pub fn rate(&self) -> u32 {
+    match self {
+      VAT::AAM | VAT::FAD | VAT::TAM => 0,
+      VAT::_5 => 5,
+      VAT::_18 => 18,
+      VAT::_27 => 27,
+    }
+  }
+  /// VAT of a net amount, rounded half up
+  pub fn vat_of(&self, net: u32) -> u32 {
+    percent_of(net, self.rate())
+  }
+  /// Gross of a net amount
+  /// Always net + vat_of(net), so the parts add up exactly
+  pub fn gross_of(&self, net: u32) -> u32 {
+    net + self.vat_of(net)
+  }
+  /// Net part of a gross amount, rounded half up
+  pub fn net_of(&self, gross: u32) -> u32 {
+    div_round(gross as u64 * 100, 100 + self.rate() as u64) as u32
+  }
 }
 
 impl ToString for VAT {
@@ -142,15 +343,43 @@ impl Mul<VAT> for u32 {
   type Output = u32;
 
   fn mul(self, rhs: VAT) -> Self::Output {
-    let res = match rhs {
-      VAT::AAM => self as f32 * 1.0,
-      VAT::FAD => self as f32 * 1.0,
-      VAT::TAM => self as f32 * 1.0,
-      VAT::_5 => self as f32 * 1.05,
-      VAT::_18 => self as f32 * 1.18,
-      VAT::_27 => self as f32 * 1.27,
+    rhs.gross_of(self)
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct VatTotal {
+  pub vat: VAT,         // VAT rate
+  pub total_net: u32,   // Net subtotal
+  pub total_vat: u32,   // VAT subtotal
+  pub total_gross: u32, // Gross subtotal
+}
+
+impl Default for VatTotal {
+  fn default() -> Self {
+    Self {
+      vat: VAT::default(),
+      total_net: 0,
+      total_vat: 0,
+      total_gross: 0,
+    }
+  }
+}
+
+impl VatTotal {
+  // Get the group of the given VAT, create it if not exist
+  pub(crate) fn find_or_insert(totals: &mut Vec<VatTotal>, vat: VAT) -> &mut VatTotal {
+    let pos = match totals.iter().position(|t| t.vat == vat) {
+      Some(pos) => pos,
+      None => {
+        totals.push(VatTotal {
+          vat,
+          ..VatTotal::default()
+        });
+        totals.len() - 1
+      }
     };
-    res.round() as u32
+    &mut totals[pos]
   }
 }
 
@@ -162,12 +391,16 @@ pub struct Cart {
   pub commitment: Option<Commitment>,         // Applied customer commitment
   pub commitment_discount_value: u32,         // Commitment value
   pub loyalty_card: Option<LoyaltyCard>,      // Applied loyalty card
+  pub promotions: Vec<Promotion>,             // Promotion rules to evaluate
+  pub promotion_time: Option<DateTime<Utc>>,  // Some(_) if promotions are evaluated at a fixed time
+  pub promotion_lines: Vec<PromotionLine>,    // Promotion discount lines
   pub shopping_list: Vec<ListItem>,           // Shopping list
   pub upls_sku: Vec<UplInfoObject>,           // UPLs that are healty
   pub upls_unique: Vec<UplInfoObject>,        // Upls that are depreciated or opened
   pub total_net: u32,                         // Total cart net value in HUF
   pub total_vat: u32,                         // Total VAT
   pub total_gross: u32,                       // Total cart gross value in HUF
+  pub vat_totals: Vec<VatTotal>,              // Totals per VAT rate
   pub document_kind: DocumentKind,            // Receipt or Invoice
   pub payment_kind: PaymentKind,              // cash, transfer, card
   pub payments: Vec<Payment>,                 // Payment vector
@@ -177,8 +410,14 @@ pub struct Cart {
   pub store_id: Option<u32>,                  // Now its stock ID
   pub date_completion: DateTime<Utc>,         // Invoice Completion date
   pub payment_duedate: DateTime<Utc>,         // Invoice Payment duedate
+  pub payment_duedate_explicit: bool,         // True if duedate is set explicitly
+  pub parked: Option<ParkInfo>,               // Some(_) if cart is parked
+  pub preorder: Option<Preorder>,             // Some(_) if cart is a pre-order
   pub created_by: u32,                        // UID
   pub created_at: DateTime<Utc>,              // When cart created
+  pub updated_at: DateTime<Utc>,              // When cart last modified
+  pub version: u32,                           // Increased by every modification
+  pub events: Vec<CartEvent>,                 // Audit trail of modifications
 }
 
 impl Default for Cart {
@@ -190,12 +429,16 @@ impl Default for Cart {
       commitment: None,
       commitment_discount_value: 0,
       loyalty_card: None,
+      promotions: Vec::new(),
+      promotion_time: None,
+      promotion_lines: Vec::new(),
       shopping_list: Vec::new(),
       upls_sku: Vec::new(),
       upls_unique: Vec::new(),
       total_net: 0,
       total_vat: 0,
       total_gross: 0,
+      vat_totals: Vec::new(),
       document_kind: DocumentKind::default(),
       payment_kind: PaymentKind::default(),
       payments: Vec::default(),
@@ -205,12 +448,65 @@ impl Default for Cart {
       store_id: None,
       date_completion: Utc::today().and_hms(0, 0, 0),
       payment_duedate: Utc::today().and_hms(0, 0, 0),
+      payment_duedate_explicit: false,
+      parked: None,
+      preorder: None,
+      created_by: 0,
+      created_at: Utc::now(),
+      updated_at: Utc::now(),
+      version: 1,
+      events: Vec::new(),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ParkInfo {
+  pub label: String,            // Label to find the cart by
+  pub parked_by: u32,           // UID
+  pub parked_at: DateTime<Utc>, // When cart parked
+}
+
+impl Default for ParkInfo {
+  fn default() -> Self {
+    Self {
+      label: String::default(),
+      parked_by: 0,
+      parked_at: Utc::now(),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Preorder {
+  pub expected_arrival: DateTime<Utc>,     // When stock is expected
+  pub deposit: i32,                        // Deposit to pay in advance
+  pub created_by: u32,                     // UID
+  pub created_at: DateTime<Utc>,           // When pre-order created
+  pub fulfilled_by: Option<u32>,           // UID who marked it arrived
+  pub fulfilled_at: Option<DateTime<Utc>>, // Some(_) if stock arrived
+}
+
+impl Default for Preorder {
+  fn default() -> Self {
+    Self {
+      expected_arrival: Utc::now(),
+      deposit: 0,
       created_by: 0,
       created_at: Utc::now(),
+      fulfilled_by: None,
+      fulfilled_at: None,
     }
   }
 }
 
+impl Preorder {
+  /// Check if stock arrived
+  pub fn is_fulfilled(&self) -> bool {
+    self.fulfilled_at.is_some()
+  }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Commitment {
   pub commitment_id: Uuid,
@@ -257,11 +553,11 @@ impl ToString for LoyaltyLevel {
 }
 
 impl LoyaltyLevel {
-  pub fn from_str(str: &str) -> Result<Self, String> {
+  pub fn from_str(str: &str) -> Result<Self, DomainError> {
     match str {
       "l1" | "L1" => Ok(Self::L1),
       "l2" | "L2" => Ok(Self::L2),
-      _ => Err("Ismeretlen kedvezmény kártya szint. L1 | L2".to_string()),
+      _ => Err(DomainError::InvalidLoyaltyLevel),
     }
   }
 }
@@ -324,6 +620,7 @@ pub struct ListItem {
   pub total_price_net: u32,
   pub total_price_vat: u32,
   pub total_price_gross: u32,
+  pub price_override: Option<PriceOverride>,
 }
 
 impl ListItem {
@@ -348,6 +645,7 @@ impl ListItem {
       total_price_net: total_net,
       total_price_vat: total_gross - total_net,
       total_price_gross: total_gross,
+      price_override: None,
     }
   }
   // Update piece by replacing it
@@ -363,11 +661,24 @@ impl ListItem {
     // Return self ref
     self
   }
+  // Update unit prices and recalculate totals
+  fn update_unit_price(&mut self, unit_price_net: u32, unit_price_gross: u32) -> &Self {
+    self.unit_price_net = unit_price_net;
+    self.unit_price_gross = unit_price_gross;
+    self.unit_price_vat = unit_price_gross - unit_price_net;
+    self.update_piece(self.piece);
+    self
+  }
   // Update piece by adding new ones
   fn update_add_piece(&mut self, plus_piece: u32) -> &Self {
     self.update_piece(self.piece + plus_piece);
     self
   }
+  // Update piece by taking some back
+  fn update_sub_piece(&mut self, minus_piece: u32) -> &Self {
+    self.update_piece(self.piece.saturating_sub(minus_piece));
+    self
+  }
   fn replace(&mut self, new_item: ListItem) -> &Self {
     let _ = std::mem::replace(self, new_item);
     self
@@ -387,10 +698,97 @@ impl Default for ListItem {
       total_price_net: 0,
       total_price_vat: 0,
       total_price_gross: 0,
+      price_override: None,
     }
   }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub enum PriceOverrideReason {
+  Damaged,
+  PriceMatch,
+  Other,
+}
+
+impl Default for PriceOverrideReason {
+  fn default() -> Self {
+    Self::Other
+  }
+}
+
+impl ToString for PriceOverrideReason {
+  fn to_string(&self) -> String {
+    match self {
+      PriceOverrideReason::Damaged => "DAMAGED".to_string(),
+      PriceOverrideReason::PriceMatch => "PRICE_MATCH".to_string(),
+      PriceOverrideReason::Other => "OTHER".to_string(),
+    }
+  }
+}
+
+impl PriceOverrideReason {
+  pub fn from_str(str: &str) -> Result<Self, DomainError> {
+    match str {
+      "damaged" | "DAMAGED" => Ok(Self::Damaged),
+      "price_match" | "PRICE_MATCH" => Ok(Self::PriceMatch),
+      "other" | "OTHER" => Ok(Self::Other),
+      _ => Err(DomainError::InvalidPriceOverrideReason),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PriceOverride {
+  pub original_price_net: u32,     // Price before override
+  pub original_price_gross: u32,   // Price before override
+  pub price_net: u32,              // Overridden price
+  pub price_gross: u32,            // Overridden price
+  pub reason: PriceOverrideReason, // Reason code
+  pub authorized_by: u32,          // UID
+  pub created_at: DateTime<Utc>,   // When price overridden
+}
+
+impl Default for PriceOverride {
+  fn default() -> Self {
+    Self {
+      original_price_net: 0,
+      original_price_gross: 0,
+      price_net: 0,
+      price_gross: 0,
+      reason: PriceOverrideReason::default(),
+      authorized_by: 0,
+      created_at: Utc::now(),
+    }
+  }
+}
+
+impl PriceOverride {
+  pub fn new(
+    original_price_net: u32,
+    original_price_gross: u32,
+    price_net: u32,
+    price_gross: u32,
+    reason: PriceOverrideReason,
+    authorized_by: u32,
+  ) -> Result<Self, DomainError> {
+    if price_net > price_gross {
+      return Err(DomainError::NetAboveGross);
+    }
+    if price_gross > original_price_gross {
+      return Err(DomainError::OverrideAboveOriginal);
+    }
+    Ok(Self {
+      original_price_net,
+      original_price_gross,
+      price_net,
+      price_gross,
+      reason,
+      authorized_by,
+      created_at: Utc::now(),
+    })
+  }
+}
+
 impl VecPackMember for Cart {
   type Out = Uuid;
 
@@ -399,6 +797,27 @@ impl VecPackMember for Cart {
   }
 }
 
+// Check if completion date is within the allowed range
+// returns the date at midnight
+fn check_date_completion(
+  date_completion: DateTime<Utc>,
+  limits: DateCompletionLimits,
+) -> Result<DateTime<Utc>, DomainError> {
+  let date_completion = date_completion.date().and_hms(0, 0, 0);
+  let today = Utc::today().and_hms(0, 0, 0);
+  if date_completion < today - Duration::days(limits.max_days_back) {
+    return Err(DomainError::DateCompletionTooEarly {
+      max_days: limits.max_days_back,
+    });
+  }
+  if date_completion > today + Duration::days(limits.max_days_forward) {
+    return Err(DomainError::DateCompletionTooLate {
+      max_days: limits.max_days_forward,
+    });
+  }
+  Ok(date_completion)
+}
+
 impl CartMethods for Cart {
   fn new(owner_uid: u32, store_id: Option<u32>, created_by: u32) -> Self {
     Self {
@@ -408,12 +827,16 @@ impl CartMethods for Cart {
       commitment: None,
       commitment_discount_value: 0,
       loyalty_card: None,
+      promotions: Vec::default(),
+      promotion_time: None,
+      promotion_lines: Vec::default(),
       shopping_list: Vec::default(),
       upls_sku: Vec::default(),
       upls_unique: Vec::default(),
       total_net: 0,
       total_vat: 0,
       total_gross: 0,
+      vat_totals: Vec::new(),
       document_kind: DocumentKind::Receipt,
       payment_kind: PaymentKind::Cash,
       payments: Vec::default(),
@@ -423,14 +846,106 @@ impl CartMethods for Cart {
       store_id,
       date_completion: Utc::today().and_hms(0, 0, 0),
       payment_duedate: Utc::today().and_hms(0, 0, 0),
+      payment_duedate_explicit: false,
+      parked: None,
+      preorder: None,
       created_by,
       created_at: Utc::now(),
+      updated_at: Utc::now(),
+      version: 1,
+      events: Vec::new(),
+    }
+  }
+
+  fn record<F>(&mut self, created_by: u32, kind: CartEventKind, f: F) -> Result<&Self, DomainError>
+  where
+    F: FnOnce(&mut Self) -> Result<(), DomainError>,
+  {
+    let before = self.get_event_totals();
+    f(self)?;
+    let after = self.get_event_totals();
+    self
+      .events
+      .push(CartEvent::new(kind, before, after, created_by));
+    // One version per accepted mutation
+    self.touch();
+    Ok(self)
+  }
+
+  fn check_version(&mut self, expected_version: u32) -> Result<&mut Self, DomainError> {
+    match expected_version == 0 || expected_version == self.version {
+      true => Ok(self),
+      false => Err(DomainError::VersionConflict {
+        expected: expected_version,
+        actual: self.version,
+      }),
+    }
+  }
+
+  fn undo_last(
+    &mut self,
+    created_by: u32,
+    transaction_id: Option<Uuid>,
+  ) -> Result<&Self, DomainError> {
+    let last = self
+      .events
+      .last()
+      .cloned()
+      .ok_or(DomainError::NothingToUndo)?;
+    let undo = CartEventKind::Undo { event_id: last.id };
+    match last.kind {
+      CartEventKind::AddUpl { upl_id } => {
+        self.record(created_by, undo, |c| c.undo_add_upl(upl_id).map(|_| ()))
+      }
+      CartEventKind::SetSkuPiece {
+        sku,
+        piece: _,
+        piece_before,
+      } => self.record(created_by, undo, |c| {
+        c.set_sku_piece(sku, piece_before).map(|_| ())
+      }),
+      CartEventKind::AddCommitment {
+        commitment_id: _,
+        previous,
+      } => self.record(created_by, undo, |c| match previous {
+        Some((commitment_id, percentage)) => {
+          c.add_commitment(commitment_id, percentage).map(|_| ())
+        }
+        None => c.remove_commitment().map(|_| ()),
+      }),
+      CartEventKind::BurnPoints {
+        transaction_id: burn_transaction_id,
+        points,
+      } => {
+        let transaction_id = transaction_id.ok_or(DomainError::UndoTransactionIdRequired)?;
+        let loyalty_account_id = self
+          .burned_points
+          .iter()
+          .find(|t| t.transaction_id == burn_transaction_id)
+          .map(|t| t.loyalty_account_id)
+          .ok_or(DomainError::UndoBurnNotFound)?;
+        self.record(created_by, undo, |c| {
+          c.burn_points(loyalty_account_id, transaction_id, -points)
+            .map(|_| ())
+        })
+      }
+      _ => Err(DomainError::NotUndoable),
+    }
+  }
+
+  fn get_event_totals(&self) -> EventTotals {
+    EventTotals {
+      total_net: self.total_net,
+      total_gross: self.total_gross,
+      payable: self.payable,
+      balance: self.get_balance(),
     }
   }
 
   fn add_customer(&mut self, customer: Option<Customer>) -> &Self {
     self.customer = customer;
-    self
+    // Payment term may depend on the customer
+    self.set_payment(self.payment_kind)
   }
 
   fn add_sku(
@@ -458,7 +973,13 @@ impl CartMethods for Cart {
       // If we found it, lets update it
       Some(p) => {
         if let Some(item) = self.shopping_list.get_mut(p) {
+          // Keep the authorized price override
+          let price_override = item.price_override.take();
           item.replace(new_sku);
+          if let Some(po) = price_override {
+            item.update_unit_price(po.price_net, po.price_gross);
+            item.price_override = Some(po);
+          }
         }
       }
       // Othwise lets push it
@@ -471,7 +992,7 @@ impl CartMethods for Cart {
     self
   }
 
-  fn remove_sku(&mut self, sku: u32) -> Result<&Self, String> {
+  fn remove_sku(&mut self, sku: u32) -> Result<&Self, DomainError> {
     // Check if there is any sku in upls
     if self
       .upls_sku
@@ -485,7 +1006,7 @@ impl CartMethods for Cart {
       .count()
       > 0
     {
-      return Err("Az adott SKU-hoz még van UPL a kosárban!".to_string());
+      return Err(DomainError::SkuHasUpls { sku });
     }
 
     // Remove from shopping_list
@@ -496,7 +1017,7 @@ impl CartMethods for Cart {
     Ok(self)
   }
 
-  fn set_sku_piece(&mut self, sku: u32, piece: u32) -> Result<&Self, String> {
+  fn set_sku_piece(&mut self, sku: u32, piece: u32) -> Result<&Self, DomainError> {
     for item in &mut self.shopping_list {
       if item.sku == sku {
         // Update sku piece if we found it
@@ -506,10 +1027,21 @@ impl CartMethods for Cart {
         return Ok(self);
       }
     }
-    Err("A kért SKU nem szerepel a kosárban!".to_string())
+    Err(DomainError::SkuNotInCart { sku })
   }
 
-  fn add_upl(&mut self, upl: UplInfoObject) -> Result<&Self, String> {
+  fn add_upl(&mut self, upl: UplInfoObject) -> Result<&Self, DomainError> {
+    // Expired UPL can be sold only as depreciated
+    // or with manager approval
+    if upl.is_expired() && !upl.depreciated && upl.expiry_authorized_by.is_none() {
+      return Err(DomainError::UplExpired {
+        upl_id: upl.upl_id.to_string(),
+      });
+    }
+    self.attach_upl(upl)
+  }
+
+  fn attach_upl(&mut self, upl: UplInfoObject) -> Result<&Self, DomainError> {
     // Check if UPL is in the SKU upls
     // If yes, then return error
     if self
@@ -518,7 +1050,9 @@ impl CartMethods for Cart {
       .find(|u| u.upl_id == upl.upl_id)
       .is_some()
     {
-      return Err("A kért UPL már a kosárban van!".to_string());
+      return Err(DomainError::UplAlreadyInCart {
+        upl_id: upl.upl_id.to_string(),
+      });
     }
 
     // Check if UPL is in the Unique upls
@@ -529,11 +1063,32 @@ impl CartMethods for Cart {
       .find(|u| u.upl_id == upl.upl_id)
       .is_some()
     {
-      return Err("A kért UPL már a kosárban van!".to_string());
+      return Err(DomainError::UplAlreadyInCart {
+        upl_id: upl.upl_id.to_string(),
+      });
+    }
+
+    // Pre-ordered SKUs are already on the shopping list
+    // arriving UPLs are attached to them without adding pieces
+    if let UplKind::Sku { sku, piece } = upl.kind {
+      if self.preorder.is_some() && !upl.depreciated {
+        let ordered = self
+          .shopping_list
+          .iter()
+          .find(|i| i.sku == sku)
+          .map(|i| i.piece)
+          .unwrap_or(0);
+        if self.get_upl_piece(sku) + piece > ordered {
+          return Err(DomainError::UplOverPreorder { sku });
+        }
+        self.upls_sku.push(upl);
+        self.calculate_totals();
+        return Ok(self);
+      }
     }
 
     match upl.kind {
-      UplKind::Sku { sku, piece } => {
+      UplKind::Sku { .. } => {
         // Add to unique UPLs
         if upl.depreciated {
           self.upls_unique.push(upl.clone());
@@ -542,7 +1097,7 @@ impl CartMethods for Cart {
         }
       }
       // Add to unique UPLs
-      UplKind::DerivedProduct { product_id, amount } => self.upls_unique.push(upl.clone()),
+      UplKind::DerivedProduct { .. } => self.upls_unique.push(upl.clone()),
     }
 
     // Add it as a SKU if a normal SKU
@@ -560,7 +1115,7 @@ impl CartMethods for Cart {
           sku,
           upl.name.clone(),
           piece,
-          upl.vat.clone(),
+          upl.vat,
           upl.retail_net_price,
           upl.retail_gross_price,
         ));
@@ -571,7 +1126,7 @@ impl CartMethods for Cart {
     Ok(self)
   }
 
-  fn remove_upl(&mut self, upl_id: String) -> Result<&Self, String> {
+  fn remove_upl(&mut self, upl_id: String) -> Result<&Self, DomainError> {
     // Remove from Upl Sku if its there
     self.upls_sku.retain(|u| u.upl_id != upl_id);
     // Remove from Upl Unique if its there
@@ -582,6 +1137,46 @@ impl CartMethods for Cart {
     Ok(self)
   }
 
+  fn undo_add_upl(&mut self, upl_id: String) -> Result<&Self, DomainError> {
+    let upl = self
+      .upls_sku
+      .iter()
+      .chain(self.upls_unique.iter())
+      .find(|u| u.upl_id == upl_id)
+      .cloned()
+      .ok_or(DomainError::UplNotInCart {
+        upl_id: upl_id.to_string(),
+      })?;
+
+    // Take back the piece add_upl added to the shopping list
+    // and remove the SKU if nothing left
+    // Pre-order UPLs added no pieces
+    if let UplKind::Sku { sku, piece } = upl.kind {
+      if !upl.depreciated && self.preorder.is_none() {
+        for item in &mut self.shopping_list {
+          if item.sku == sku {
+            item.update_sub_piece(piece);
+          }
+        }
+        self.shopping_list.retain(|i| i.sku != sku || i.piece > 0);
+      }
+    }
+
+    self.remove_upl(upl_id)
+  }
+
+  fn get_upl_piece(&self, sku: u32) -> u32 {
+    self
+      .upls_sku
+      .iter()
+      .filter(|u| match u.kind {
+        UplKind::Sku { sku: upl_sku, .. } => upl_sku == sku,
+        _ => false,
+      })
+      .map(|u| u.get_piece())
+      .sum::<u32>()
+  }
+
   fn set_document(&mut self, document_kind: DocumentKind) -> &Self {
     // Set DocumentKind
     self.document_kind = document_kind;
@@ -592,15 +1187,70 @@ impl CartMethods for Cart {
     self.payments.iter().map(|p| p.amount).sum()
   }
 
+  fn get_payment_term(&self) -> u32 {
+    match &self.customer {
+      Some(c) if c.payment_term > 0 => c.payment_term,
+      _ => DEFAULT_PAYMENT_TERM,
+    }
+  }
+
+  fn set_date_completion(
+    &mut self,
+    date_completion: DateTime<Utc>,
+    limits: DateCompletionLimits,
+  ) -> Result<&Self, DomainError> {
+    self.date_completion = check_date_completion(date_completion, limits)?;
+    // Explicit duedate cannot be before the new completion date
+    if self.payment_duedate < self.date_completion {
+      self.payment_duedate_explicit = false;
+    }
+    // Set payment duedate by the new completion date
+    Ok(self.set_payment(self.payment_kind))
+  }
+
+  fn set_invoice_dates(
+    &mut self,
+    date_completion: DateTime<Utc>,
+    payment_duedate: DateTime<Utc>,
+    limits: DateCompletionLimits,
+  ) -> Result<&Self, DomainError> {
+    let date_completion = check_date_completion(date_completion, limits)?;
+    let payment_duedate = payment_duedate.date().and_hms(0, 0, 0);
+    if payment_duedate < date_completion {
+      return Err(DomainError::DuedateBeforeCompletion);
+    }
+    if self.payment_kind != PaymentKind::Transfer && payment_duedate != date_completion {
+      return Err(DomainError::DuedateOnlyForTransfer);
+    }
+    self.date_completion = date_completion;
+    self.payment_duedate = payment_duedate;
+    self.payment_duedate_explicit = true;
+    Ok(self)
+  }
+
+  fn get_payment_total_by_kind(&self, payment_kind: PaymentKind) -> i32 {
+    self
+      .payments
+      .iter()
+      .filter(|p| p.kind == payment_kind)
+      .map(|p| p.amount)
+      .sum()
+  }
+
   fn set_payment(&mut self, payment_kind: PaymentKind) -> &Self {
     // Set payment duedate
     match &payment_kind {
-      // Set 30 days if transfer
+      // Keep explicitly set duedate if transfer
+      PaymentKind::Transfer if self.payment_duedate_explicit => (),
+      // Set payment term days if transfer
       PaymentKind::Transfer => {
-        self.payment_duedate = Utc::today().and_hms(0, 0, 0) + Duration::days(30)
+        self.payment_duedate = self.date_completion + Duration::days(self.get_payment_term() as i64)
+      }
+      // Completion date if cash or card or else
+      _ => {
+        self.payment_duedate = self.date_completion;
+        self.payment_duedate_explicit = false;
       }
-      // Today if cash or card or else
-      _ => self.payment_duedate = Utc::today().and_hms(0, 0, 0),
     }
 
     // TODO! Maybe some validation before set new value?
@@ -611,9 +1261,108 @@ impl CartMethods for Cart {
 
   fn add_payment(&mut self, payment: Payment) -> &Self {
     self.payments.push(payment);
+    // Payable depends on the cash part
+    self.calculate_totals();
     self
   }
 
+  fn add_cash_payment(&mut self, payment_id: String, tendered: i32) -> Result<&Self, DomainError> {
+    if tendered <= 0 {
+      return Err(DomainError::InvalidTendered);
+    }
+    // Cash due is always rounded
+    let cash_due = crate::rounding::round_huf(self.get_balance());
+    if cash_due <= 0 {
+      return Err(DomainError::NothingToPay);
+    }
+    let amount = tendered.min(cash_due);
+    self.add_payment(Payment {
+      payment_id,
+      amount,
+      kind: PaymentKind::Cash,
+      tendered,
+      change: tendered - amount,
+      reversal: None,
+    });
+    Ok(self)
+  }
+
+  fn get_payment_reversible_amount(&self, payment_id: &str) -> Result<i32, DomainError> {
+    let payment = self
+      .payments
+      .iter()
+      .find(|p| p.payment_id == payment_id)
+      .ok_or(DomainError::PaymentNotFound {
+        payment_id: payment_id.to_string(),
+      })?;
+    if payment.reversal.is_some() {
+      return Err(DomainError::PaymentIsReversal {
+        payment_id: payment_id.to_string(),
+      });
+    }
+    let reversed = self
+      .payments
+      .iter()
+      .filter(|p| match &p.reversal {
+        Some(r) => r.payment_id == payment_id,
+        None => false,
+      })
+      .map(|p| p.amount)
+      .sum::<i32>();
+    Ok(payment.amount + reversed)
+  }
+
+  fn reverse_payment(
+    &mut self,
+    payment_id: String,
+    reversal_id: String,
+    amount: i32,
+    reason: String,
+    created_by: u32,
+  ) -> Result<&Self, DomainError> {
+    if self.payments.iter().any(|p| p.payment_id == reversal_id) {
+      return Err(DomainError::ReversalIdExists { reversal_id });
+    }
+    let reversible = self.get_payment_reversible_amount(&payment_id)?;
+    if amount <= 0 || amount > reversible {
+      return Err(DomainError::ReversalAmountOutOfRange { max: reversible });
+    }
+    let kind = self
+      .payments
+      .iter()
+      .find(|p| p.payment_id == payment_id)
+      .map(|p| p.kind)
+      .unwrap_or_default();
+    self.add_payment(Payment {
+      payment_id: reversal_id,
+      amount: -amount,
+      kind,
+      tendered: 0,
+      change: 0,
+      reversal: Some(PaymentReversal {
+        payment_id,
+        reason,
+        created_by,
+        created_at: Utc::now(),
+      }),
+    });
+    Ok(self)
+  }
+
+  fn void_payment(
+    &mut self,
+    payment_id: String,
+    reversal_id: String,
+    reason: String,
+    created_by: u32,
+  ) -> Result<&Self, DomainError> {
+    let reversible = self.get_payment_reversible_amount(&payment_id)?;
+    if reversible <= 0 {
+      return Err(DomainError::PaymentAlreadyReversed { payment_id });
+    }
+    self.reverse_payment(payment_id, reversal_id, reversible, reason, created_by)
+  }
+
   fn set_owner(&mut self, owner_uid: u32) -> &Self {
     self.owner_uid = owner_uid;
     self
@@ -646,72 +1395,108 @@ impl CartMethods for Cart {
           .sum::<i32>())
   }
 
-  fn close_cart(&mut self) -> Result<&Self, String> {
+  fn validate_close(&self) -> Vec<CloseIssue> {
+    let mut issues: Vec<CloseIssue> = Vec::new();
+
+    // Parked cart must be resumed first
+    if self.parked.is_some() {
+      issues.push(CloseIssue::Parked);
+    }
+
+    // Pre-order can be closed only when its stock arrived
+    if let Some(preorder) = &self.preorder {
+      if !preorder.is_fulfilled() {
+        issues.push(CloseIssue::PreorderNotFulfilled);
+      }
+    }
+
     // Check if document_kind::Invoice but no customer added
-    // return error
     if let DocumentKind::Invoice = self.document_kind {
       if self.customer.is_none() {
-        return Err(
-          "A kosár nem zárható le! Számlaigény van beállítva, de a vásárló üres!".to_string(),
-        );
+        issues.push(CloseIssue::MissingCustomer);
       }
     }
 
     // Check items count match
     for item in &self.shopping_list {
-      if item.piece
-        != self
-          .upls_sku
-          .iter()
-          .filter(|u| {
-            if let UplKind::Sku { sku, piece } = u.kind {
-              return sku == item.sku;
-            }
-            false
-          })
-          .map(|u| u.get_piece())
-          .sum::<u32>()
-      {
-        return Err("Rendezd a kosarat! Minden SKU-hoz UPL-t kell rendelni!".to_string());
+      let upl_piece = self.get_upl_piece(item.sku);
+      if item.piece != upl_piece {
+        issues.push(CloseIssue::SkuUplMismatch {
+          sku: item.sku,
+          name: item.name.to_string(),
+          list_piece: item.piece,
+          upl_piece,
+        });
       }
     }
+
     // Check totals
-    let _total_net = self.get_items_total_net()
-      - ((self.get_commitment_discount_value() + self.get_burned_points_balance()) as f32 / 1.27)
-        .round() as u32;
-    let _total_gross = self.get_items_total_gross()
-      - self.get_commitment_discount_value()
-      - self.get_burned_points_balance();
-    let _total_vat = _total_gross - _total_net;
-
-    if _total_net != self.total_net
-      || _total_vat != self.total_vat
-      || _total_gross != self.total_gross
-    {
-      return Err("A kosár záró összegei nem helyesek! Nem lehet lezárni!".to_string());
+    let (_total_net, _total_vat, _total_gross) = self.compute_totals();
+    for (figure, stored, computed) in [
+      ("total_net", self.total_net, _total_net),
+      ("total_vat", self.total_vat, _total_vat),
+      ("total_gross", self.total_gross, _total_gross),
+    ] {
+      if stored != computed {
+        issues.push(CloseIssue::TotalsMismatch {
+          figure: figure.to_string(),
+          stored,
+          computed,
+        });
+      }
     }
-    // Check payment
-    match self.payment_kind {
-      PaymentKind::Cash => {
-        if self.get_balance() != 0 {
-          return Err(
-            "A kosár nem zárható le, készpénzes fizetés esetén rendezze a befizetést!".to_string(),
-          );
-        }
+
+    // Check payments per method
+    // no method can be paid back more than it was paid
+    for payment_kind in [PaymentKind::Cash, PaymentKind::Card, PaymentKind::Transfer] {
+      let total = self.get_payment_total_by_kind(payment_kind);
+      if total < 0 {
+        issues.push(CloseIssue::PaymentNegative {
+          payment_kind,
+          total,
+        });
       }
-      PaymentKind::Card => {
-        if self.get_balance() != 0 {
-          return Err(
-            "A kosár nem zárható le, bankkártyás fizetés esetén rendezze a befizetést!".to_string(),
-          );
-        }
+    }
+    let cash_total = self.get_payment_total_by_kind(PaymentKind::Cash);
+    if cash_total != crate::rounding::round_huf(cash_total) {
+      issues.push(CloseIssue::CashNotRounded { cash_total });
+    }
+    if let DocumentKind::Receipt = self.document_kind {
+      if self.get_payment_total_by_kind(PaymentKind::Transfer) != 0
+        || self.payment_kind == PaymentKind::Transfer
+      {
+        issues.push(CloseIssue::TransferNeedsInvoice);
       }
-      PaymentKind::Transfer => match self.document_kind {
-        DocumentKind::Receipt => {
-          return Err("A kosár nem zárható le, átutalás esetén kötelező számlát kérni!".to_string())
+    }
+
+    // Check the balance
+    // payments cannot exceed the payable by any method
+    let balance = self.get_balance();
+    if balance < 0 {
+      issues.push(CloseIssue::PaymentOverpaid { balance });
+    }
+
+    // Check the remaining part by the cart payment method
+    // Only transfer can leave it open, the invoice settles it later
+    match self.payment_kind {
+      PaymentKind::Cash | PaymentKind::Card => {
+        if balance > 0 {
+          issues.push(CloseIssue::PaymentUnbalanced {
+            payment_kind: self.payment_kind,
+            balance,
+          });
         }
-        DocumentKind::Invoice => (),
-      },
+      }
+      PaymentKind::Transfer => (),
+    }
+
+    issues
+  }
+
+  fn close_cart(&mut self) -> Result<&Self, DomainError> {
+    // Refuse closing by the first blocking issue
+    if let Some(issue) = self.validate_close().into_iter().next() {
+      return Err(DomainError::NotClosable(issue));
     }
 
     // Return self as ref
@@ -720,23 +1505,232 @@ impl CartMethods for Cart {
     Ok(self)
   }
 
-  fn calculate_totals(&mut self) {
-    // Set new total net
-    self.total_net = self.get_items_total_net()
-      - ((self.get_commitment_discount_value() + self.get_burned_points_balance()) as f32 / 1.27)
-        .round() as u32;
+  fn park(&mut self, label: String, parked_by: u32) -> Result<&Self, DomainError> {
+    if self.parked.is_some() {
+      return Err(DomainError::AlreadyParked);
+    }
+    if label.trim().is_empty() {
+      return Err(DomainError::ParkLabelRequired);
+    }
+    self.parked = Some(ParkInfo {
+      label: label.trim().to_string(),
+      parked_by,
+      parked_at: Utc::now(),
+    });
+    Ok(self)
+  }
+
+  fn resume(&mut self, owner_uid: u32) -> Result<&Self, DomainError> {
+    if self.parked.is_none() {
+      return Err(DomainError::NotParked);
+    }
+    self.parked = None;
+    self.owner_uid = owner_uid;
+    Ok(self)
+  }
+
+  fn is_parked_overdue(&self, max_parked: Duration) -> bool {
+    match &self.parked {
+      Some(p) => p.parked_at + max_parked < Utc::now(),
+      None => false,
+    }
+  }
+
+  fn is_idle(&self, max_idle: Duration) -> bool {
+    // Pre-orders are waiting for the stock, not idle
+    if self.preorder.is_some() {
+      return false;
+    }
+    // Only recorded modifications count as activity
+    let last_activity = self
+      .events
+      .last()
+      .map(|e| e.created_at)
+      .unwrap_or(self.created_at);
+    last_activity + max_idle < Utc::now()
+  }
+
+  fn get_upl_ids(&self) -> Vec<String> {
+    self
+      .upls_sku
+      .iter()
+      .chain(self.upls_unique.iter())
+      .map(|u| u.upl_id.to_string())
+      .collect()
+  }
+
+  fn set_preorder(
+    &mut self,
+    expected_arrival: DateTime<Utc>,
+    deposit: i32,
+    created_by: u32,
+  ) -> Result<&Self, DomainError> {
+    if self.preorder.is_some() {
+      return Err(DomainError::AlreadyPreorder);
+    }
+    if self.customer.is_none() {
+      return Err(DomainError::PreorderCustomerRequired);
+    }
+    if self.shopping_list.is_empty() {
+      return Err(DomainError::PreorderEmptyCart);
+    }
+    if expected_arrival <= Utc::now() {
+      return Err(DomainError::ArrivalInPast);
+    }
+    if deposit <= 0 || deposit > self.get_payable() {
+      return Err(DomainError::DepositOutOfRange {
+        max: self.get_payable(),
+      });
+    }
+    self.preorder = Some(Preorder {
+      expected_arrival,
+      deposit,
+      created_by,
+      created_at: Utc::now(),
+      fulfilled_by: None,
+      fulfilled_at: None,
+    });
+    Ok(self)
+  }
+
+  fn remove_preorder(&mut self) -> Result<&Self, DomainError> {
+    match &self.preorder {
+      Some(preorder) => {
+        if preorder.is_fulfilled() {
+          return Err(DomainError::PreorderFulfilled);
+        }
+      }
+      None => return Err(DomainError::NotPreorder),
+    }
+    self.preorder = None;
+    Ok(self)
+  }
 
-    // Set new total gross
-    self.total_gross = self.get_items_total_gross()
-      - self.get_commitment_discount_value()
-      - self.get_burned_points_balance();
+  fn get_deposit_due(&self) -> i32 {
+    match &self.preorder {
+      Some(preorder) => (preorder.deposit - self.get_payment_total()).max(0),
+      None => 0,
+    }
+  }
+
+  fn fulfil_preorder(&mut self, fulfilled_by: u32) -> Result<&Self, DomainError> {
+    let deposit_due = self.get_deposit_due();
+    match &mut self.preorder {
+      Some(preorder) => {
+        if preorder.is_fulfilled() {
+          return Err(DomainError::PreorderFulfilled);
+        }
+        if deposit_due > 0 {
+          return Err(DomainError::DepositDue { due: deposit_due });
+        }
+        preorder.fulfilled_by = Some(fulfilled_by);
+        preorder.fulfilled_at = Some(Utc::now());
+      }
+      None => return Err(DomainError::NotPreorder),
+    }
+    Ok(self)
+  }
+
+  fn check_removable(&self) -> Result<&Self, DomainError> {
+    // Payments must be reversed before removing
+    if self.get_payment_total() != 0 {
+      return Err(DomainError::HasPayments);
+    }
+    // Burned points must be given back before removing
+    if self.get_burned_points_balance() != 0 {
+      return Err(DomainError::HasBurnedPoints);
+    }
+    Ok(self)
+  }
+
+  fn compute_totals(&self) -> (u32, u32, u32) {
+    // Totals are the sums of the VAT groups,
+    // so every discount is counted by the VAT of the discounted items
+    let vat_totals = self.compute_vat_totals();
+    let total_net = vat_totals.iter().map(|g| g.total_net).sum::<u32>();
+    let total_gross = vat_totals.iter().map(|g| g.total_gross).sum::<u32>();
+
+    (total_net, total_gross - total_net, total_gross)
+  }
 
-    // Set new total vat
-    self.total_vat = self.total_gross - self.total_net;
+  fn compute_vat_totals(&self) -> Vec<VatTotal> {
+    let mut res: Vec<VatTotal> = Vec::new();
+
+    // Item totals by their own VAT
+    for (vat, net, gross) in self
+      .shopping_list
+      .iter()
+      .map(|i| (i.vat, i.total_price_net, i.total_price_gross))
+      .chain(
+        self
+          .upls_unique
+          .iter()
+          .map(|u| (u.vat, u.get_price_net(), u.get_price_gross())),
+      )
+    {
+      let group = VatTotal::find_or_insert(&mut res, vat);
+      group.total_net += net;
+      group.total_gross += gross;
+    }
+
+    // Promotion discounts belong to the VAT of the discounted SKUs
+    // split by their gross value if they have different VAT rates
+    for line in &self.promotion_lines {
+      let items = self
+        .shopping_list
+        .iter()
+        .filter(|i| line.skus.contains(&i.sku))
+        .collect::<Vec<&ListItem>>();
+      let weights = items
+        .iter()
+        .map(|i| i.total_price_gross)
+        .collect::<Vec<u32>>();
+      let nets = allocate(line.discount_net, &weights);
+      let grosses = allocate(line.discount_gross, &weights);
+      for (k, item) in items.iter().enumerate() {
+        let group = VatTotal::find_or_insert(&mut res, item.vat);
+        group.total_net = group.total_net.saturating_sub(nets[k]);
+        group.total_gross = group.total_gross.saturating_sub(grosses[k]);
+      }
+    }
+
+    // Commitment and loyalty discounts are split between the VAT groups
+    // by their gross value, each net is reduced by the net part of its share
+    let discount_gross = self.get_commitment_discount_value() + self.get_burned_points_balance();
+    let weights = res.iter().map(|g| g.total_gross).collect::<Vec<u32>>();
+    for (group, share) in res.iter_mut().zip(allocate(discount_gross, &weights)) {
+      group.total_net = group.total_net.saturating_sub(group.vat.net_of(share));
+      group.total_gross = group.total_gross.saturating_sub(share);
+    }
+
+    for group in &mut res {
+      group.total_vat = group.total_gross.saturating_sub(group.total_net);
+    }
+    res.sort_by_key(|g| g.vat as u8);
+    res
+  }
+
+  fn calculate_totals(&mut self) {
+    // Evaluate promotions first
+    // as every other discount is based on the promoted price
+    self.promotion_lines = self.evaluate_promotions();
+
+    // Set new totals
+    let (total_net, total_vat, total_gross) = self.compute_totals();
+    self.total_net = total_net;
+    self.total_vat = total_vat;
+    self.total_gross = total_gross;
+    self.vat_totals = self.compute_vat_totals();
 
     // Set payable
+    // Cash rounding applies only to the part paid in cash;
+    // payment_kind is the method the remaining part is paid by
+    let non_cash = self.get_payment_total_by_kind(PaymentKind::Card)
+      + self.get_payment_total_by_kind(PaymentKind::Transfer);
     self.payable = match self.payment_kind {
-      PaymentKind::Cash => crate::rounding::round_huf(self.total_gross as i32),
+      PaymentKind::Cash => {
+        non_cash + crate::rounding::round_huf(self.total_gross as i32 - non_cash)
+      }
       _ => self.total_gross as i32,
     };
 
@@ -749,12 +1743,9 @@ impl CartMethods for Cart {
     account_id: Uuid,
     card_id: String,
     loyalty_level: LoyaltyLevel,
-  ) -> Result<&Self, String> {
+  ) -> Result<&Self, DomainError> {
     match &self.loyalty_card {
-      Some(card) => Err(
-        "A kosárhoz már van kedvezmény kártya rendelve! Törölje azt, mielőtt másikat adna hozzá!"
-          .to_string(),
-      ),
+      Some(_) => Err(DomainError::LoyaltyCardAlreadyAdded),
       None => {
         self.loyalty_card = Some(LoyaltyCard::new(account_id, card_id, loyalty_level));
         Ok(self)
@@ -762,9 +1753,9 @@ impl CartMethods for Cart {
     }
   }
 
-  fn remove_loyalty_card(&mut self) -> Result<&Self, String> {
+  fn remove_loyalty_card(&mut self) -> Result<&Self, DomainError> {
     if self.loyalty_card.is_none() {
-      return Err("A kosárhoz nincs kártya rendelve, így azt nem lehet törölni!".to_string());
+      return Err(DomainError::NoLoyaltyCard);
     }
     match self.get_burned_points_balance() == 0 {
       true => {
@@ -772,7 +1763,7 @@ impl CartMethods for Cart {
         self.loyalty_card = None;
         Ok(self)
       }
-      false => Err("Kártyát akkor lehet törölni, ha a felhasznált pontok összege 0. Törölje a felhasznált pontokat!".to_string())
+      false => Err(DomainError::LoyaltyCardHasBurnedPoints),
     }
   }
 
@@ -781,17 +1772,14 @@ impl CartMethods for Cart {
     loyalty_account_id: Uuid,
     transaction_id: Uuid,
     points_to_burn: i32,
-  ) -> Result<&Self, String> {
+  ) -> Result<&Self, DomainError> {
     // Check if we have enough points to remove
     // if we want to remove
     if points_to_burn < 0 {
       // If we want to get out more points that we have in
       // return error
-      if self.get_burned_points_balance() < points_to_burn.abs() as u32 {
-        return Err(
-          "Több pontot szeretnénk kivenni a kosárból, mint amennyit felhasználtunk hozzá!"
-            .to_string(),
-        );
+      if self.get_burned_points_balance() < points_to_burn.unsigned_abs() {
+        return Err(DomainError::BurnMoreThanBurned);
       }
     }
 
@@ -802,7 +1790,9 @@ impl CartMethods for Cart {
       .find(|tr| tr.transaction_id == transaction_id)
       .is_some()
     {
-      return Err("A kért tranzakció már a felhasznált pontok között szerepel a kosárban, így nem adható hozzá ismét!".to_string());
+      return Err(DomainError::BurnTransactionExists {
+        transaction_id: transaction_id.to_string(),
+      });
     }
 
     // Burn points
@@ -824,7 +1814,7 @@ impl CartMethods for Cart {
     &mut self,
     commitment_id: Uuid,
     commitment_percentage: u32,
-  ) -> Result<&Self, String> {
+  ) -> Result<&Self, DomainError> {
     // Create commitment object
     let new_commitment = Commitment::new(commitment_id, commitment_percentage);
     // Set commitment
@@ -835,11 +1825,9 @@ impl CartMethods for Cart {
     Ok(self)
   }
 
-  fn remove_commitment(&mut self) -> Result<&Self, String> {
+  fn remove_commitment(&mut self) -> Result<&Self, DomainError> {
     if self.commitment.is_none() {
-      return Err(
-        "A kosárhoz nincs hozzárendelt commitment, így azt nem lehet eltávolítani".to_string(),
-      );
+      return Err(DomainError::NoCommitment);
     }
     // Remove commitment
     self.commitment = None;
@@ -849,6 +1837,112 @@ impl CartMethods for Cart {
     Ok(self)
   }
 
+  fn set_sku_price_override(
+    &mut self,
+    sku: u32,
+    unit_price_net: u32,
+    unit_price_gross: u32,
+    reason: PriceOverrideReason,
+    authorized_by: u32,
+  ) -> Result<&Self, DomainError> {
+    for item in &mut self.shopping_list {
+      if item.sku == sku {
+        // Keep the very first original price
+        let (original_net, original_gross) = match &item.price_override {
+          Some(po) => (po.original_price_net, po.original_price_gross),
+          None => (item.unit_price_net, item.unit_price_gross),
+        };
+        let price_override = PriceOverride::new(
+          original_net,
+          original_gross,
+          unit_price_net,
+          unit_price_gross,
+          reason,
+          authorized_by,
+        )?;
+        item.update_unit_price(unit_price_net, unit_price_gross);
+        item.price_override = Some(price_override);
+        // Recalculate the cart totals
+        self.calculate_totals();
+        return Ok(self);
+      }
+    }
+    Err(DomainError::SkuNotInCart { sku })
+  }
+
+  fn remove_sku_price_override(&mut self, sku: u32) -> Result<&Self, DomainError> {
+    for item in &mut self.shopping_list {
+      if item.sku == sku {
+        match item.price_override.take() {
+          Some(po) => {
+            item.update_unit_price(po.original_price_net, po.original_price_gross);
+          }
+          None => return Err(DomainError::NoSkuPriceOverride { sku }),
+        }
+        // Recalculate the cart totals
+        self.calculate_totals();
+        return Ok(self);
+      }
+    }
+    Err(DomainError::SkuNotInCart { sku })
+  }
+
+  fn set_upl_price_override(
+    &mut self,
+    upl_id: String,
+    price_net: u32,
+    price_gross: u32,
+    reason: PriceOverrideReason,
+    authorized_by: u32,
+  ) -> Result<&Self, DomainError> {
+    // SKU UPLs are priced by the shopping list
+    if self.upls_sku.iter().any(|u| u.upl_id == upl_id) {
+      return Err(DomainError::UplPricedBySku { upl_id });
+    }
+    for upl in &mut self.upls_unique {
+      if upl.upl_id == upl_id {
+        // Keep the very first original price
+        let (original_net, original_gross) = match &upl.price_override {
+          Some(po) => (po.original_price_net, po.original_price_gross),
+          None => (upl.retail_net_price, upl.retail_gross_price),
+        };
+        let price_override = PriceOverride::new(
+          original_net,
+          original_gross,
+          price_net,
+          price_gross,
+          reason,
+          authorized_by,
+        )?;
+        upl.retail_net_price = price_net;
+        upl.retail_gross_price = price_gross;
+        upl.price_override = Some(price_override);
+        // Recalculate the cart totals
+        self.calculate_totals();
+        return Ok(self);
+      }
+    }
+    Err(DomainError::UplNotInCart { upl_id })
+  }
+
+  fn remove_upl_price_override(&mut self, upl_id: String) -> Result<&Self, DomainError> {
+    for upl in &mut self.upls_unique {
+      if upl.upl_id == upl_id {
+        match upl.price_override.take() {
+          Some(po) => {
+            upl.retail_net_price = po.original_price_net;
+            upl.retail_gross_price = po.original_price_gross;
+          }
+          None => return Err(DomainError::NoUplPriceOverride { upl_id }),
+        }
+        // Recalculate the cart totals
+        self.calculate_totals();
+        return Ok(self);
+      }
+    }
+    Err(DomainError::UplNotInCart { upl_id })
+  }
+
   fn get_burned_points_balance(&self) -> u32 {
     match self
       .burned_points
@@ -860,13 +1954,43 @@ impl CartMethods for Cart {
     }
   }
 
+  fn set_promotions(&mut self, promotions: Vec<Promotion>) -> &Self {
+    self.promotions = promotions;
+    self.promotion_time = None;
+    self.calculate_totals();
+    self
+  }
+
+  fn remove_promotion(&mut self, promotion_id: Uuid) -> &Self {
+    if self.promotion_time.is_none() && self.promotions.iter().any(|p| p.id == promotion_id) {
+      self.promotions.retain(|p| p.id != promotion_id);
+      self.calculate_totals();
+    }
+    self
+  }
+
+  fn get_promotion_discount_net(&self) -> u32 {
+    self
+      .promotion_lines
+      .iter()
+      .map(|p| p.discount_net)
+      .sum::<u32>()
+  }
+
+  fn get_promotion_discount_gross(&self) -> u32 {
+    self
+      .promotion_lines
+      .iter()
+      .map(|p| p.discount_gross)
+      .sum::<u32>()
+  }
+
   fn get_commitment_discount_value(&self) -> u32 {
     match &self.commitment {
-      Some(commitment) => {
-        return (self.get_items_total_gross() as f32
-          * (commitment.commitment_percentage as f32 / 100.0))
-          .round() as u32
-      }
+      Some(commitment) => percent_of(
+        self.get_items_total_gross() - self.get_promotion_discount_gross(),
+        commitment.commitment_percentage,
+      ),
       None => 0,
     }
   }
@@ -902,12 +2026,44 @@ impl CartMethods for Cart {
   }
 }
 
+impl Cart {
+  // Mark cart as modified
+  fn touch(&mut self) {
+    self.updated_at = Utc::now();
+    self.version += 1;
+  }
+  // Evaluate the active promotion rules against the shopping list
+  // Every SKU is discounted by one promotion at most
+  // Restored carts evaluate the rules at the time of their sale
+  fn evaluate_promotions(&self) -> Vec<PromotionLine> {
+    let now = self.promotion_time.unwrap_or_else(Utc::now);
+    let mut used_skus: Vec<u32> = Vec::new();
+    let mut res: Vec<PromotionLine> = Vec::new();
+    for promotion in self.promotions.iter().filter(|p| p.is_active(now)) {
+      if let Some(applied) = promotion.evaluate(&self.shopping_list, &used_skus) {
+        used_skus.extend(applied.skus.iter());
+        res.push(applied);
+      }
+    }
+    res
+  }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub enum DocumentKind {
   Receipt,
   Invoice,
 }
 
+impl ToString for DocumentKind {
+  fn to_string(&self) -> String {
+    match self {
+      DocumentKind::Receipt => "RECEIPT".to_string(),
+      DocumentKind::Invoice => "INVOICE".to_string(),
+    }
+  }
+}
+
 impl Default for DocumentKind {
   fn default() -> Self {
     Self::Receipt
@@ -922,6 +2078,7 @@ pub struct Customer {
   pub location: String,
   pub street: String,
   pub tax_number: String,
+  pub payment_term: u32, // Payment term in days; 0 if default
 }
 
 impl Default for Customer {
@@ -933,17 +2090,28 @@ impl Default for Customer {
       location: String::default(),
       street: String::default(),
       tax_number: String::default(),
+      payment_term: 0,
     }
   }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub enum PaymentKind {
   Cash,
   Card,
   Transfer,
 }
 
+impl ToString for PaymentKind {
+  fn to_string(&self) -> String {
+    match self {
+      PaymentKind::Cash => "CASH".to_string(),
+      PaymentKind::Card => "CARD".to_string(),
+      PaymentKind::Transfer => "TRANSFER".to_string(),
+    }
+  }
+}
+
 impl Default for PaymentKind {
   fn default() -> Self {
     Self::Cash
@@ -954,6 +2122,10 @@ impl Default for PaymentKind {
 pub struct Payment {
   pub payment_id: String,
   pub amount: i32,
+  pub kind: PaymentKind,
+  pub tendered: i32, // Cash handed over by the customer; 0 if not recorded
+  pub change: i32,   // Cash given back to the customer
+  pub reversal: Option<PaymentReversal>, // Some(_) if it reverses another payment
 }
 
 impl Default for Payment {
@@ -961,6 +2133,29 @@ impl Default for Payment {
     Self {
       payment_id: String::default(),
       amount: 0,
+      kind: PaymentKind::default(),
+      tendered: 0,
+      change: 0,
+      reversal: None,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PaymentReversal {
+  pub payment_id: String,        // Reversed payment ID
+  pub reason: String,            // Why it was reversed
+  pub created_by: u32,           // UID
+  pub created_at: DateTime<Utc>, // When payment reversed
+}
+
+impl Default for PaymentReversal {
+  fn default() -> Self {
+    Self {
+      payment_id: String::default(),
+      reason: String::default(),
+      created_by: 0,
+      created_at: Utc::now(),
     }
   }
 }
@@ -976,6 +2171,9 @@ pub struct UplInfoObject {
   pub procurement_net_price: u32,
   pub best_before: Option<DateTime<Utc>>,
   pub depreciated: bool,
+  pub price_override: Option<PriceOverride>,
+  pub unit: Option<Unit>, // DerivedProduct amount unit, retail prices are per unit
+  pub expiry_authorized_by: Option<u32>, // Manager UID allowing an expired UPL
 }
 
 impl Default for UplInfoObject {
@@ -990,6 +2188,9 @@ impl Default for UplInfoObject {
       procurement_net_price: 0,
       best_before: None,
       depreciated: false,
+      price_override: None,
+      unit: None,
+      expiry_authorized_by: None,
     }
   }
 }
@@ -1014,18 +2215,40 @@ impl UplInfoObject {
       _ => None,
     }
   }
+  /// Check if UPL is past its best before date
+  pub fn is_expired(&self) -> bool {
+    match self.best_before {
+      Some(bb) => bb < Utc::now(),
+      None => false,
+    }
+  }
+  /// Check if UPL expires within the given duration
+  pub fn is_near_expiry(&self, within: Duration) -> bool {
+    match self.best_before {
+      Some(bb) => bb < Utc::now() + within,
+      None => false,
+    }
+  }
   /// Get UPL price net
+  /// DerivedProduct is priced by its amount
   pub fn get_price_net(&self) -> u32 {
     match self.kind {
-      UplKind::Sku { sku, piece } => self.retail_net_price * piece,
-      UplKind::DerivedProduct { product_id, amount } => self.retail_net_price,
+      UplKind::Sku { sku: _, piece } => self.retail_net_price * piece,
+      UplKind::DerivedProduct {
+        product_id: _,
+        amount,
+      } => unit::derived_price(self.unit, self.retail_net_price, amount),
     }
   }
   /// Get UPL price gross
+  /// DerivedProduct is priced by its amount
   pub fn get_price_gross(&self) -> u32 {
     match self.kind {
-      UplKind::Sku { sku, piece } => self.retail_gross_price * piece,
-      UplKind::DerivedProduct { product_id, amount } => self.retail_gross_price,
+      UplKind::Sku { sku: _, piece } => self.retail_gross_price * piece,
+      UplKind::DerivedProduct {
+        product_id: _,
+        amount,
+      } => unit::derived_price(self.unit, self.retail_gross_price, amount),
     }
   }
   /// Get UPL price VAT
@@ -1033,13 +2256,24 @@ impl UplInfoObject {
     self.get_price_gross() - self.get_price_net()
   }
   /// Get piece
+  /// DerivedProduct measured by weight, volume or length is 1 piece
   pub fn get_piece(&self) -> u32 {
     match self.kind {
       UplKind::Sku { sku: _, piece } => piece,
       UplKind::DerivedProduct {
         product_id: _,
-        amount: _,
-      } => 1,
+        amount,
+      } => unit::derived_piece(self.unit, amount),
+    }
+  }
+  /// Get amount in unit
+  pub fn get_amount(&self) -> u32 {
+    match self.kind {
+      UplKind::Sku { sku: _, piece } => piece,
+      UplKind::DerivedProduct {
+        product_id: _,
+        amount,
+      } => amount,
     }
   }
 }
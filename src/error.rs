@@ -0,0 +1,253 @@
+// Typed domain errors of carts and purchases
+// Stable codes for the clients, human readable text as display layer
+// Texts are in the i18n message catalog
+
+use crate::i18n::{Locale, Localize};
+use crate::validation::CloseIssue;
+use std::fmt;
+
+#[derive(Clone)]
+pub enum DomainError {
+  // Parsing
+  InvalidVat,
+  InvalidLoyaltyLevel,
+  InvalidPriceOverrideReason,
+  InvalidUnit,
+  // Request
+  InvalidId { id: String },
+  InvalidDate { date: String },
+  InvalidPaymentKind,
+  InvalidDocumentKind,
+  MissingField { field: String },
+  VersionConflict { expected: u32, actual: u32 },
+  // Undo
+  NothingToUndo,
+  UndoTransactionIdRequired,
+  UndoBurnNotFound,
+  NotUndoable,
+  // Shopping list and UPLs
+  SkuHasUpls { sku: u32 },
+  SkuNotInCart { sku: u32 },
+  UplAlreadyInCart { upl_id: String },
+  UplNotInCart { upl_id: String },
+  UplExpired { upl_id: String },
+  UplPricedBySku { upl_id: String },
+  // Price overrides
+  NetAboveGross,
+  OverrideAboveOriginal,
+  NoSkuPriceOverride { sku: u32 },
+  NoUplPriceOverride { upl_id: String },
+  // Invoice dates
+  DateCompletionTooEarly { max_days: i64 },
+  DateCompletionTooLate { max_days: i64 },
+  DuedateBeforeCompletion,
+  DuedateOnlyForTransfer,
+  // Payments
+  InvalidTendered,
+  NothingToPay,
+  PaymentNotFound { payment_id: String },
+  PaymentIsReversal { payment_id: String },
+  ReversalIdExists { reversal_id: String },
+  ReversalAmountOutOfRange { max: i32 },
+  PaymentAlreadyReversed { payment_id: String },
+  // Close and remove
+  NotClosable(CloseIssue),
+  HasPayments,
+  HasBurnedPoints,
+  UplsAlreadyReleased,
+  // Park
+  AlreadyParked,
+  ParkLabelRequired,
+  NotParked,
+  // Pre-order
+  AlreadyPreorder,
+  PreorderCustomerRequired,
+  PreorderEmptyCart,
+  ArrivalInPast,
+  DepositOutOfRange { max: i32 },
+  NotPreorder,
+  PreorderFulfilled,
+  DepositDue { due: i32 },
+  UplOverPreorder { sku: u32 },
+  // Loyalty and commitment
+  LoyaltyCardAlreadyAdded,
+  NoLoyaltyCard,
+  LoyaltyCardHasBurnedPoints,
+  BurnMoreThanBurned,
+  BurnTransactionExists { transaction_id: String },
+  NoCommitment,
+  // Purchase
+  InvoiceAlreadySet,
+  BurnedPointsMismatch,
+  PurchaseNoLoyaltyCard,
+  AlreadyRestored,
+  RefundNotForPurchase,
+  RefundOfRestored,
+  RestoreOfRefunded,
+  UplAlreadyReturned { upl_id: String },
+  // Refund
+  RefundNoUpls,
+  UplNotInPurchase { upl_id: String },
+  // Quote
+  QuoteEmptyCart,
+  QuoteValidTillInPast,
+  QuoteAlreadyConverted,
+  QuoteExpired,
+  QuoteMissingPrice { sku: u32 },
+  QuoteUniqueUpl { upl_id: String },
+  // Promotion
+  PromotionInvalidPeriod,
+  PromotionInvalidBuyXPayY,
+  PromotionBundleEmpty,
+  PromotionBundleZeroPiece,
+  PromotionInvalidPercentage,
+  PromotionCategoryNoSkus,
+}
+
+impl DomainError {
+  /// Stable error code in SCREAMING_SNAKE_CASE
+  pub fn code(&self) -> String {
+    match self {
+      DomainError::InvalidVat => "INVALID_VAT",
+      DomainError::InvalidLoyaltyLevel => "INVALID_LOYALTY_LEVEL",
+      DomainError::InvalidPriceOverrideReason => "INVALID_PRICE_OVERRIDE_REASON",
+      DomainError::InvalidUnit => "INVALID_UNIT",
+      DomainError::InvalidId { .. } => "INVALID_ID",
+      DomainError::InvalidDate { .. } => "INVALID_DATE",
+      DomainError::InvalidPaymentKind => "INVALID_PAYMENT_KIND",
+      DomainError::InvalidDocumentKind => "INVALID_DOCUMENT_KIND",
+      DomainError::MissingField { .. } => "MISSING_FIELD",
+      DomainError::VersionConflict { .. } => "VERSION_CONFLICT",
+      DomainError::NothingToUndo => "NOTHING_TO_UNDO",
+      DomainError::UndoTransactionIdRequired => "UNDO_TRANSACTION_ID_REQUIRED",
+      DomainError::UndoBurnNotFound => "UNDO_BURN_NOT_FOUND",
+      DomainError::NotUndoable => "NOT_UNDOABLE",
+      DomainError::SkuHasUpls { .. } => "SKU_HAS_UPLS",
+      DomainError::SkuNotInCart { .. } => "SKU_NOT_IN_CART",
+      DomainError::UplAlreadyInCart { .. } => "UPL_ALREADY_IN_CART",
+      DomainError::UplNotInCart { .. } => "UPL_NOT_IN_CART",
+      DomainError::UplExpired { .. } => "UPL_EXPIRED",
+      DomainError::UplPricedBySku { .. } => "UPL_PRICED_BY_SKU",
+      DomainError::NetAboveGross => "NET_ABOVE_GROSS",
+      DomainError::OverrideAboveOriginal => "OVERRIDE_ABOVE_ORIGINAL",
+      DomainError::NoSkuPriceOverride { .. } => "NO_SKU_PRICE_OVERRIDE",
+      DomainError::NoUplPriceOverride { .. } => "NO_UPL_PRICE_OVERRIDE",
+      DomainError::DateCompletionTooEarly { .. } => "DATE_COMPLETION_TOO_EARLY",
+      DomainError::DateCompletionTooLate { .. } => "DATE_COMPLETION_TOO_LATE",
+      DomainError::DuedateBeforeCompletion => "DUEDATE_BEFORE_COMPLETION",
+      DomainError::DuedateOnlyForTransfer => "DUEDATE_ONLY_FOR_TRANSFER",
+      DomainError::InvalidTendered => "INVALID_TENDERED",
+      DomainError::NothingToPay => "NOTHING_TO_PAY",
+      DomainError::PaymentNotFound { .. } => "PAYMENT_NOT_FOUND",
+      DomainError::PaymentIsReversal { .. } => "PAYMENT_IS_REVERSAL",
+      DomainError::ReversalIdExists { .. } => "REVERSAL_ID_EXISTS",
+      DomainError::ReversalAmountOutOfRange { .. } => "REVERSAL_AMOUNT_OUT_OF_RANGE",
+      DomainError::PaymentAlreadyReversed { .. } => "PAYMENT_ALREADY_REVERSED",
+      DomainError::NotClosable(_) => "NOT_CLOSABLE",
+      DomainError::HasPayments => "HAS_PAYMENTS",
+      DomainError::HasBurnedPoints => "HAS_BURNED_POINTS",
+      DomainError::UplsAlreadyReleased => "UPLS_ALREADY_RELEASED",
+      DomainError::AlreadyParked => "ALREADY_PARKED",
+      DomainError::ParkLabelRequired => "PARK_LABEL_REQUIRED",
+      DomainError::NotParked => "NOT_PARKED",
+      DomainError::AlreadyPreorder => "ALREADY_PREORDER",
+      DomainError::PreorderCustomerRequired => "PREORDER_CUSTOMER_REQUIRED",
+      DomainError::PreorderEmptyCart => "PREORDER_EMPTY_CART",
+      DomainError::ArrivalInPast => "ARRIVAL_IN_PAST",
+      DomainError::DepositOutOfRange { .. } => "DEPOSIT_OUT_OF_RANGE",
+      DomainError::NotPreorder => "NOT_PREORDER",
+      DomainError::PreorderFulfilled => "PREORDER_FULFILLED",
+      DomainError::DepositDue { .. } => "DEPOSIT_DUE",
+      DomainError::UplOverPreorder { .. } => "UPL_OVER_PREORDER",
+      DomainError::LoyaltyCardAlreadyAdded => "LOYALTY_CARD_ALREADY_ADDED",
+      DomainError::NoLoyaltyCard => "NO_LOYALTY_CARD",
+      DomainError::LoyaltyCardHasBurnedPoints => "LOYALTY_CARD_HAS_BURNED_POINTS",
+      DomainError::BurnMoreThanBurned => "BURN_MORE_THAN_BURNED",
+      DomainError::BurnTransactionExists { .. } => "BURN_TRANSACTION_EXISTS",
+      DomainError::NoCommitment => "NO_COMMITMENT",
+      DomainError::InvoiceAlreadySet => "INVOICE_ALREADY_SET",
+      DomainError::BurnedPointsMismatch => "BURNED_POINTS_MISMATCH",
+      DomainError::PurchaseNoLoyaltyCard => "PURCHASE_NO_LOYALTY_CARD",
+      DomainError::AlreadyRestored => "ALREADY_RESTORED",
+      DomainError::RefundNotForPurchase => "REFUND_NOT_FOR_PURCHASE",
+      DomainError::RefundOfRestored => "REFUND_OF_RESTORED",
+      DomainError::RestoreOfRefunded => "RESTORE_OF_REFUNDED",
+      DomainError::UplAlreadyReturned { .. } => "UPL_ALREADY_RETURNED",
+      DomainError::RefundNoUpls => "REFUND_NO_UPLS",
+      DomainError::UplNotInPurchase { .. } => "UPL_NOT_IN_PURCHASE",
+      DomainError::QuoteEmptyCart => "QUOTE_EMPTY_CART",
+      DomainError::QuoteValidTillInPast => "QUOTE_VALID_TILL_IN_PAST",
+      DomainError::QuoteAlreadyConverted => "QUOTE_ALREADY_CONVERTED",
+      DomainError::QuoteExpired => "QUOTE_EXPIRED",
+      DomainError::QuoteMissingPrice { .. } => "QUOTE_MISSING_PRICE",
+      DomainError::QuoteUniqueUpl { .. } => "QUOTE_UNIQUE_UPL",
+      DomainError::PromotionInvalidPeriod => "PROMOTION_INVALID_PERIOD",
+      DomainError::PromotionInvalidBuyXPayY => "PROMOTION_INVALID_BUY_X_PAY_Y",
+      DomainError::PromotionBundleEmpty => "PROMOTION_BUNDLE_EMPTY",
+      DomainError::PromotionBundleZeroPiece => "PROMOTION_BUNDLE_ZERO_PIECE",
+      DomainError::PromotionInvalidPercentage => "PROMOTION_INVALID_PERCENTAGE",
+      DomainError::PromotionCategoryNoSkus => "PROMOTION_CATEGORY_NO_SKUS",
+    }
+    .to_string()
+  }
+  /// Machine readable details as key value pairs
+  pub fn params(&self) -> Vec<(String, String)> {
+    match self {
+      DomainError::InvalidId { id } => vec![("id".to_string(), id.to_string())],
+      DomainError::InvalidDate { date } => vec![("date".to_string(), date.to_string())],
+      DomainError::MissingField { field } => vec![("field".to_string(), field.to_string())],
+      DomainError::VersionConflict { expected, actual } => vec![
+        ("expected".to_string(), expected.to_string()),
+        ("actual".to_string(), actual.to_string()),
+      ],
+      DomainError::SkuHasUpls { sku }
+      | DomainError::SkuNotInCart { sku }
+      | DomainError::NoSkuPriceOverride { sku }
+      | DomainError::QuoteMissingPrice { sku }
+      | DomainError::UplOverPreorder { sku } => vec![("sku".to_string(), sku.to_string())],
+      DomainError::UplAlreadyInCart { upl_id }
+      | DomainError::UplNotInCart { upl_id }
+      | DomainError::UplExpired { upl_id }
+      | DomainError::UplPricedBySku { upl_id }
+      | DomainError::NoUplPriceOverride { upl_id }
+      | DomainError::UplAlreadyReturned { upl_id }
+      | DomainError::UplNotInPurchase { upl_id }
+      | DomainError::QuoteUniqueUpl { upl_id } => {
+        vec![("upl_id".to_string(), upl_id.to_string())]
+      }
+      DomainError::DateCompletionTooEarly { max_days }
+      | DomainError::DateCompletionTooLate { max_days } => {
+        vec![("max_days".to_string(), max_days.to_string())]
+      }
+      DomainError::PaymentNotFound { payment_id }
+      | DomainError::PaymentIsReversal { payment_id }
+      | DomainError::PaymentAlreadyReversed { payment_id } => {
+        vec![("payment_id".to_string(), payment_id.to_string())]
+      }
+      DomainError::ReversalIdExists { reversal_id } => {
+        vec![("reversal_id".to_string(), reversal_id.to_string())]
+      }
+      DomainError::ReversalAmountOutOfRange { max } | DomainError::DepositOutOfRange { max } => {
+        vec![("max".to_string(), max.to_string())]
+      }
+      DomainError::DepositDue { due } => vec![("due".to_string(), due.to_string())],
+      DomainError::NotClosable(issue) => vec![("issue".to_string(), issue.code())],
+      DomainError::BurnTransactionExists { transaction_id } => {
+        vec![("transaction_id".to_string(), transaction_id.to_string())]
+      }
+      _ => Vec::new(),
+    }
+  }
+}
+
+impl fmt::Display for DomainError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.localize(Locale::default()))
+  }
+}
+
+impl fmt::Debug for DomainError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}: {}", self.code(), self)
+  }
+}
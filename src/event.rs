@@ -0,0 +1,308 @@
+// Cart audit trail
+// Every cart mutation with the acting user
+// and the totals before and after it
+
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CartEvent {
+  pub id: Uuid,                  // Event ID
+  pub kind: CartEventKind,       // What happened
+  pub before: EventTotals,       // Totals before the event
+  pub after: EventTotals,        // Totals after the event
+  pub created_by: u32,           // UID
+  pub created_at: DateTime<Utc>, // When event happened
+}
+
+impl CartEvent {
+  pub fn new(
+    kind: CartEventKind,
+    before: EventTotals,
+    after: EventTotals,
+    created_by: u32,
+  ) -> Self {
+    Self {
+      id: Uuid::new_v4(),
+      kind,
+      before,
+      after,
+      created_by,
+      created_at: Utc::now(),
+    }
+  }
+}
+
+impl Default for CartEvent {
+  fn default() -> Self {
+    Self {
+      id: Uuid::default(),
+      kind: CartEventKind::default(),
+      before: EventTotals::default(),
+      after: EventTotals::default(),
+      created_by: 0,
+      created_at: Utc::now(),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct EventTotals {
+  pub total_net: u32,   // Cart net total
+  pub total_gross: u32, // Cart gross total
+  pub payable: i32,     // Payable amount
+  pub balance: i32,     // Payment balance
+}
+
+impl Default for EventTotals {
+  fn default() -> Self {
+    Self {
+      total_net: 0,
+      total_gross: 0,
+      payable: 0,
+      balance: 0,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum CartEventKind {
+  AddCustomer {
+    customer_id: u32,
+  },
+  RemoveCustomer,
+  AddSku {
+    sku: u32,
+    piece: u32,
+  },
+  RemoveSku {
+    sku: u32,
+  },
+  SetSkuPiece {
+    sku: u32,
+    piece: u32,
+    piece_before: u32,
+  },
+  AddUpl {
+    upl_id: String,
+  },
+  RemoveUpl {
+    upl_id: String,
+  },
+  SetDocument {
+    document_kind: String,
+  },
+  SetPayment {
+    payment_kind: String,
+  },
+  SetDateCompletion {
+    date_completion: DateTime<Utc>,
+  },
+  SetInvoiceDates {
+    date_completion: DateTime<Utc>,
+    payment_duedate: DateTime<Utc>,
+  },
+  AddPayment {
+    payment_id: String,
+    amount: i32,
+  },
+  AddCashPayment {
+    payment_id: String,
+    tendered: i32,
+  },
+  ReversePayment {
+    payment_id: String,
+    reversal_id: String,
+  },
+  SetSkuPriceOverride {
+    sku: u32,
+    price_gross: u32,
+  },
+  RemoveSkuPriceOverride {
+    sku: u32,
+  },
+  SetUplPriceOverride {
+    upl_id: String,
+    price_gross: u32,
+  },
+  RemoveUplPriceOverride {
+    upl_id: String,
+  },
+  SetOwner {
+    owner_uid: u32,
+  },
+  SetStore {
+    store_id: Option<u32>,
+  },
+  Park {
+    label: String,
+  },
+  Resume,
+  SetPreorder {
+    expected_arrival: DateTime<Utc>,
+    deposit: i32,
+  },
+  RemovePreorder,
+  FulfilPreorder,
+  AddCommitment {
+    commitment_id: Uuid,
+    previous: Option<(Uuid, u32)>, // Replaced commitment ID and percentage
+  },
+  RemoveCommitment,
+  AddLoyaltyCard {
+    card_id: String,
+  },
+  RemoveLoyaltyCard,
+  BurnPoints {
+    transaction_id: Uuid,
+    points: i32,
+  },
+  Close,
+  Undo {
+    event_id: Uuid,
+  },
+  RefreshPromotions {
+    promotion_ids: Vec<Uuid>, // Promotions applied after the refresh
+  },
+  RemovePromotion {
+    promotion_id: Uuid,
+  },
+}
+
+impl Default for CartEventKind {
+  fn default() -> Self {
+    Self::Close
+  }
+}
+
+impl CartEventKind {
+  /// Event name in SCREAMING_SNAKE_CASE
+  pub fn name(&self) -> String {
+    match self {
+      CartEventKind::AddCustomer { .. } => "ADD_CUSTOMER",
+      CartEventKind::RemoveCustomer => "REMOVE_CUSTOMER",
+      CartEventKind::AddSku { .. } => "ADD_SKU",
+      CartEventKind::RemoveSku { .. } => "REMOVE_SKU",
+      CartEventKind::SetSkuPiece { .. } => "SET_SKU_PIECE",
+      CartEventKind::AddUpl { .. } => "ADD_UPL",
+      CartEventKind::RemoveUpl { .. } => "REMOVE_UPL",
+      CartEventKind::SetDocument { .. } => "SET_DOCUMENT",
+      CartEventKind::SetPayment { .. } => "SET_PAYMENT",
+      CartEventKind::SetDateCompletion { .. } => "SET_DATE_COMPLETION",
+      CartEventKind::SetInvoiceDates { .. } => "SET_INVOICE_DATES",
+      CartEventKind::AddPayment { .. } => "ADD_PAYMENT",
+      CartEventKind::AddCashPayment { .. } => "ADD_CASH_PAYMENT",
+      CartEventKind::ReversePayment { .. } => "REVERSE_PAYMENT",
+      CartEventKind::SetSkuPriceOverride { .. } => "SET_SKU_PRICE_OVERRIDE",
+      CartEventKind::RemoveSkuPriceOverride { .. } => "REMOVE_SKU_PRICE_OVERRIDE",
+      CartEventKind::SetUplPriceOverride { .. } => "SET_UPL_PRICE_OVERRIDE",
+      CartEventKind::RemoveUplPriceOverride { .. } => "REMOVE_UPL_PRICE_OVERRIDE",
+      CartEventKind::SetOwner { .. } => "SET_OWNER",
+      CartEventKind::SetStore { .. } => "SET_STORE",
+      CartEventKind::Park { .. } => "PARK",
+      CartEventKind::Resume => "RESUME",
+      CartEventKind::SetPreorder { .. } => "SET_PREORDER",
+      CartEventKind::RemovePreorder => "REMOVE_PREORDER",
+      CartEventKind::FulfilPreorder => "FULFIL_PREORDER",
+      CartEventKind::AddCommitment { .. } => "ADD_COMMITMENT",
+      CartEventKind::RemoveCommitment => "REMOVE_COMMITMENT",
+      CartEventKind::AddLoyaltyCard { .. } => "ADD_LOYALTY_CARD",
+      CartEventKind::RemoveLoyaltyCard => "REMOVE_LOYALTY_CARD",
+      CartEventKind::BurnPoints { .. } => "BURN_POINTS",
+      CartEventKind::Close => "CLOSE",
+      CartEventKind::Undo { .. } => "UNDO",
+      CartEventKind::RefreshPromotions { .. } => "REFRESH_PROMOTIONS",
+      CartEventKind::RemovePromotion { .. } => "REMOVE_PROMOTION",
+    }
+    .to_string()
+  }
+  /// Event payload as human readable text
+  pub fn details(&self) -> String {
+    match self {
+      CartEventKind::AddCustomer { customer_id } => format!("customer_id: {}", customer_id),
+      CartEventKind::AddSku { sku, piece } => format!("sku: {}, piece: {}", sku, piece),
+      CartEventKind::RemoveSku { sku } => format!("sku: {}", sku),
+      CartEventKind::SetSkuPiece {
+        sku,
+        piece,
+        piece_before,
+      } => format!("sku: {}, piece: {} -> {}", sku, piece_before, piece),
+      CartEventKind::AddUpl { upl_id } => format!("upl_id: {}", upl_id),
+      CartEventKind::RemoveUpl { upl_id } => format!("upl_id: {}", upl_id),
+      CartEventKind::SetDocument { document_kind } => {
+        format!("document_kind: {}", document_kind)
+      }
+      CartEventKind::SetPayment { payment_kind } => format!("payment_kind: {}", payment_kind),
+      CartEventKind::SetDateCompletion { date_completion } => {
+        format!("date_completion: {}", date_completion.to_rfc3339())
+      }
+      CartEventKind::SetInvoiceDates {
+        date_completion,
+        payment_duedate,
+      } => format!(
+        "date_completion: {}, payment_duedate: {}",
+        date_completion.to_rfc3339(),
+        payment_duedate.to_rfc3339()
+      ),
+      CartEventKind::AddPayment { payment_id, amount } => {
+        format!("payment_id: {}, amount: {}", payment_id, amount)
+      }
+      CartEventKind::AddCashPayment {
+        payment_id,
+        tendered,
+      } => format!("payment_id: {}, tendered: {}", payment_id, tendered),
+      CartEventKind::ReversePayment {
+        payment_id,
+        reversal_id,
+      } => format!("payment_id: {}, reversal_id: {}", payment_id, reversal_id),
+      CartEventKind::SetSkuPriceOverride { sku, price_gross } => {
+        format!("sku: {}, price_gross: {}", sku, price_gross)
+      }
+      CartEventKind::RemoveSkuPriceOverride { sku } => format!("sku: {}", sku),
+      CartEventKind::SetUplPriceOverride {
+        upl_id,
+        price_gross,
+      } => format!("upl_id: {}, price_gross: {}", upl_id, price_gross),
+      CartEventKind::RemoveUplPriceOverride { upl_id } => format!("upl_id: {}", upl_id),
+      CartEventKind::SetOwner { owner_uid } => format!("owner_uid: {}", owner_uid),
+      CartEventKind::SetStore { store_id } => format!("store_id: {}", store_id.unwrap_or(0)),
+      CartEventKind::Park { label } => format!("label: {}", label),
+      CartEventKind::SetPreorder {
+        expected_arrival,
+        deposit,
+      } => format!(
+        "expected_arrival: {}, deposit: {}",
+        expected_arrival.to_rfc3339(),
+        deposit
+      ),
+      CartEventKind::AddCommitment { commitment_id, .. } => {
+        format!("commitment_id: {}", commitment_id)
+      }
+      CartEventKind::AddLoyaltyCard { card_id } => format!("card_id: {}", card_id),
+      CartEventKind::Undo { event_id } => format!("event_id: {}", event_id),
+      CartEventKind::RefreshPromotions { promotion_ids } => format!(
+        "promotion_ids: {}",
+        promotion_ids
+          .iter()
+          .map(|id| id.to_string())
+          .collect::<Vec<String>>()
+          .join(", ")
+      ),
+      CartEventKind::RemovePromotion { promotion_id } => {
+        format!("promotion_id: {}", promotion_id)
+      }
+      CartEventKind::BurnPoints {
+        transaction_id,
+        points,
+      } => format!("transaction_id: {}, points: {}", transaction_id, points),
+      CartEventKind::RemoveCustomer
+      | CartEventKind::Resume
+      | CartEventKind::RemovePreorder
+      | CartEventKind::FulfilPreorder
+      | CartEventKind::RemoveCommitment
+      | CartEventKind::RemoveLoyaltyCard
+      | CartEventKind::Close => String::default(),
+    }
+  }
+}
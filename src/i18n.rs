@@ -0,0 +1,676 @@
+// Message catalog
+// Every user facing text in Hungarian and English
+// Locale comes from the accept-language request metadata
+
+use crate::error::DomainError;
+use crate::purchase::{DocumentKind, PaymentKind, PriceOverrideReason, Purchase, Unit};
+use crate::validation::CloseIssue;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Locale {
+  Hu,
+  En,
+}
+
+impl Default for Locale {
+  fn default() -> Self {
+    Locale::Hu
+  }
+}
+
+impl Locale {
+  /// Locale from an accept-language like header value
+  /// Only the first language tag counts, unknown ones fall back to Hungarian
+  pub fn from_header(value: &str) -> Self {
+    let tag = value
+      .split(',')
+      .next()
+      .unwrap_or_default()
+      .split(';')
+      .next()
+      .unwrap_or_default()
+      .trim()
+      .to_lowercase();
+    if tag.starts_with("en") {
+      Locale::En
+    } else {
+      Locale::Hu
+    }
+  }
+  /// Locale from the accept-language request metadata
+  /// Hungarian if it is missing
+  pub fn from_metadata(metadata: &::tonic::metadata::MetadataMap) -> Self {
+    metadata
+      .get("accept-language")
+      .and_then(|v| v.to_str().ok())
+      .map(Locale::from_header)
+      .unwrap_or_default()
+  }
+}
+
+/// Text in the given locale
+pub trait Localize {
+  fn localize(&self, locale: Locale) -> String;
+}
+
+impl Localize for DomainError {
+  fn localize(&self, locale: Locale) -> String {
+    let hu = locale == Locale::Hu;
+    match self {
+      DomainError::InvalidVat => match hu {
+        true => "Nem megfelelő Áfa formátum! 5, 18, 27, AAM, TAM, FAD".to_string(),
+        false => "Invalid VAT format! 5, 18, 27, AAM, TAM, FAD".to_string(),
+      },
+      DomainError::InvalidLoyaltyLevel => match hu {
+        true => "Ismeretlen kedvezmény kártya szint. L1 | L2".to_string(),
+        false => "Unknown loyalty card level. L1 | L2".to_string(),
+      },
+      DomainError::InvalidPriceOverrideReason => match hu {
+        true => "Ismeretlen ármódosítási ok. DAMAGED | PRICE_MATCH | OTHER".to_string(),
+        false => "Unknown price override reason. DAMAGED | PRICE_MATCH | OTHER".to_string(),
+      },
+      DomainError::InvalidUnit => match hu {
+        true => "Nem megfelelő mértékegység! PIECE, GRAM, MILLILITER, MILLIMETER".to_string(),
+        false => "Invalid unit! PIECE, GRAM, MILLILITER, MILLIMETER".to_string(),
+      },
+      DomainError::InvalidId { id } => match hu {
+        true => format!("A kért ID hibás: {}", id),
+        false => format!("Invalid ID: {}", id),
+      },
+      DomainError::InvalidDate { date } => match hu {
+        true => format!("A megadott dátum hibás: {}", date),
+        false => format!("Invalid date: {}", date),
+      },
+      DomainError::InvalidPaymentKind => match hu {
+        true => "Ismeretlen fizetési mód!".to_string(),
+        false => "Unknown payment method!".to_string(),
+      },
+      DomainError::InvalidDocumentKind => match hu {
+        true => "Ismeretlen dokumentum típus!".to_string(),
+        false => "Unknown document kind!".to_string(),
+      },
+      DomainError::MissingField { field } => match hu {
+        true => format!("Hiányzó mező: {}", field),
+        false => format!("Missing field: {}", field),
+      },
+      DomainError::VersionConflict { expected, actual } => match hu {
+        true => format!(
+          "A kosár időközben módosult! Várt verzió: {}, aktuális verzió: {}",
+          expected, actual
+        ),
+        false => format!(
+          "The cart has been modified meanwhile! Expected version: {}, current version: {}",
+          expected, actual
+        ),
+      },
+      DomainError::NothingToUndo => match hu {
+        true => "A kosáron még nem történt visszavonható művelet!".to_string(),
+        false => "There is nothing to undo on the cart!".to_string(),
+      },
+      DomainError::UndoTransactionIdRequired => match hu {
+        true => "A pontfelhasználás visszavonásához tranzakció azonosító szükséges!".to_string(),
+        false => "Undoing a points burn requires a transaction ID!".to_string(),
+      },
+      DomainError::UndoBurnNotFound => match hu {
+        true => "A visszavonandó pontfelhasználás nem található!".to_string(),
+        false => "The points burn to undo was not found!".to_string(),
+      },
+      DomainError::NotUndoable => match hu {
+        true => "A legutóbbi művelet nem vonható vissza!".to_string(),
+        false => "The last operation cannot be undone!".to_string(),
+      },
+      DomainError::SkuHasUpls { .. } => match hu {
+        true => "Az adott SKU-hoz még van UPL a kosárban!".to_string(),
+        false => "The SKU still has UPLs in the cart!".to_string(),
+      },
+      DomainError::SkuNotInCart { .. } => match hu {
+        true => "A kért SKU nem szerepel a kosárban!".to_string(),
+        false => "The requested SKU is not in the cart!".to_string(),
+      },
+      DomainError::UplAlreadyInCart { .. } => match hu {
+        true => "A kért UPL már a kosárban van!".to_string(),
+        false => "The requested UPL is already in the cart!".to_string(),
+      },
+      DomainError::UplNotInCart { .. } => match hu {
+        true => "A kért UPL nem szerepel a kosárban!".to_string(),
+        false => "The requested UPL is not in the cart!".to_string(),
+      },
+      DomainError::UplExpired { .. } => match hu {
+        true => {
+          "A kért UPL lejárt! Csak leértékelve vagy vezetői jóváhagyással adható el.".to_string()
+        }
+        false => {
+          "The requested UPL has expired! It can only be sold discounted or with manager approval."
+            .to_string()
+        }
+      },
+      DomainError::UplPricedBySku { .. } => match hu {
+        true => {
+          "A kért UPL ára a bevásárlólista alapján számolódik, az SKU árát módosítsa!".to_string()
+        }
+        false => {
+          "The price of the requested UPL comes from the shopping list, override the SKU price!"
+            .to_string()
+        }
+      },
+      DomainError::NetAboveGross => match hu {
+        true => "A nettó ár nem lehet nagyobb a bruttó árnál!".to_string(),
+        false => "Net price cannot be greater than gross price!".to_string(),
+      },
+      DomainError::OverrideAboveOriginal => match hu {
+        true => "Az egyedi ár nem lehet magasabb az eredeti árnál!".to_string(),
+        false => "Override price cannot be higher than the original price!".to_string(),
+      },
+      DomainError::NoSkuPriceOverride { .. } => match hu {
+        true => "A kért SKU-hoz nincs egyedi ár beállítva!".to_string(),
+        false => "The requested SKU has no price override!".to_string(),
+      },
+      DomainError::NoUplPriceOverride { .. } => match hu {
+        true => "A kért UPL-hez nincs egyedi ár beállítva!".to_string(),
+        false => "The requested UPL has no price override!".to_string(),
+      },
+      DomainError::DateCompletionTooEarly { max_days } => match hu {
+        true => format!(
+          "A teljesítés dátuma legfeljebb {} nappal lehet korábbi a mai napnál!",
+          max_days
+        ),
+        false => format!(
+          "Date of completion can be at most {} days before today!",
+          max_days
+        ),
+      },
+      DomainError::DateCompletionTooLate { max_days } => match hu {
+        true => format!(
+          "A teljesítés dátuma legfeljebb {} nappal lehet későbbi a mai napnál!",
+          max_days
+        ),
+        false => format!(
+          "Date of completion can be at most {} days after today!",
+          max_days
+        ),
+      },
+      DomainError::DuedateBeforeCompletion => match hu {
+        true => "A fizetési határidő nem lehet korábbi, mint a teljesítés dátuma!".to_string(),
+        false => "Payment due date cannot be earlier than the date of completion!".to_string(),
+      },
+      DomainError::DuedateOnlyForTransfer => match hu {
+        true => {
+          "Csak átutalásos fizetésnél lehet a fizetési határidő későbbi, mint a teljesítés!"
+            .to_string()
+        }
+        false => {
+          "Payment due date can only be later than the date of completion for transfers!"
+            .to_string()
+        }
+      },
+      DomainError::InvalidTendered => match hu {
+        true => "Az átadott készpénz összege nem lehet 0 vagy negatív!".to_string(),
+        false => "Tendered cash cannot be 0 or negative!".to_string(),
+      },
+      DomainError::NothingToPay => match hu {
+        true => "A kosárhoz nincs fizetendő összeg!".to_string(),
+        false => "There is nothing to pay for the cart!".to_string(),
+      },
+      DomainError::PaymentNotFound { .. } => match hu {
+        true => "A kért befizetés nem szerepel a kosárban!".to_string(),
+        false => "The requested payment is not in the cart!".to_string(),
+      },
+      DomainError::PaymentIsReversal { .. } => match hu {
+        true => "Sztornó befizetést nem lehet sztornózni!".to_string(),
+        false => "A reversal payment cannot be reversed!".to_string(),
+      },
+      DomainError::ReversalIdExists { .. } => match hu {
+        true => "A sztornó befizetés azonosítója már szerepel a kosárban!".to_string(),
+        false => "The reversal payment ID is already in the cart!".to_string(),
+      },
+      DomainError::ReversalAmountOutOfRange { max } => match hu {
+        true => format!("A sztornó összege 1 és {} között lehet!", max),
+        false => format!("Reversal amount must be between 1 and {}!", max),
+      },
+      DomainError::PaymentAlreadyReversed { .. } => match hu {
+        true => "A kért befizetés már sztornózva van!".to_string(),
+        false => "The requested payment is already reversed!".to_string(),
+      },
+      DomainError::NotClosable(issue) => issue.localize(locale),
+      DomainError::HasPayments => match hu {
+        true => {
+          "A kosár nem törölhető, mert befizetés tartozik hozzá! Előbb rendezze a befizetéseket!"
+            .to_string()
+        }
+        false => "The cart has payments, it cannot be removed! Settle the payments first!"
+          .to_string(),
+      },
+      DomainError::HasBurnedPoints => match hu {
+        true => "A kosár nem törölhető, mert felhasznált pontok tartoznak hozzá! Előbb törölje a felhasznált pontokat!".to_string(),
+        false => "The cart has burned points, it cannot be removed! Remove the burned points first!".to_string(),
+      },
+      DomainError::UplsAlreadyReleased => match hu {
+        true => "A törölt kosár UPL-jei már vissza lettek helyezve!".to_string(),
+        false => "The UPLs of the removed cart are already reshelved!".to_string(),
+      },
+      DomainError::AlreadyParked => match hu {
+        true => "A kosár már parkoltatva van!".to_string(),
+        false => "The cart is already parked!".to_string(),
+      },
+      DomainError::ParkLabelRequired => match hu {
+        true => "A parkoltatott kosárhoz címke megadása kötelező!".to_string(),
+        false => "A parked cart requires a label!".to_string(),
+      },
+      DomainError::NotParked => match hu {
+        true => "A kosár nincs parkoltatva!".to_string(),
+        false => "The cart is not parked!".to_string(),
+      },
+      DomainError::AlreadyPreorder => match hu {
+        true => "A kosár már előrendelés!".to_string(),
+        false => "The cart is already a pre-order!".to_string(),
+      },
+      DomainError::PreorderCustomerRequired => match hu {
+        true => "Előrendeléshez kötelező vásárlót megadni!".to_string(),
+        false => "A pre-order requires a customer!".to_string(),
+      },
+      DomainError::PreorderEmptyCart => match hu {
+        true => "Üres kosárból nem lehet előrendelés!".to_string(),
+        false => "An empty cart cannot be a pre-order!".to_string(),
+      },
+      DomainError::ArrivalInPast => match hu {
+        true => "A várható beérkezés nem lehet múltbeli dátum!".to_string(),
+        false => "Expected arrival cannot be in the past!".to_string(),
+      },
+      DomainError::DepositOutOfRange { max } => match hu {
+        true => format!("A foglaló összege 1 és {} között lehet!", max),
+        false => format!("Deposit must be between 1 and {}!", max),
+      },
+      DomainError::NotPreorder => match hu {
+        true => "A kosár nem előrendelés!".to_string(),
+        false => "The cart is not a pre-order!".to_string(),
+      },
+      DomainError::PreorderFulfilled => match hu {
+        true => "Az előrendelés már teljesítve van!".to_string(),
+        false => "The pre-order is already fulfilled!".to_string(),
+      },
+      DomainError::DepositDue { due } => match hu {
+        true => format!(
+          "Az előrendelés nem teljesíthető, a foglalóból még {} Ft hiányzik!",
+          due
+        ),
+        false => format!(
+          "The pre-order cannot be fulfilled, {} HUF of the deposit is still due!",
+          due
+        ),
+      },
+      DomainError::UplOverPreorder { sku } => match hu {
+        true => format!(
+          "Az UPL több darabot tartalmaz, mint amennyi előrendelve van a következő SKU-ból: {}",
+          sku
+        ),
+        false => format!(
+          "The UPL covers more pieces than pre-ordered of SKU: {}",
+          sku
+        ),
+      },
+      DomainError::LoyaltyCardAlreadyAdded => match hu {
+        true => "A kosárhoz már van kedvezmény kártya rendelve! Törölje azt, mielőtt másikat adna hozzá!".to_string(),
+        false => "The cart already has a loyalty card! Remove it before adding another one!".to_string(),
+      },
+      DomainError::NoLoyaltyCard => match hu {
+        true => "A kosárhoz nincs kártya rendelve, így azt nem lehet törölni!".to_string(),
+        false => "The cart has no loyalty card to remove!".to_string(),
+      },
+      DomainError::LoyaltyCardHasBurnedPoints => match hu {
+        true => "Kártyát akkor lehet törölni, ha a felhasznált pontok összege 0. Törölje a felhasznált pontokat!".to_string(),
+        false => "The card can only be removed if no points are burned. Remove the burned points!".to_string(),
+      },
+      DomainError::BurnMoreThanBurned => match hu {
+        true => {
+          "Több pontot szeretnénk kivenni a kosárból, mint amennyit felhasználtunk hozzá!"
+            .to_string()
+        }
+        false => "Cannot remove more points from the cart than were burned!".to_string(),
+      },
+      DomainError::BurnTransactionExists { .. } => match hu {
+        true => "A kért tranzakció már a felhasznált pontok között szerepel a kosárban, így nem adható hozzá ismét!".to_string(),
+        false => "The requested transaction is already among the burned points of the cart!".to_string(),
+      },
+      DomainError::NoCommitment => match hu {
+        true => "A kosárhoz nincs hozzárendelt commitment, így azt nem lehet eltávolítani"
+          .to_string(),
+        false => "The cart has no commitment to remove".to_string(),
+      },
+      DomainError::InvoiceAlreadySet => match hu {
+        true => "A vásárlás már rendelkezik számlával".to_string(),
+        false => "The purchase already has an invoice".to_string(),
+      },
+      DomainError::BurnedPointsMismatch => match hu {
+        true => "A vásárláshoz rendelt felhasznált pontok összege nem egyezik meg az összefoglaló szerinti felhasznált pontok összegével.".to_string(),
+        false => "The burned points of the purchase do not match the burned points of the summary.".to_string(),
+      },
+      DomainError::PurchaseNoLoyaltyCard => match hu {
+        true => "A vásárláshoz nem tartozik törzsvásárlói kártya, így nem lehet hozzá adatokat adni.".to_string(),
+        false => "The purchase has no loyalty card, no loyalty data can be added.".to_string(),
+      },
+      DomainError::AlreadyRestored => match hu {
+        true => "A vásárlás már sztornózva van!".to_string(),
+        false => "The purchase is already restored!".to_string(),
+      },
+      DomainError::RefundNotForPurchase => match hu {
+        true => "A visszáru nem ehhez a vásárláshoz tartozik!".to_string(),
+        false => "The refund does not belong to this purchase!".to_string(),
+      },
+      DomainError::RefundOfRestored => match hu {
+        true => "Sztornózott vásárláshoz nem lehet visszárut rögzíteni!".to_string(),
+        false => "A restored purchase cannot be refunded!".to_string(),
+      },
+      DomainError::RestoreOfRefunded => match hu {
+        true => "Visszárus vásárlást nem lehet sztornózni!".to_string(),
+        false => "A purchase with refunds cannot be restored!".to_string(),
+      },
+      DomainError::UplAlreadyReturned { upl_id } => match hu {
+        true => format!("A kért UPL már visszáruként szerepel: {}", upl_id),
+        false => format!("The requested UPL is already refunded: {}", upl_id),
+      },
+      DomainError::RefundNoUpls => match hu {
+        true => "Legalább egy UPL-t meg kell adni a visszáruhoz!".to_string(),
+        false => "A refund requires at least one UPL!".to_string(),
+      },
+      DomainError::UplNotInPurchase { upl_id } => match hu {
+        true => format!("A kért UPL nem szerepel a vásárlásban: {}", upl_id),
+        false => format!("The requested UPL is not in the purchase: {}", upl_id),
+      },
+      DomainError::QuoteEmptyCart => match hu {
+        true => "Üres kosárból nem készíthető árajánlat!".to_string(),
+        false => "A quote cannot be made from an empty cart!".to_string(),
+      },
+      DomainError::QuoteValidTillInPast => match hu {
+        true => "Az árajánlat érvényessége nem lehet múltbeli dátum!".to_string(),
+        false => "Quote validity cannot be in the past!".to_string(),
+      },
+      DomainError::QuoteAlreadyConverted => match hu {
+        true => "Az árajánlat már kosárrá lett alakítva!".to_string(),
+        false => "The quote is already converted to a cart!".to_string(),
+      },
+      DomainError::QuoteExpired => match hu {
+        true => "Az árajánlat érvényessége lejárt!".to_string(),
+        false => "The quote has expired!".to_string(),
+      },
+      DomainError::QuoteMissingPrice { sku } => match hu {
+        true => format!("Hiányzó aktuális ár a következő SKU-hoz: {}", sku),
+        false => format!("Missing current price for SKU: {}", sku),
+      },
+      DomainError::QuoteUniqueUpl { upl_id } => match hu {
+        true => format!(
+          "Árajánlat csak SKU-kból készíthető, egyedi UPL nem szerepelhet benne: {}",
+          upl_id
+        ),
+        false => format!(
+          "A quote can contain SKUs only, not unique UPLs: {}",
+          upl_id
+        ),
+      },
+      DomainError::PromotionInvalidPeriod => match hu {
+        true => "Az akció kezdete nem lehet később, mint a vége!".to_string(),
+        false => "Promotion start cannot be later than its end!".to_string(),
+      },
+      DomainError::PromotionInvalidBuyXPayY => match hu {
+        true => {
+          "Az X-et fizet Y-t kap akciónál a fizetendő darab kevesebb kell legyen!".to_string()
+        }
+        false => "For buy X pay Y promotions the paid piece must be less!".to_string(),
+      },
+      DomainError::PromotionBundleEmpty => match hu {
+        true => "A csomag akcióhoz legalább egy SKU és ár szükséges!".to_string(),
+        false => "A bundle promotion requires at least one SKU and a price!".to_string(),
+      },
+      DomainError::PromotionBundleZeroPiece => match hu {
+        true => "A csomag akció darabszáma nem lehet 0!".to_string(),
+        false => "Bundle promotion piece cannot be 0!".to_string(),
+      },
+      DomainError::PromotionInvalidPercentage => match hu {
+        true => "A kategória akció mértéke 1 és 100% között lehet!".to_string(),
+        false => "Category promotion percentage must be between 1 and 100%!".to_string(),
+      },
+      DomainError::PromotionCategoryNoSkus => match hu {
+        true => "A kategória akcióhoz meg kell adni a kategória cikkszámait!".to_string(),
+        false => "Category promotion needs the SKUs of the category!".to_string(),
+      },
+    }
+  }
+}
+
+impl Localize for CloseIssue {
+  fn localize(&self, locale: Locale) -> String {
+    let hu = locale == Locale::Hu;
+    match self {
+      CloseIssue::Parked => match hu {
+        true => "Parkoltatott kosár nem zárható le!".to_string(),
+        false => "A parked cart cannot be closed!".to_string(),
+      },
+      CloseIssue::PreorderNotFulfilled => match hu {
+        true => "Az előrendelés nem zárható le, amíg az áru meg nem érkezett!".to_string(),
+        false => "The pre-order cannot be closed until the goods have arrived!".to_string(),
+      },
+      CloseIssue::MissingCustomer => match hu {
+        true => "A kosár nem zárható le! Számlaigény van beállítva, de a vásárló üres!".to_string(),
+        false => {
+          "The cart cannot be closed! Invoice is requested, but customer is empty!".to_string()
+        }
+      },
+      CloseIssue::SkuUplMismatch {
+        sku,
+        name,
+        list_piece,
+        upl_piece,
+      } => match hu {
+        true => format!(
+          "Rendezd a kosarat! {} (SKU {}): {} db a listán, {} db UPL rendelve.",
+          name, sku, list_piece, upl_piece
+        ),
+        false => format!(
+          "Settle the cart! {} (SKU {}): {} pcs on the list, {} pcs of UPLs added.",
+          name, sku, list_piece, upl_piece
+        ),
+      },
+      CloseIssue::TotalsMismatch { .. } => match hu {
+        true => "A kosár záró összegei nem helyesek! Nem lehet lezárni!".to_string(),
+        false => "The cart totals are incorrect! It cannot be closed!".to_string(),
+      },
+      CloseIssue::CashNotRounded { .. } => match hu {
+        true => {
+          "A kosár nem zárható le, a készpénzes befizetések összege nincs kerekítve!".to_string()
+        }
+        false => "The cart cannot be closed, the cash payments are not rounded!".to_string(),
+      },
+      CloseIssue::TransferNeedsInvoice => match hu {
+        true => "A kosár nem zárható le, átutalás esetén kötelező számlát kérni!".to_string(),
+        false => "The cart cannot be closed, transfer payments require an invoice!".to_string(),
+      },
+      CloseIssue::PaymentNegative { .. } => match hu {
+        true => {
+          "A kosár nem zárható le, egy fizetési mód befizetéseinek összege negatív!".to_string()
+        }
+        false => "The cart cannot be closed, the payments of a method add up to a negative amount!"
+          .to_string(),
+      },
+      CloseIssue::PaymentOverpaid { .. } => match hu {
+        true => {
+          "A kosár nem zárható le, a befizetések összege meghaladja a fizetendőt!".to_string()
+        }
+        false => "The cart cannot be closed, the payments exceed the payable amount!".to_string(),
+      },
+      CloseIssue::PaymentUnbalanced { payment_kind, .. } => match (hu, payment_kind) {
+        (true, crate::cart::PaymentKind::Card) => {
+          "A kosár nem zárható le, bankkártyás fizetés esetén rendezze a befizetést!".to_string()
+        }
+        (true, _) => {
+          "A kosár nem zárható le, készpénzes fizetés esetén rendezze a befizetést!".to_string()
+        }
+        (false, crate::cart::PaymentKind::Card) => {
+          "The cart cannot be closed, settle the card payment!".to_string()
+        }
+        (false, _) => "The cart cannot be closed, settle the cash payment!".to_string(),
+      },
+    }
+  }
+}
+
+/// Fixed labels printed on receipts and invoices
+#[derive(Clone, Copy)]
+pub enum ReceiptText {
+  DateCompletion,
+  PaymentDuedate,
+  TotalNet,
+  TotalVat,
+  TotalGross,
+  Discount,
+  LoyaltyPoints,
+  Deposit,
+  Payable,
+  Tendered,
+  Change,
+  Restored,
+}
+
+impl ReceiptText {
+  pub fn all() -> Vec<ReceiptText> {
+    vec![
+      ReceiptText::DateCompletion,
+      ReceiptText::PaymentDuedate,
+      ReceiptText::TotalNet,
+      ReceiptText::TotalVat,
+      ReceiptText::TotalGross,
+      ReceiptText::Discount,
+      ReceiptText::LoyaltyPoints,
+      ReceiptText::Deposit,
+      ReceiptText::Payable,
+      ReceiptText::Tendered,
+      ReceiptText::Change,
+      ReceiptText::Restored,
+    ]
+  }
+  /// Label key in SCREAMING_SNAKE_CASE
+  pub fn key(&self) -> String {
+    match self {
+      ReceiptText::DateCompletion => "DATE_COMPLETION",
+      ReceiptText::PaymentDuedate => "PAYMENT_DUEDATE",
+      ReceiptText::TotalNet => "TOTAL_NET",
+      ReceiptText::TotalVat => "TOTAL_VAT",
+      ReceiptText::TotalGross => "TOTAL_GROSS",
+      ReceiptText::Discount => "DISCOUNT",
+      ReceiptText::LoyaltyPoints => "LOYALTY_POINTS",
+      ReceiptText::Deposit => "DEPOSIT",
+      ReceiptText::Payable => "PAYABLE",
+      ReceiptText::Tendered => "TENDERED",
+      ReceiptText::Change => "CHANGE",
+      ReceiptText::Restored => "RESTORED",
+    }
+    .to_string()
+  }
+}
+
+impl Localize for ReceiptText {
+  fn localize(&self, locale: Locale) -> String {
+    let (hu, en) = match self {
+      ReceiptText::DateCompletion => ("Teljesítés dátuma", "Date of completion"),
+      ReceiptText::PaymentDuedate => ("Fizetési határidő", "Payment due date"),
+      ReceiptText::TotalNet => ("Nettó összesen", "Total net"),
+      ReceiptText::TotalVat => ("Áfa összesen", "Total VAT"),
+      ReceiptText::TotalGross => ("Bruttó összesen", "Total gross"),
+      ReceiptText::Discount => ("Kedvezmény", "Discount"),
+      ReceiptText::LoyaltyPoints => ("Felhasznált pontok", "Burned points"),
+      ReceiptText::Deposit => ("Foglaló", "Deposit"),
+      ReceiptText::Payable => ("Fizetendő", "Payable"),
+      ReceiptText::Tendered => ("Átadott készpénz", "Cash tendered"),
+      ReceiptText::Change => ("Visszajáró", "Change"),
+      ReceiptText::Restored => ("Sztornózva", "Restored"),
+    };
+    match locale {
+      Locale::Hu => hu.to_string(),
+      Locale::En => en.to_string(),
+    }
+  }
+}
+
+impl Localize for DocumentKind {
+  fn localize(&self, locale: Locale) -> String {
+    match (locale, self) {
+      (Locale::Hu, DocumentKind::Receipt) => "Nyugta",
+      (Locale::Hu, DocumentKind::Invoice) => "Számla",
+      (Locale::En, DocumentKind::Receipt) => "Receipt",
+      (Locale::En, DocumentKind::Invoice) => "Invoice",
+    }
+    .to_string()
+  }
+}
+
+impl Localize for PaymentKind {
+  fn localize(&self, locale: Locale) -> String {
+    match (locale, self) {
+      (Locale::Hu, PaymentKind::Cash) => "Készpénz",
+      (Locale::Hu, PaymentKind::Card) => "Bankkártya",
+      (Locale::Hu, PaymentKind::Transfer) => "Átutalás",
+      (Locale::En, PaymentKind::Cash) => "Cash",
+      (Locale::En, PaymentKind::Card) => "Card",
+      (Locale::En, PaymentKind::Transfer) => "Transfer",
+    }
+    .to_string()
+  }
+}
+
+impl Localize for PriceOverrideReason {
+  fn localize(&self, locale: Locale) -> String {
+    match (locale, self) {
+      (Locale::Hu, PriceOverrideReason::Damaged) => "Sérült termék",
+      (Locale::Hu, PriceOverrideReason::PriceMatch) => "Árillesztés",
+      (Locale::Hu, PriceOverrideReason::Other) => "Egyéb",
+      (Locale::En, PriceOverrideReason::Damaged) => "Damaged",
+      (Locale::En, PriceOverrideReason::PriceMatch) => "Price match",
+      (Locale::En, PriceOverrideReason::Other) => "Other",
+    }
+    .to_string()
+  }
+}
+
+impl Localize for Unit {
+  fn localize(&self, locale: Locale) -> String {
+    match (locale, self) {
+      (Locale::Hu, Unit::Piece) => "db",
+      (Locale::En, Unit::Piece) => "pcs",
+      (_, Unit::Gram) => "g",
+      (_, Unit::Milliliter) => "ml",
+      (_, Unit::Millimeter) => "mm",
+    }
+    .to_string()
+  }
+}
+
+/// Every text a receipt of the purchase needs
+/// Document title and payment kind by the purchase itself,
+/// fixed labels, units and price override reasons by their codes
+pub fn receipt_texts(purchase: &Purchase, locale: Locale) -> HashMap<String, String> {
+  let mut res = HashMap::new();
+  res.insert(
+    "DOCUMENT_TITLE".to_string(),
+    purchase.document_kind.localize(locale),
+  );
+  res.insert(
+    "PAYMENT_KIND".to_string(),
+    purchase.payment_kind.localize(locale),
+  );
+  for text in ReceiptText::all() {
+    res.insert(text.key(), text.localize(locale));
+  }
+  for unit in [Unit::Piece, Unit::Gram, Unit::Milliliter, Unit::Millimeter] {
+    res.insert(format!("UNIT_{}", unit.to_string()), unit.localize(locale));
+  }
+  for reason in [
+    PriceOverrideReason::Damaged,
+    PriceOverrideReason::PriceMatch,
+    PriceOverrideReason::Other,
+  ] {
+    res.insert(
+      format!("PRICE_OVERRIDE_{}", reason.to_string()),
+      reason.localize(locale),
+    );
+  }
+  res
+}
@@ -1,5 +1,32 @@
+// Manual Default and ToString impls, from_str methods and the chrono 0.4
+// date API are kept as they are used all over the service
+#![allow(
+  deprecated,
+  clippy::derivable_impls,
+  clippy::inherent_to_string,
+  clippy::to_string_trait_impl,
+  clippy::should_implement_trait
+)]
+
+pub mod abandoned;
 pub mod cart;
+pub mod error;
+pub mod event;
+pub mod i18n;
 pub mod migration;
 pub mod prelude;
+pub mod promotion;
 pub mod purchase;
+pub mod quote;
+pub mod refund;
 pub mod rounding;
+pub mod unit;
+pub mod validation;
+
+// Purchase service API built from proto/purchase.proto
+pub mod proto {
+  pub use gzlib::proto::loyalty;
+  pub mod purchase {
+    tonic::include_proto!("purchase");
+  }
+}
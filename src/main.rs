@@ -1,54 +1,134 @@
+// Manual Default and ToString impls, from_str methods and the chrono 0.4
+// date API are kept as they are used all over the service
+#![allow(
+  deprecated,
+  clippy::derivable_impls,
+  clippy::inherent_to_string,
+  clippy::to_string_trait_impl,
+  clippy::should_implement_trait
+)]
+
 use cart::CartMethods;
-use chrono::{DateTime, Utc};
-use gzlib::proto::{
-  loyalty::{BurnRequest, PurchaseSummary},
-  purchase::{
-    purchase_server::*, AddCommitmentRequest, BurnPointsRequest, CartBulkRequest, CartByIdRequest,
-    CartNewRequest, CartSetDocumentRequest, DocumentKind, LoyaltyCardAddRequest,
-    LoyaltyCardRemoveRequest, PurchaseSetInvoiceIdRequest, RemoveCommitmentRequest,
-  },
-};
+use chrono::{DateTime, Datelike, Duration, Utc};
 use packman::*;
 use prelude::*;
 use proto::purchase::{
-  CartAddCustomerReuqest, CartAddPaymentRequest, CartAddSkuRequest, CartAddUplRequest,
-  CartCloseRequest, CartIds, CartInfoObject, CartObject, CartRemoveCustomerRequest,
-  CartRemoveSkuRequest, CartRemoveUplRequest, CartSetOwnerRequest, CartSetPaymentRequest,
-  CartSetSkuPieceRequest, CartSetStoreRequest, PurchaseBulkRequest, PurchaseByIdRequest,
-  PurchaseIds, PurchaseInfoObject, PurchaseObject,
+  CartAddCashPaymentRequest, CartAddCustomerReuqest, CartAddPaymentRequest, CartAddSkuRequest,
+  CartAddUplRequest, CartCloseIssue, CartCloseRequest, CartCloseValidation, CartEventObject,
+  CartEvents, CartFulfilPreorderRequest, CartGetParkedRequest, CartGetPreordersRequest, CartIds,
+  CartInfoObject, CartObject, CartParkRequest, CartRefreshPromotionsRequest,
+  CartRemoveCustomerRequest, CartRemovePreorderRequest, CartRemoveRequest,
+  CartRemoveSkuPriceOverrideRequest, CartRemoveSkuRequest, CartRemoveUplPriceOverrideRequest,
+  CartRemoveUplRequest, CartResumeRequest, CartReversePaymentRequest, CartSetDateCompletionRequest,
+  CartSetInvoiceDatesRequest, CartSetOwnerRequest, CartSetPaymentRequest, CartSetPreorderRequest,
+  CartSetSkuPieceRequest, CartSetSkuPriceOverrideRequest, CartSetStoreRequest,
+  CartSetUplPriceOverrideRequest, CartUndoRequest, CartVoidPaymentRequest, ParkedCartObject,
+  ParkedCarts, PreorderCartObject, PreorderCarts, PromotionByIdRequest, PromotionIds,
+  PromotionNewRequest, PromotionObject, PromotionRemoveRequest, PurchaseBulkRequest,
+  PurchaseByIdRequest, PurchaseIds, PurchaseInfoObject, PurchaseObject, PurchaseRefundRequest,
+  PurchaseRestoreRequest, QuoteByIdRequest, QuoteConvertRequest, QuoteIds, QuoteNewRequest,
+  QuoteObject, RefundByIdRequest, RefundObject, ReturnedUpls,
+};
+use purchase_microservice::error::DomainError;
+use purchase_microservice::event::CartEventKind;
+use purchase_microservice::i18n::Localize;
+use purchase_microservice::proto::{
+  loyalty::PurchaseSummary,
+  purchase::{
+    purchase_server::*, AbandonedCartByIdRequest, AbandonedCartIds, AbandonedCartObject,
+    AbandonedCartSetUplsReleasedRequest, AddCommitmentRequest, BurnPointsRequest, CartBulkRequest,
+    CartByIdRequest, CartNewRequest, CartSetDocumentRequest, DocumentKind, LoyaltyCardAddRequest,
+    LoyaltyCardRemoveRequest, PurchaseSetInvoiceIdRequest, RemoveCommitmentRequest,
+  },
 };
 use purchase_microservice::purchase::PurchaseExt;
 use purchase_microservice::*;
+use std::convert::TryFrom;
 use std::error::Error;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::{env, str::FromStr};
 use tokio::sync::{oneshot, Mutex};
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{transport::Server, Request, Response, Status};
 use uuid::Uuid;
 
-use gzlib::proto;
+use purchase_microservice::proto;
 
 struct PurchaseService {
-  carts: Mutex<VecPack<cart::Cart>>,
+  carts: Arc<Mutex<VecPack<cart::Cart>>>,
+  abandoned_carts: Arc<Mutex<VecPack<abandoned::AbandonedCart>>>,
   purchases: Mutex<VecPack<purchase::Purchase>>,
+  refunds: Mutex<VecPack<refund::Refund>>,
+  promotions: Mutex<VecPack<promotion::Promotion>>,
+  quotes: Mutex<VecPack<quote::Quote>>,
+  max_parked: Duration,
+  max_idle: Duration,
+  near_expiry: Duration,
+  date_limits: cart::DateCompletionLimits,
 }
 
 // Helper to try convert string to UUID
 fn string_to_uuid(id: String) -> ServiceResult<Uuid> {
-  Uuid::from_str(&id).map_err(|_| ServiceError::BadRequest(format!("A kért ID hibás: {}", id)))
+  str_to_uuid(&id)
+}
+
+// Helper to try convert RFC3339 string to UTC date
+fn string_to_date(date: &str) -> ServiceResult<DateTime<Utc>> {
+  DateTime::parse_from_rfc3339(date)
+    .map(|d| d.with_timezone(&Utc))
+    .map_err(|_| {
+      DomainError::InvalidDate {
+        date: date.to_string(),
+      }
+      .into()
+    })
 }
 
 impl PurchaseService {
-  pub fn init(carts: VecPack<cart::Cart>, purchases: VecPack<purchase::Purchase>) -> Self {
+  #[allow(clippy::too_many_arguments)]
+  pub fn init(
+    carts: Arc<Mutex<VecPack<cart::Cart>>>,
+    abandoned_carts: Arc<Mutex<VecPack<abandoned::AbandonedCart>>>,
+    purchases: VecPack<purchase::Purchase>,
+    refunds: VecPack<refund::Refund>,
+    promotions: VecPack<promotion::Promotion>,
+    quotes: VecPack<quote::Quote>,
+    max_parked: Duration,
+    max_idle: Duration,
+    near_expiry: Duration,
+    date_limits: cart::DateCompletionLimits,
+  ) -> Self {
     Self {
-      carts: Mutex::new(carts),
+      carts,
+      abandoned_carts,
       purchases: Mutex::new(purchases),
+      refunds: Mutex::new(refunds),
+      promotions: Mutex::new(promotions),
+      quotes: Mutex::new(quotes),
+      max_parked,
+      max_idle,
+      near_expiry,
+      date_limits,
     }
   }
+
+  // Get promotions that are not expired yet
+  async fn get_promotions(&self) -> Vec<promotion::Promotion> {
+    let now = Utc::now();
+    self
+      .promotions
+      .lock()
+      .await
+      .iter()
+      .filter(|p| p.unpack().valid_till > now)
+      .map(|p| p.unpack().clone())
+      .collect::<Vec<promotion::Promotion>>()
+  }
+
   async fn cart_new(&self, r: CartNewRequest) -> ServiceResult<CartObject> {
     // Create new cart
-    let new_cart = cart::Cart::new(
+    let mut new_cart = cart::Cart::new(
       r.owner_id,
       match r.store_id {
         0 => None,
@@ -56,10 +136,12 @@ impl PurchaseService {
       },
       r.created_by,
     );
+    // Set promotions to evaluate
+    new_cart.set_promotions(self.get_promotions().await);
     // Insert it to the carts DB
-    let _ = self.carts.lock().await.insert(new_cart.clone())?;
+    self.carts.lock().await.insert(new_cart.clone())?;
     // Return new cart
-    Ok(new_cart.into())
+    Ok(cart_object(new_cart, self.near_expiry))
   }
 
   async fn cart_get_all(&self) -> ServiceResult<Vec<String>> {
@@ -75,29 +157,38 @@ impl PurchaseService {
     Ok(res)
   }
 
+  async fn cart_get_idle(&self) -> ServiceResult<Vec<String>> {
+    // Collect the IDs of carts idle longer than max_idle
+    let res = self
+      .carts
+      .lock()
+      .await
+      .iter()
+      .map(|c| c.unpack())
+      .filter(|c| c.is_idle(self.max_idle))
+      .map(|c| c.id.to_string())
+      .collect::<Vec<String>>();
+    Ok(res)
+  }
+
   async fn cart_get_by_id(&self, r: CartByIdRequest) -> ServiceResult<CartObject> {
     // Try to find cart by id
     let res = self
       .carts
       .lock()
       .await
-      .find_id(
-        &Uuid::from_str(&r.cart_id)
-          .map_err(|_| ServiceError::bad_request("A kért kosár ID hibás"))?,
-      )?
+      .find_id(&str_to_uuid(&r.cart_id)?)?
       .unpack()
       .clone();
     // Return it as cart object
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
   async fn cart_get_info_bulk(&self, r: CartBulkRequest) -> ServiceResult<Vec<CartInfoObject>> {
     // Transform the IDs from Vec<String> to Vec<Uuid>
     let mut ids: Vec<Uuid> = Vec::new();
     for id in r.cart_ids {
-      ids.push(
-        Uuid::from_str(&id).map_err(|_| ServiceError::BadRequest("A kért ID hibás".to_string()))?,
-      );
+      ids.push(str_to_uuid(&id)?);
     }
     // Try to find and transform the suitable carts
     let res = self
@@ -112,6 +203,15 @@ impl PurchaseService {
   }
 
   async fn cart_add_customer(&self, r: CartAddCustomerReuqest) -> ServiceResult<CartObject> {
+    let customer = cart::Customer {
+      id: r.customer_id,
+      name: r.customer_name,
+      zip: r.customer_zip,
+      location: r.customer_location,
+      street: r.customer_street,
+      tax_number: r.tax_number,
+      payment_term: r.payment_term,
+    };
     // Try to find cart and add customer
     let res = self
       .carts
@@ -120,16 +220,19 @@ impl PurchaseService {
       .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .add_customer(Some(cart::Customer {
-        id: r.customer_id,
-        name: r.customer_name,
-        zip: r.customer_zip,
-        location: r.customer_location,
-        street: r.customer_street,
-        tax_number: r.tax_number,
-      }))
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::AddCustomer {
+          customer_id: customer.id,
+        },
+        |c| {
+          c.add_customer(Some(customer));
+          Ok(())
+        },
+      )?
       .clone();
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
   async fn cart_remove_customer(&self, r: CartRemoveCustomerRequest) -> ServiceResult<CartObject> {
@@ -140,12 +243,19 @@ impl PurchaseService {
       .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .add_customer(None)
+      .check_version(r.expected_version)?
+      .record(r.created_by, CartEventKind::RemoveCustomer, |c| {
+        c.add_customer(None);
+        Ok(())
+      })?
       .clone();
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
   async fn cart_add_sku(&self, r: CartAddSkuRequest) -> ServiceResult<CartObject> {
+    let vat = cart::VAT::from_str(&r.vat)?;
+    let (sku, piece, name) = (r.sku_id, r.piece, r.name);
+    let (retail_price_net, retail_price_gross) = (r.retail_price_net, r.retail_price_gross);
     let res = self
       .carts
       .lock()
@@ -153,19 +263,17 @@ impl PurchaseService {
       .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .add_sku(
-        r.sku_id,
-        r.piece,
-        r.name,
-        cart::VAT::from_str(&r.vat).map_err(|e| ServiceError::bad_request(&e))?,
-        r.retail_price_net,
-        r.retail_price_gross,
-      )
+      .check_version(r.expected_version)?
+      .record(r.created_by, CartEventKind::AddSku { sku, piece }, |c| {
+        c.add_sku(sku, piece, name, vat, retail_price_net, retail_price_gross);
+        Ok(())
+      })?
       .clone();
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
   async fn cart_remove_sku(&self, r: CartRemoveSkuRequest) -> ServiceResult<CartObject> {
+    let sku_id = r.sku_id;
     let res = self
       .carts
       .lock()
@@ -173,23 +281,26 @@ impl PurchaseService {
       .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .remove_sku(r.sku_id)
-      .map_err(|e| ServiceError::bad_request(&e))?
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::RemoveSku { sku: r.sku_id },
+        |c| c.remove_sku(sku_id).map(|_| ()),
+      )?
       .clone();
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
   async fn cart_add_upl(&self, r: CartAddUplRequest) -> ServiceResult<CartObject> {
-    let u = r
-      .upl
-      .ok_or(ServiceError::internal_error("Missing UPL object!"))?;
+    let u = r.upl.ok_or(DomainError::MissingField {
+      field: "upl".to_string(),
+    })?;
 
     let new_upl_info_object = cart::UplInfoObject {
       upl_id: u.upl_id,
-      kind: match u
-        .upl_kind
-        .ok_or(ServiceError::internal_error("Missing KIND!"))?
-      {
+      kind: match u.upl_kind.ok_or(DomainError::MissingField {
+        field: "upl_kind".to_string(),
+      })? {
         proto::purchase::upl_info_object::UplKind::Sku(s) => cart::UplKind::Sku {
           sku: s.sku,
           piece: s.piece,
@@ -201,18 +312,21 @@ impl PurchaseService {
       },
       name: u.name,
       retail_net_price: u.retail_net_price,
-      vat: cart::VAT::from_str(&u.vat).map_err(|e| ServiceError::bad_request(&e))?,
+      vat: cart::VAT::from_str(&u.vat)?,
       retail_gross_price: u.retail_gross_price,
       procurement_net_price: u.procurement_net_price,
-      best_before: match u.best_before.len() > 0 {
-        true => Some(
-          DateTime::parse_from_rfc3339(&u.best_before)
-            .map_err(|_| ServiceError::internal_error("A megadott dátum hibás"))?
-            .with_timezone(&Utc),
-        ),
+      best_before: match !u.best_before.is_empty() {
+        true => Some(string_to_date(&u.best_before)?),
         false => None,
       },
       depreciated: u.depreciated,
+      price_override: None,
+      // Empty unit keeps the whole UPL price for older clients
+      unit: unit::parse(&u.unit)?,
+      expiry_authorized_by: match r.expiry_authorized_by {
+        0 => None,
+        x => Some(x),
+      },
     };
     let res = self
       .carts
@@ -221,13 +335,20 @@ impl PurchaseService {
       .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .add_upl(new_upl_info_object)
-      .map_err(|e| ServiceError::bad_request(&e))?
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::AddUpl {
+          upl_id: new_upl_info_object.upl_id.to_string(),
+        },
+        |c| c.add_upl(new_upl_info_object).map(|_| ()),
+      )?
       .clone();
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
   async fn cart_remove_upl(&self, r: CartRemoveUplRequest) -> ServiceResult<CartObject> {
+    let upl_id = r.upl_id;
     let res = self
       .carts
       .lock()
@@ -235,15 +356,21 @@ impl PurchaseService {
       .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .remove_upl(r.upl_id)
-      .map_err(|e| ServiceError::bad_request(&e))?
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::RemoveUpl {
+          upl_id: upl_id.to_string(),
+        },
+        |c| c.remove_upl(upl_id).map(|_| ()),
+      )?
       .clone();
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
   async fn cart_set_payment(&self, r: CartSetPaymentRequest) -> ServiceResult<CartObject> {
     let p: proto::purchase::PaymentKind = proto::purchase::PaymentKind::from_i32(r.payment_kind)
-      .ok_or(ServiceError::internal_error("PaymentKind decode error"))?;
+      .ok_or(DomainError::InvalidPaymentKind)?;
 
     let payment = match p {
       proto::purchase::PaymentKind::Cash => cart::PaymentKind::Cash,
@@ -258,12 +385,27 @@ impl PurchaseService {
       .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .set_payment(payment)
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::SetPayment {
+          payment_kind: payment.to_string(),
+        },
+        |c| {
+          c.set_payment(payment);
+          Ok(())
+        },
+      )?
       .clone();
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
-  async fn cart_add_payment(&self, r: CartAddPaymentRequest) -> ServiceResult<CartObject> {
+  async fn cart_set_date_completion(
+    &self,
+    r: CartSetDateCompletionRequest,
+  ) -> ServiceResult<CartObject> {
+    let date_completion = string_to_date(&r.date_completion)?;
+    let date_limits = self.date_limits;
     let res = self
       .carts
       .lock()
@@ -271,15 +413,26 @@ impl PurchaseService {
       .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .add_payment(cart::Payment {
-        payment_id: r.payment_id,
-        amount: r.amount,
-      })
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::SetDateCompletion { date_completion },
+        |c| {
+          c.set_date_completion(date_completion, date_limits)
+            .map(|_| ())
+        },
+      )?
       .clone();
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
-  async fn cart_set_sku_piece(&self, r: CartSetSkuPieceRequest) -> ServiceResult<CartObject> {
+  async fn cart_set_invoice_dates(
+    &self,
+    r: CartSetInvoiceDatesRequest,
+  ) -> ServiceResult<CartObject> {
+    let date_completion = string_to_date(&r.date_completion)?;
+    let payment_duedate = string_to_date(&r.payment_duedate)?;
+    let date_limits = self.date_limits;
     let res = self
       .carts
       .lock()
@@ -287,13 +440,36 @@ impl PurchaseService {
       .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .set_sku_piece(r.sku, r.piece)
-      .map_err(|e| ServiceError::bad_request(&e))?
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::SetInvoiceDates {
+          date_completion,
+          payment_duedate,
+        },
+        |c| {
+          c.set_invoice_dates(date_completion, payment_duedate, date_limits)
+            .map(|_| ())
+        },
+      )?
       .clone();
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
-  async fn cart_set_owner(&self, r: CartSetOwnerRequest) -> ServiceResult<CartObject> {
+  async fn cart_add_payment(&self, r: CartAddPaymentRequest) -> ServiceResult<CartObject> {
+    let payment_kind: proto::purchase::PaymentKind =
+      proto::purchase::PaymentKind::from_i32(r.payment_kind)
+        .ok_or(DomainError::InvalidPaymentKind)?;
+
+    let payment = cart::Payment {
+      payment_id: r.payment_id,
+      amount: r.amount,
+      kind: payment_kind.into(),
+      tendered: 0,
+      change: 0,
+      reversal: None,
+    };
+
     let res = self
       .carts
       .lock()
@@ -301,12 +477,25 @@ impl PurchaseService {
       .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .set_owner(r.owner_uid)
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::AddPayment {
+          payment_id: payment.payment_id.to_string(),
+          amount: payment.amount,
+        },
+        |c| {
+          c.add_payment(payment);
+          Ok(())
+        },
+      )?
       .clone();
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
-  async fn cart_set_store(&self, r: CartSetStoreRequest) -> ServiceResult<CartObject> {
+  async fn cart_add_cash_payment(&self, r: CartAddCashPaymentRequest) -> ServiceResult<CartObject> {
+    let amount_tendered = r.amount_tendered;
+    let payment_id = r.payment_id;
     let res = self
       .carts
       .lock()
@@ -314,420 +503,1650 @@ impl PurchaseService {
       .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .set_store_id(if r.store_id == 0 {
-        None
-      } else {
-        Some(r.store_id)
-      })
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::AddCashPayment {
+          payment_id: payment_id.to_string(),
+          tendered: r.amount_tendered,
+        },
+        |c| c.add_cash_payment(payment_id, amount_tendered).map(|_| ()),
+      )?
       .clone();
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
-  async fn cart_close(&self, r: CartCloseRequest) -> ServiceResult<CartObject> {
-    // Try to close cart!
+  async fn cart_reverse_payment(&self, r: CartReversePaymentRequest) -> ServiceResult<CartObject> {
+    let amount = r.amount;
+    let created_by = r.created_by;
+    let (payment_id, reversal_id, reason) = (r.payment_id, r.reversal_id, r.reason);
+    let kind = CartEventKind::ReversePayment {
+      payment_id: payment_id.to_string(),
+      reversal_id: reversal_id.to_string(),
+    };
     let res = self
       .carts
       .lock()
       .await
-      .find_id_mut(&string_to_uuid(r.cart_id.clone())?)?
+      .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .close_cart()
-      .map_err(|e| ServiceError::bad_request(&e))?
+      .check_version(r.expected_version)?
+      .record(r.created_by, kind, |c| {
+        c.reverse_payment(payment_id, reversal_id, amount, reason, created_by)
+          .map(|_| ())
+      })?
       .clone();
+    Ok(cart_object(res, self.near_expiry))
+  }
 
-    let purchase: purchase::Purchase = res.clone().into();
-
-    // Convert it to purchase and save it!
-    self.purchases.lock().await.insert(purchase)?;
-
-    // Remove cart finally
-    self
+  async fn cart_void_payment(&self, r: CartVoidPaymentRequest) -> ServiceResult<CartObject> {
+    let created_by = r.created_by;
+    let (payment_id, reversal_id, reason) = (r.payment_id, r.reversal_id, r.reason);
+    let kind = CartEventKind::ReversePayment {
+      payment_id: payment_id.to_string(),
+      reversal_id: reversal_id.to_string(),
+    };
+    let res = self
       .carts
       .lock()
       .await
-      .remove_pack(&string_to_uuid(r.cart_id)?)?;
+      .find_id_mut(&string_to_uuid(r.cart_id)?)?
+      .as_mut()
+      .unpack()
+      .check_version(r.expected_version)?
+      .record(r.created_by, kind, |c| {
+        c.void_payment(payment_id, reversal_id, reason, created_by)
+          .map(|_| ())
+      })?
+      .clone();
+    Ok(cart_object(res, self.near_expiry))
+  }
 
-    Ok(res.into())
+  async fn cart_set_sku_piece(&self, r: CartSetSkuPieceRequest) -> ServiceResult<CartObject> {
+    let sku = r.sku;
+    let piece = r.piece;
+    let cart_id = string_to_uuid(r.cart_id)?;
+    let mut carts = self.carts.lock().await;
+    // Previous piece is kept to be able to undo
+    let piece_before = carts
+      .find_id(&cart_id)?
+      .unpack()
+      .shopping_list
+      .iter()
+      .find(|i| i.sku == sku)
+      .map(|i| i.piece)
+      .unwrap_or(0);
+    let res = carts
+      .find_id_mut(&cart_id)?
+      .as_mut()
+      .unpack()
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::SetSkuPiece {
+          sku,
+          piece,
+          piece_before,
+        },
+        |c| c.set_sku_piece(sku, piece).map(|_| ()),
+      )?
+      .clone();
+    Ok(cart_object(res, self.near_expiry))
   }
 
-  async fn purchase_get_by_id(&self, r: PurchaseByIdRequest) -> ServiceResult<PurchaseObject> {
+  async fn cart_set_sku_price_override(
+    &self,
+    r: CartSetSkuPriceOverrideRequest,
+  ) -> ServiceResult<CartObject> {
+    let sku = r.sku;
+    let price_net = r.price_net;
+    let price_gross = r.price_gross;
+    let authorized_by = r.authorized_by;
+    let reason = cart::PriceOverrideReason::from_str(&r.reason)?;
     let res = self
-      .purchases
+      .carts
       .lock()
       .await
-      .find_id_mut(&string_to_uuid(r.purchase_id)?)?
+      .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
+      .check_version(r.expected_version)?
+      .record(
+        r.authorized_by,
+        CartEventKind::SetSkuPriceOverride {
+          sku: r.sku,
+          price_gross: r.price_gross,
+        },
+        |c| {
+          c.set_sku_price_override(sku, price_net, price_gross, reason, authorized_by)
+            .map(|_| ())
+        },
+      )?
       .clone();
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
-  async fn purchase_get_all(&self) -> ServiceResult<Vec<String>> {
+  async fn cart_remove_sku_price_override(
+    &self,
+    r: CartRemoveSkuPriceOverrideRequest,
+  ) -> ServiceResult<CartObject> {
+    let sku = r.sku;
     let res = self
-      .purchases
+      .carts
       .lock()
       .await
-      .iter()
-      .map(|p| p.unpack().id.to_string())
-      .collect::<Vec<String>>();
-    Ok(res)
+      .find_id_mut(&string_to_uuid(r.cart_id)?)?
+      .as_mut()
+      .unpack()
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::RemoveSkuPriceOverride { sku: r.sku },
+        |c| c.remove_sku_price_override(sku).map(|_| ()),
+      )?
+      .clone();
+    Ok(cart_object(res, self.near_expiry))
   }
 
-  async fn purchase_get_info_bulk(
+  async fn cart_set_upl_price_override(
     &self,
-    r: PurchaseBulkRequest,
-  ) -> ServiceResult<Vec<PurchaseInfoObject>> {
-    // Transform bulk IDS to Uuid vector
-    let mut ids: Vec<Uuid> = Vec::new();
-    for id in r.purchase_ids {
-      ids.push(
-        Uuid::from_str(&id).map_err(|_| ServiceError::BadRequest("A kért ID hibás".to_string()))?,
-      );
-    }
-
+    r: CartSetUplPriceOverrideRequest,
+  ) -> ServiceResult<CartObject> {
+    let price_net = r.price_net;
+    let price_gross = r.price_gross;
+    let authorized_by = r.authorized_by;
+    let upl_id = r.upl_id;
+    let reason = cart::PriceOverrideReason::from_str(&r.reason)?;
     let res = self
-      .purchases
+      .carts
       .lock()
       .await
-      .iter()
-      .filter(|p| ids.contains(&p.unpack().id))
-      .map(|p| p.unpack().clone().into())
-      .collect::<Vec<PurchaseInfoObject>>();
-
-    Ok(res.into())
+      .find_id_mut(&string_to_uuid(r.cart_id)?)?
+      .as_mut()
+      .unpack()
+      .check_version(r.expected_version)?
+      .record(
+        r.authorized_by,
+        CartEventKind::SetUplPriceOverride {
+          upl_id: upl_id.to_string(),
+          price_gross: r.price_gross,
+        },
+        |c| {
+          c.set_upl_price_override(upl_id, price_net, price_gross, reason, authorized_by)
+            .map(|_| ())
+        },
+      )?
+      .clone();
+    Ok(cart_object(res, self.near_expiry))
   }
 
-  async fn cart_set_document(&self, r: CartSetDocumentRequest) -> ServiceResult<CartObject> {
-    let document_kind: proto::purchase::DocumentKind = DocumentKind::from_i32(r.document_kind)
-      .ok_or(ServiceError::bad_request("dokumentum típus kódolási hiba"))?;
-
+  async fn cart_remove_upl_price_override(
+    &self,
+    r: CartRemoveUplPriceOverrideRequest,
+  ) -> ServiceResult<CartObject> {
+    let upl_id = r.upl_id;
     let res = self
       .carts
       .lock()
       .await
-      .find_id_mut(
-        &Uuid::from_str(&r.cart_id)
-          .map_err(|_| ServiceError::BadRequest("A kért kosár ID hibás".to_string()))?,
-      )?
+      .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .set_document(match document_kind {
-        DocumentKind::Receipt => cart::DocumentKind::Receipt,
-        DocumentKind::Invoice => cart::DocumentKind::Invoice,
-      })
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::RemoveUplPriceOverride {
+          upl_id: upl_id.to_string(),
+        },
+        |c| c.remove_upl_price_override(upl_id).map(|_| ()),
+      )?
       .clone();
-
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
-  async fn purchase_set_invoice_id(
-    &self,
-    r: PurchaseSetInvoiceIdRequest,
-  ) -> ServiceResult<PurchaseObject> {
+  async fn cart_set_owner(&self, r: CartSetOwnerRequest) -> ServiceResult<CartObject> {
+    let owner_uid = r.owner_uid;
     let res = self
-      .purchases
+      .carts
       .lock()
       .await
-      .find_id_mut(
-        &Uuid::from_str(&r.purchase_id)
-          .map_err(|_| ServiceError::BadRequest("A kért vásárlás ID hibás".to_string()))?,
-      )?
+      .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .set_invoice_id(r.invoice_id)
-      .map_err(|e| ServiceError::bad_request(&e))?
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::SetOwner {
+          owner_uid: r.owner_uid,
+        },
+        |c| {
+          c.set_owner(owner_uid);
+          Ok(())
+        },
+      )?
       .clone();
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
-  async fn cart_commitment_add(&self, r: AddCommitmentRequest) -> ServiceResult<CartObject> {
+  async fn cart_set_store(&self, r: CartSetStoreRequest) -> ServiceResult<CartObject> {
+    // 0 means no store
+    let store_id = match r.store_id {
+      0 => None,
+      _ => Some(r.store_id),
+    };
     let res = self
       .carts
       .lock()
       .await
-      .find_id_mut(
-        &Uuid::from_str(&r.cart_id)
-          .map_err(|_| ServiceError::BadRequest("A kért vásárlás ID hibás".to_string()))?,
-      )?
+      .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .add_commitment(
-        Uuid::from_str(&r.commitment_id)
-          .map_err(|_| ServiceError::BadRequest("A kért vásárlás ID hibás".to_string()))?,
-        r.discount_percentage,
-      )
-      .map_err(|e| ServiceError::bad_request(&e))?
+      .check_version(r.expected_version)?
+      .record(r.created_by, CartEventKind::SetStore { store_id }, |c| {
+        c.set_store_id(store_id);
+        Ok(())
+      })?
       .clone();
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
-  async fn cart_commitment_remove(&self, r: RemoveCommitmentRequest) -> ServiceResult<CartObject> {
+  async fn cart_close(&self, r: CartCloseRequest) -> ServiceResult<CartObject> {
+    // Try to close cart!
     let res = self
       .carts
       .lock()
       .await
-      .find_id_mut(&str_to_uuid(&r.cart_id, "A kért vásárlás ID hibás")?)?
+      .find_id_mut(&string_to_uuid(r.cart_id.clone())?)?
       .as_mut()
       .unpack()
-      .remove_commitment()
-      .map_err(|e| ServiceError::bad_request(&e))?
+      .check_version(r.expected_version)?
+      .record(r.created_by, CartEventKind::Close, |c| {
+        c.close_cart().map(|_| ())
+      })?
       .clone();
-    Ok(res.into())
-  }
 
-  async fn cart_loyalty_card_add(&self, r: LoyaltyCardAddRequest) -> ServiceResult<CartObject> {
-    let res = self
+    let purchase: purchase::Purchase = res.clone().into();
+
+    // Convert it to purchase and save it!
+    self.purchases.lock().await.insert(purchase)?;
+
+    // Remove cart finally
+    self
       .carts
       .lock()
       .await
-      .find_id_mut(&str_to_uuid(&r.cart_id, "A kért vásárlás ID hibás")?)?
+      .remove_pack(&string_to_uuid(r.cart_id)?)?;
+
+    Ok(cart_object(res, self.near_expiry))
+  }
+
+  async fn cart_remove(&self, r: CartRemoveRequest) -> ServiceResult<()> {
+    let cart_id = string_to_uuid(r.cart_id)?;
+
+    let mut carts = self.carts.lock().await;
+
+    // Check if cart can be removed
+    let cart = carts
+      .find_id_mut(&cart_id)?
       .as_mut()
       .unpack()
-      .add_loyalty_card(
-        str_to_uuid(&r.account_id, "A megadott törzsvásárlói fiók ID hibás")?,
-        r.card_id,
-        cart::LoyaltyLevel::from_str(&r.loyalty_level)
-          .map_err(|e| ServiceError::bad_request(&e))?,
-      )
-      .map_err(|e| ServiceError::bad_request(&e))?
+      .check_version(r.expected_version)?
+      .check_removable()?
       .clone();
-    Ok(res.into())
+
+    // Archive it as an abandoned cart
+    self
+      .abandoned_carts
+      .lock()
+      .await
+      .insert(abandoned::AbandonedCart::new(cart, r.reason, r.removed_by))?;
+
+    // Remove cart finally
+    carts.remove_pack(&cart_id)?;
+
+    Ok(())
   }
 
-  async fn cart_loyalty_card_remove(
-    &self,
-    r: LoyaltyCardRemoveRequest,
-  ) -> ServiceResult<CartObject> {
+  async fn cart_park(&self, r: CartParkRequest) -> ServiceResult<CartObject> {
+    let parked_by = r.parked_by;
+    let label = r.label;
     let res = self
       .carts
       .lock()
       .await
-      .find_id_mut(&str_to_uuid(&r.cart_id, "A kért vásárlás ID hibás")?)?
+      .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .remove_loyalty_card()
-      .map_err(|e| ServiceError::bad_request(&e))?
+      .check_version(r.expected_version)?
+      .record(
+        r.parked_by,
+        CartEventKind::Park {
+          label: label.to_string(),
+        },
+        |c| c.park(label, parked_by).map(|_| ()),
+      )?
       .clone();
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
-  async fn cart_burn_points(&self, r: BurnPointsRequest) -> ServiceResult<CartObject> {
+  async fn cart_resume(&self, r: CartResumeRequest) -> ServiceResult<CartObject> {
+    let owner_uid = r.owner_uid;
     let res = self
       .carts
       .lock()
       .await
-      .find_id_mut(&str_to_uuid(&r.cart_id, "A kért vásárlás ID hibás")?)?
+      .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .burn_points(
-        str_to_uuid(&r.loyalty_account_id, "Hibás törzsvásárlói fiók ID")?,
-        str_to_uuid(&r.transaction_id, "Hibás tranzakció azonosító")?,
-        r.points_to_burn,
-      )
-      .map_err(|e| ServiceError::bad_request(&e))?
+      .check_version(r.expected_version)?
+      .record(r.owner_uid, CartEventKind::Resume, |c| {
+        c.resume(owner_uid).map(|_| ())
+      })?
       .clone();
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
 
-  async fn purchase_set_loyalty_summary(
-    &self,
-    r: PurchaseSummary,
-  ) -> ServiceResult<PurchaseObject> {
+  async fn cart_get_parked(&self, r: CartGetParkedRequest) -> ServiceResult<Vec<ParkedCartObject>> {
     let res = self
-      .purchases
+      .carts
+      .lock()
+      .await
+      .iter()
+      .map(|c| c.unpack())
+      .filter(|c| c.store_id == Some(r.store_id))
+      .filter_map(|c| {
+        c.parked.as_ref().map(|p| ParkedCartObject {
+          cart_id: c.id.to_string(),
+          label: p.label.to_string(),
+          customer_name: match &c.customer {
+            Some(customer) => customer.name.to_string(),
+            None => "".to_string(),
+          },
+          total_gross: c.total_gross,
+          owner_uid: c.owner_uid,
+          parked_by: p.parked_by,
+          parked_at: p.parked_at.to_rfc3339(),
+          overdue: c.is_parked_overdue(self.max_parked),
+        })
+      })
+      .collect::<Vec<ParkedCartObject>>();
+    Ok(res)
+  }
+
+  async fn cart_set_preorder(&self, r: CartSetPreorderRequest) -> ServiceResult<CartObject> {
+    let expected_arrival = string_to_date(&r.expected_arrival)?;
+    let deposit = r.deposit;
+    let created_by = r.created_by;
+    let res = self
+      .carts
       .lock()
       .await
-      .find_id_mut(&str_to_uuid(&r.purchase_id, "A kért vásárlás ID hibás")?)?
+      .find_id_mut(&string_to_uuid(r.cart_id)?)?
       .as_mut()
       .unpack()
-      .set_loyalty_summary(
-        r.balance_opening,
-        r.burned_points,
-        r.earned_points,
-        r.balance_closing,
-      )
-      .map_err(|e| ServiceError::bad_request(&e))?
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::SetPreorder {
+          expected_arrival,
+          deposit,
+        },
+        |c| {
+          c.set_preorder(expected_arrival, deposit, created_by)
+            .map(|_| ())
+        },
+      )?
       .clone();
-    Ok(res.into())
+    Ok(cart_object(res, self.near_expiry))
   }
-}
-
-fn str_to_uuid(str: &str, error_msg: &str) -> ServiceResult<Uuid> {
-  Uuid::from_str(str).map_err(|_| ServiceError::bad_request(error_msg))
-}
 
-#[tonic::async_trait]
+  async fn cart_remove_preorder(&self, r: CartRemovePreorderRequest) -> ServiceResult<CartObject> {
+    let res = self
+      .carts
+      .lock()
+      .await
+      .find_id_mut(&string_to_uuid(r.cart_id)?)?
+      .as_mut()
+      .unpack()
+      .check_version(r.expected_version)?
+      .record(r.created_by, CartEventKind::RemovePreorder, |c| {
+        c.remove_preorder().map(|_| ())
+      })?
+      .clone();
+    Ok(cart_object(res, self.near_expiry))
+  }
+
+  async fn cart_fulfil_preorder(&self, r: CartFulfilPreorderRequest) -> ServiceResult<CartObject> {
+    let fulfilled_by = r.fulfilled_by;
+    let res = self
+      .carts
+      .lock()
+      .await
+      .find_id_mut(&string_to_uuid(r.cart_id)?)?
+      .as_mut()
+      .unpack()
+      .check_version(r.expected_version)?
+      .record(r.fulfilled_by, CartEventKind::FulfilPreorder, |c| {
+        c.fulfil_preorder(fulfilled_by).map(|_| ())
+      })?
+      .clone();
+    Ok(cart_object(res, self.near_expiry))
+  }
+
+  async fn cart_get_preorders(
+    &self,
+    r: CartGetPreordersRequest,
+  ) -> ServiceResult<Vec<PreorderCartObject>> {
+    let now = Utc::now();
+    let res = self
+      .carts
+      .lock()
+      .await
+      .iter()
+      .map(|c| c.unpack())
+      .filter(|c| c.store_id == Some(r.store_id))
+      .filter_map(|c| {
+        c.preorder.as_ref().map(|p| PreorderCartObject {
+          cart_id: c.id.to_string(),
+          customer_name: match &c.customer {
+            Some(customer) => customer.name.to_string(),
+            None => "".to_string(),
+          },
+          item_names: c
+            .shopping_list
+            .iter()
+            .map(|i| i.name.to_string())
+            .collect::<Vec<String>>(),
+          total_gross: c.total_gross,
+          deposit: p.deposit,
+          deposit_due: c.get_deposit_due(),
+          balance: c.get_balance(),
+          expected_arrival: p.expected_arrival.to_rfc3339(),
+          fulfilled: p.is_fulfilled(),
+          overdue: !p.is_fulfilled() && p.expected_arrival < now,
+        })
+      })
+      .collect::<Vec<PreorderCartObject>>();
+    Ok(res)
+  }
+
+  async fn abandoned_cart_get_all(&self) -> ServiceResult<Vec<String>> {
+    let res = self
+      .abandoned_carts
+      .lock()
+      .await
+      .iter()
+      .map(|c| c.unpack().id.to_string())
+      .collect::<Vec<String>>();
+    Ok(res)
+  }
+
+  async fn abandoned_cart_get_by_id(
+    &self,
+    r: AbandonedCartByIdRequest,
+  ) -> ServiceResult<AbandonedCartObject> {
+    let res = self
+      .abandoned_carts
+      .lock()
+      .await
+      .find_id(&string_to_uuid(r.cart_id)?)?
+      .unpack()
+      .clone();
+    Ok(res.into())
+  }
+
+  async fn abandoned_cart_get_upls_to_release(&self) -> ServiceResult<Vec<String>> {
+    // Removed carts whose UPLs are not reshelved yet
+    let res = self
+      .abandoned_carts
+      .lock()
+      .await
+      .iter()
+      .map(|c| c.unpack())
+      .filter(|c| c.has_upls_to_release())
+      .map(|c| c.id.to_string())
+      .collect::<Vec<String>>();
+    Ok(res)
+  }
+
+  async fn abandoned_cart_set_upls_released(
+    &self,
+    r: AbandonedCartSetUplsReleasedRequest,
+  ) -> ServiceResult<AbandonedCartObject> {
+    let res = self
+      .abandoned_carts
+      .lock()
+      .await
+      .find_id_mut(&string_to_uuid(r.cart_id)?)?
+      .as_mut()
+      .unpack()
+      .set_upls_released(r.released_by)?
+      .clone();
+    Ok(res.into())
+  }
+
+  async fn purchase_get_by_id(
+    &self,
+    r: PurchaseByIdRequest,
+    locale: i18n::Locale,
+  ) -> ServiceResult<PurchaseObject> {
+    let res = self
+      .purchases
+      .lock()
+      .await
+      .find_id_mut(&string_to_uuid(r.purchase_id)?)?
+      .as_mut()
+      .unpack()
+      .clone();
+    Ok(PurchaseObject {
+      receipt_texts: i18n::receipt_texts(&res, locale),
+      ..res.into()
+    })
+  }
+
+  async fn purchase_get_all(&self) -> ServiceResult<Vec<String>> {
+    let res = self
+      .purchases
+      .lock()
+      .await
+      .iter()
+      .map(|p| p.unpack().id.to_string())
+      .collect::<Vec<String>>();
+    Ok(res)
+  }
+
+  async fn purchase_get_info_bulk(
+    &self,
+    r: PurchaseBulkRequest,
+  ) -> ServiceResult<Vec<PurchaseInfoObject>> {
+    // Transform bulk IDS to Uuid vector
+    let mut ids: Vec<Uuid> = Vec::new();
+    for id in r.purchase_ids {
+      ids.push(str_to_uuid(&id)?);
+    }
+
+    let res = self
+      .purchases
+      .lock()
+      .await
+      .iter()
+      .filter(|p| ids.contains(&p.unpack().id))
+      .map(|p| p.unpack().clone().into())
+      .collect::<Vec<PurchaseInfoObject>>();
+
+    Ok(res)
+  }
+
+  async fn purchase_restore(&self, r: PurchaseRestoreRequest) -> ServiceResult<PurchaseObject> {
+    let purchase_id = string_to_uuid(r.purchase_id)?;
+
+    let mut purchases = self.purchases.lock().await;
+
+    // Rebuild purchase as a new cart
+    let mut new_cart = cart::Cart::try_from(purchases.find_id(&purchase_id)?.unpack().clone())?;
+    new_cart.set_owner(r.created_by);
+    new_cart.created_by = r.created_by;
+
+    // Set purchase restored
+    let res = purchases
+      .find_id_mut(&purchase_id)?
+      .as_mut()
+      .unpack()
+      .set_restored(new_cart.id, r.created_by)?
+      .clone();
+
+    // Insert the new cart to the carts DB
+    self.carts.lock().await.insert(new_cart)?;
+
+    Ok(res.into())
+  }
+
+  async fn purchase_refund(&self, r: PurchaseRefundRequest) -> ServiceResult<RefundObject> {
+    let purchase_id = string_to_uuid(r.purchase_id)?;
+
+    let mut purchases = self.purchases.lock().await;
+
+    // Create refund for the requested UPLs
+    let refund = refund::Refund::new(
+      purchases.find_id(&purchase_id)?.unpack(),
+      r.upl_ids,
+      r.payments
+        .into_iter()
+        .map(|p| {
+          Ok(purchase::Payment {
+            payment_id: p.payment_id,
+            amount: p.amount,
+            kind: proto::purchase::PaymentKind::from_i32(p.payment_kind)
+              .ok_or(DomainError::InvalidPaymentKind)?
+              .into(),
+            tendered: p.amount_tendered,
+            change: p.change,
+            reversal: None,
+          })
+        })
+        .collect::<ServiceResult<Vec<purchase::Payment>>>()?,
+      r.created_by,
+    )?;
+
+    // Update purchase returned figures
+    purchases
+      .find_id_mut(&purchase_id)?
+      .as_mut()
+      .unpack()
+      .add_refund(&refund)?;
+
+    // Save refund
+    self.refunds.lock().await.insert(refund.clone())?;
+
+    Ok(refund.into())
+  }
+
+  async fn refund_get_by_id(&self, r: RefundByIdRequest) -> ServiceResult<RefundObject> {
+    let res = self
+      .refunds
+      .lock()
+      .await
+      .find_id(&string_to_uuid(r.refund_id)?)?
+      .unpack()
+      .clone();
+    Ok(res.into())
+  }
+
+  async fn purchase_get_returned_upls(&self, r: PurchaseByIdRequest) -> ServiceResult<Vec<String>> {
+    let res = self
+      .purchases
+      .lock()
+      .await
+      .find_id(&string_to_uuid(r.purchase_id)?)?
+      .unpack()
+      .get_returned_upls();
+    Ok(res)
+  }
+
+  async fn cart_get_events(&self, r: CartByIdRequest) -> ServiceResult<Vec<CartEventObject>> {
+    let res = self
+      .carts
+      .lock()
+      .await
+      .find_id(&string_to_uuid(r.cart_id)?)?
+      .unpack()
+      .events
+      .iter()
+      .map(|e| e.clone().into())
+      .collect::<Vec<CartEventObject>>();
+    Ok(res)
+  }
+
+  async fn cart_validate_close(
+    &self,
+    r: CartByIdRequest,
+    locale: i18n::Locale,
+  ) -> ServiceResult<CartCloseValidation> {
+    let carts = self.carts.lock().await;
+    let cart = carts.find_id(&string_to_uuid(r.cart_id)?)?.unpack();
+    let issues = cart.validate_close();
+    Ok(CartCloseValidation {
+      cart_id: cart.id.to_string(),
+      closable: issues.is_empty(),
+      issues: issues
+        .into_iter()
+        .map(|i| CartCloseIssue {
+          message: i.localize(locale),
+          ..i.into()
+        })
+        .collect::<Vec<CartCloseIssue>>(),
+    })
+  }
+
+  async fn purchase_get_events(
+    &self,
+    r: PurchaseByIdRequest,
+  ) -> ServiceResult<Vec<CartEventObject>> {
+    let res = self
+      .purchases
+      .lock()
+      .await
+      .find_id(&string_to_uuid(r.purchase_id)?)?
+      .unpack()
+      .events
+      .iter()
+      .map(|e| e.clone().into())
+      .collect::<Vec<CartEventObject>>();
+    Ok(res)
+  }
+
+  async fn cart_refresh_promotions(
+    &self,
+    r: CartRefreshPromotionsRequest,
+  ) -> ServiceResult<CartObject> {
+    let promotions = self.get_promotions().await;
+    let promotion_ids = promotions.iter().map(|p| p.id).collect::<Vec<Uuid>>();
+    let res = self
+      .carts
+      .lock()
+      .await
+      .find_id_mut(&string_to_uuid(r.cart_id)?)?
+      .as_mut()
+      .unpack()
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::RefreshPromotions { promotion_ids },
+        |c| {
+          c.set_promotions(promotions);
+          Ok(())
+        },
+      )?
+      .clone();
+    Ok(cart_object(res, self.near_expiry))
+  }
+
+  async fn quote_new(&self, r: QuoteNewRequest) -> ServiceResult<QuoteObject> {
+    let cart = self
+      .carts
+      .lock()
+      .await
+      .find_id(&string_to_uuid(r.cart_id)?)?
+      .unpack()
+      .clone();
+
+    let mut quotes = self.quotes.lock().await;
+
+    // Quote number is yearly sequential
+    let year = Utc::today().year();
+    let number = quotes
+      .iter()
+      .filter(|q| q.unpack().created_at.year() == year)
+      .count()
+      + 1;
+
+    let new_quote = quote::Quote::new(
+      cart,
+      format!("AJ-{}-{:05}", year, number),
+      string_to_date(&r.valid_till)?,
+      r.created_by,
+    )?;
+
+    quotes.insert(new_quote.clone())?;
+
+    Ok(new_quote.into())
+  }
+
+  async fn quote_get_all(&self) -> ServiceResult<Vec<String>> {
+    let res = self
+      .quotes
+      .lock()
+      .await
+      .iter()
+      .map(|q| q.unpack().id.to_string())
+      .collect::<Vec<String>>();
+    Ok(res)
+  }
+
+  async fn quote_get_by_id(&self, r: QuoteByIdRequest) -> ServiceResult<QuoteObject> {
+    let res = self
+      .quotes
+      .lock()
+      .await
+      .find_id(&string_to_uuid(r.quote_id)?)?
+      .unpack()
+      .clone();
+    Ok(res.into())
+  }
+
+  async fn quote_convert_to_cart(&self, r: QuoteConvertRequest) -> ServiceResult<CartObject> {
+    // Current prices are used only if refresh requested
+    let prices = match r.refresh_prices {
+      true => Some(
+        r.prices
+          .into_iter()
+          .map(|p| quote::SkuPrice {
+            sku: p.sku,
+            price_net: p.price_net,
+            price_gross: p.price_gross,
+          })
+          .collect::<Vec<quote::SkuPrice>>(),
+      ),
+      false => None,
+    };
+
+    let quote_id = string_to_uuid(r.quote_id)?;
+
+    // Keep quotes locked till the quote is marked as converted
+    let mut quotes = self.quotes.lock().await;
+
+    let mut new_cart = quotes.find_id(&quote_id)?.unpack().to_cart(
+      r.owner_uid,
+      match r.store_id {
+        0 => None,
+        x => Some(x),
+      },
+      prices,
+      r.created_by,
+    )?;
+
+    // Set promotions to evaluate
+    new_cart.set_promotions(self.get_promotions().await);
+
+    self.carts.lock().await.insert(new_cart.clone())?;
+
+    // Mark quote as converted only if the new cart is stored
+    quotes
+      .find_id_mut(&quote_id)?
+      .as_mut()
+      .unpack()
+      .set_converted(new_cart.id)?;
+
+    Ok(cart_object(new_cart, self.near_expiry))
+  }
+
+  async fn promotion_new(&self, r: PromotionNewRequest) -> ServiceResult<PromotionObject> {
+    let kind = match r.kind.ok_or(DomainError::MissingField {
+      field: "kind".to_string(),
+    })? {
+      proto::purchase::promotion_new_request::Kind::BuyXPayY(p) => {
+        promotion::PromotionKind::BuyXPayY {
+          sku: p.sku,
+          buy: p.buy,
+          pay: p.pay,
+        }
+      }
+      proto::purchase::promotion_new_request::Kind::BundlePrice(p) => {
+        promotion::PromotionKind::BundlePrice {
+          items: p
+            .items
+            .into_iter()
+            .map(|i| promotion::BundleItem {
+              sku: i.sku,
+              piece: i.piece,
+            })
+            .collect(),
+          price_gross: p.price_gross,
+        }
+      }
+      proto::purchase::promotion_new_request::Kind::CategoryPercentage(p) => {
+        promotion::PromotionKind::CategoryPercentage {
+          category_id: p.category_id,
+          skus: p.skus,
+          percentage: p.percentage,
+        }
+      }
+    };
+
+    let new_promotion = promotion::Promotion::new(
+      r.name,
+      kind,
+      string_to_date(&r.valid_from)?,
+      string_to_date(&r.valid_till)?,
+      r.created_by,
+    )?;
+
+    self.promotions.lock().await.insert(new_promotion.clone())?;
+
+    Ok(new_promotion.into())
+  }
+
+  async fn promotion_get_all(&self) -> ServiceResult<Vec<String>> {
+    let res = self
+      .promotions
+      .lock()
+      .await
+      .iter()
+      .map(|p| p.unpack().id.to_string())
+      .collect::<Vec<String>>();
+    Ok(res)
+  }
+
+  async fn promotion_get_by_id(&self, r: PromotionByIdRequest) -> ServiceResult<PromotionObject> {
+    let res = self
+      .promotions
+      .lock()
+      .await
+      .find_id(&string_to_uuid(r.promotion_id)?)?
+      .unpack()
+      .clone();
+    Ok(res.into())
+  }
+
+  async fn promotion_remove(&self, r: PromotionRemoveRequest) -> ServiceResult<()> {
+    let promotion_id = string_to_uuid(r.promotion_id)?;
+
+    self.promotions.lock().await.remove_pack(&promotion_id)?;
+
+    // Open carts stop applying the removed promotion
+    // Restored carts keep the promotions of the sale
+    // No client holds these carts, so their version is not checked,
+    // the carts lock is kept during the whole update
+    let mut carts = self.carts.lock().await;
+    let cart_ids = carts
+      .iter()
+      .map(|c| c.unpack())
+      .filter(|c| c.promotion_time.is_none())
+      .filter(|c| c.promotions.iter().any(|p| p.id == promotion_id))
+      .map(|c| c.id)
+      .collect::<Vec<Uuid>>();
+    for cart_id in cart_ids {
+      carts.find_id_mut(&cart_id)?.as_mut().unpack().record(
+        r.created_by,
+        CartEventKind::RemovePromotion { promotion_id },
+        |c| {
+          c.remove_promotion(promotion_id);
+          Ok(())
+        },
+      )?;
+    }
+
+    Ok(())
+  }
+
+  async fn cart_set_document(&self, r: CartSetDocumentRequest) -> ServiceResult<CartObject> {
+    let document_kind: proto::purchase::DocumentKind =
+      DocumentKind::from_i32(r.document_kind).ok_or(DomainError::InvalidDocumentKind)?;
+
+    let document_kind = match document_kind {
+      DocumentKind::Receipt => cart::DocumentKind::Receipt,
+      DocumentKind::Invoice => cart::DocumentKind::Invoice,
+    };
+
+    let res = self
+      .carts
+      .lock()
+      .await
+      .find_id_mut(&str_to_uuid(&r.cart_id)?)?
+      .as_mut()
+      .unpack()
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::SetDocument {
+          document_kind: document_kind.to_string(),
+        },
+        |c| {
+          c.set_document(document_kind);
+          Ok(())
+        },
+      )?
+      .clone();
+
+    Ok(cart_object(res, self.near_expiry))
+  }
+
+  async fn purchase_set_invoice_id(
+    &self,
+    r: PurchaseSetInvoiceIdRequest,
+  ) -> ServiceResult<PurchaseObject> {
+    let res = self
+      .purchases
+      .lock()
+      .await
+      .find_id_mut(&str_to_uuid(&r.purchase_id)?)?
+      .as_mut()
+      .unpack()
+      .set_invoice_id(r.invoice_id)?
+      .clone();
+    Ok(res.into())
+  }
+
+  async fn cart_commitment_add(&self, r: AddCommitmentRequest) -> ServiceResult<CartObject> {
+    let discount_percentage = r.discount_percentage;
+    let commitment_id = str_to_uuid(&r.commitment_id)?;
+    let cart_id = str_to_uuid(&r.cart_id)?;
+    let mut carts = self.carts.lock().await;
+    // Replaced commitment is kept to be able to undo
+    let previous = carts
+      .find_id(&cart_id)?
+      .unpack()
+      .commitment
+      .as_ref()
+      .map(|c| (c.commitment_id, c.commitment_percentage));
+    let res = carts
+      .find_id_mut(&cart_id)?
+      .as_mut()
+      .unpack()
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::AddCommitment {
+          commitment_id,
+          previous,
+        },
+        |c| {
+          c.add_commitment(commitment_id, discount_percentage)
+            .map(|_| ())
+        },
+      )?
+      .clone();
+    Ok(cart_object(res, self.near_expiry))
+  }
+
+  async fn cart_commitment_remove(&self, r: RemoveCommitmentRequest) -> ServiceResult<CartObject> {
+    let res = self
+      .carts
+      .lock()
+      .await
+      .find_id_mut(&str_to_uuid(&r.cart_id)?)?
+      .as_mut()
+      .unpack()
+      .check_version(r.expected_version)?
+      .record(r.created_by, CartEventKind::RemoveCommitment, |c| {
+        c.remove_commitment().map(|_| ())
+      })?
+      .clone();
+    Ok(cart_object(res, self.near_expiry))
+  }
+
+  async fn cart_loyalty_card_add(&self, r: LoyaltyCardAddRequest) -> ServiceResult<CartObject> {
+    let account_id = str_to_uuid(&r.account_id)?;
+    let loyalty_level = cart::LoyaltyLevel::from_str(&r.loyalty_level)?;
+    let card_id = r.card_id;
+    let res = self
+      .carts
+      .lock()
+      .await
+      .find_id_mut(&str_to_uuid(&r.cart_id)?)?
+      .as_mut()
+      .unpack()
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::AddLoyaltyCard {
+          card_id: card_id.to_string(),
+        },
+        |c| {
+          c.add_loyalty_card(account_id, card_id, loyalty_level)
+            .map(|_| ())
+        },
+      )?
+      .clone();
+    Ok(cart_object(res, self.near_expiry))
+  }
+
+  async fn cart_loyalty_card_remove(
+    &self,
+    r: LoyaltyCardRemoveRequest,
+  ) -> ServiceResult<CartObject> {
+    let res = self
+      .carts
+      .lock()
+      .await
+      .find_id_mut(&str_to_uuid(&r.cart_id)?)?
+      .as_mut()
+      .unpack()
+      .check_version(r.expected_version)?
+      .record(r.created_by, CartEventKind::RemoveLoyaltyCard, |c| {
+        c.remove_loyalty_card().map(|_| ())
+      })?
+      .clone();
+    Ok(cart_object(res, self.near_expiry))
+  }
+
+  async fn cart_burn_points(&self, r: BurnPointsRequest) -> ServiceResult<CartObject> {
+    let points_to_burn = r.points_to_burn;
+    let loyalty_account_id = str_to_uuid(&r.loyalty_account_id)?;
+    let transaction_id = str_to_uuid(&r.transaction_id)?;
+    let res = self
+      .carts
+      .lock()
+      .await
+      .find_id_mut(&str_to_uuid(&r.cart_id)?)?
+      .as_mut()
+      .unpack()
+      .check_version(r.expected_version)?
+      .record(
+        r.created_by,
+        CartEventKind::BurnPoints {
+          transaction_id,
+          points: r.points_to_burn,
+        },
+        |c| {
+          c.burn_points(loyalty_account_id, transaction_id, points_to_burn)
+            .map(|_| ())
+        },
+      )?
+      .clone();
+    Ok(cart_object(res, self.near_expiry))
+  }
+
+  async fn cart_undo(&self, r: CartUndoRequest) -> ServiceResult<CartObject> {
+    // Transaction ID is only needed to undo burned points
+    let transaction_id = match !r.transaction_id.is_empty() {
+      true => Some(str_to_uuid(&r.transaction_id)?),
+      false => None,
+    };
+    let res = self
+      .carts
+      .lock()
+      .await
+      .find_id_mut(&string_to_uuid(r.cart_id)?)?
+      .as_mut()
+      .unpack()
+      .check_version(r.expected_version)?
+      .undo_last(r.created_by, transaction_id)?
+      .clone();
+    Ok(cart_object(res, self.near_expiry))
+  }
+
+  async fn purchase_set_loyalty_summary(
+    &self,
+    r: PurchaseSummary,
+  ) -> ServiceResult<PurchaseObject> {
+    let res = self
+      .purchases
+      .lock()
+      .await
+      .find_id_mut(&str_to_uuid(&r.purchase_id)?)?
+      .as_mut()
+      .unpack()
+      .set_loyalty_summary(
+        r.balance_opening,
+        r.burned_points,
+        r.earned_points,
+        r.balance_closing,
+      )?
+      .clone();
+    Ok(res.into())
+  }
+}
+
+// Helper to get the client locale from the request metadata
+fn get_locale<T>(request: &Request<T>) -> i18n::Locale {
+  i18n::Locale::from_metadata(request.metadata())
+}
+
+fn str_to_uuid(str: &str) -> ServiceResult<Uuid> {
+  Uuid::from_str(str).map_err(|_| {
+    DomainError::InvalidId {
+      id: str.to_string(),
+    }
+    .into()
+  })
+}
+
+#[tonic::async_trait]
 impl Purchase for PurchaseService {
   async fn cart_new(
     &self,
     request: Request<proto::purchase::CartNewRequest>,
   ) -> Result<Response<proto::purchase::CartObject>, Status> {
-    let res = self.cart_new(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .cart_new(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn cart_get_all(
+    &self,
+    _request: Request<()>,
+  ) -> Result<Response<proto::purchase::CartIds>, Status> {
+    let cart_ids = self.cart_get_all().await?;
+    Ok(Response::new(CartIds { cart_ids }))
+  }
+
+  async fn cart_get_idle(
+    &self,
+    _request: Request<()>,
+  ) -> Result<Response<proto::purchase::CartIds>, Status> {
+    let cart_ids = self.cart_get_idle().await?;
+    Ok(Response::new(CartIds { cart_ids }))
+  }
+
+  async fn cart_get_by_id(
+    &self,
+    request: Request<proto::purchase::CartByIdRequest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_get_by_id(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  type CartGetInfoBulkStream = ReceiverStream<Result<CartInfoObject, Status>>;
+
+  async fn cart_get_info_bulk(
+    &self,
+    request: Request<proto::purchase::CartBulkRequest>,
+  ) -> Result<Response<Self::CartGetInfoBulkStream>, Status> {
+    // Create channel for stream response
+    let (tx, rx) = tokio::sync::mpsc::channel(100);
+
+    // Get resources as Vec<SourceObject>
+    let locale = get_locale(&request);
+    let res = self
+      .cart_get_info_bulk(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+
+    // Send the result items through the channel
+    tokio::spawn(async move {
+      for ots in res.into_iter() {
+        tx.send(Ok(ots)).await.unwrap();
+      }
+    });
+
+    // Send back the receiver
+    Ok(Response::new(ReceiverStream::new(rx)))
+  }
+
+  async fn cart_add_customer(
+    &self,
+    request: Request<proto::purchase::CartAddCustomerReuqest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_add_customer(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn cart_remove_customer(
+    &self,
+    request: Request<proto::purchase::CartRemoveCustomerRequest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_remove_customer(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn cart_add_sku(
+    &self,
+    request: Request<proto::purchase::CartAddSkuRequest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_add_sku(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn cart_set_sku_piece(
+    &self,
+    request: Request<proto::purchase::CartSetSkuPieceRequest>,
+  ) -> Result<Response<CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_set_sku_piece(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn cart_remove_sku(
+    &self,
+    request: Request<proto::purchase::CartRemoveSkuRequest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_remove_sku(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn cart_add_upl(
+    &self,
+    request: Request<proto::purchase::CartAddUplRequest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_add_upl(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn cart_remove_upl(
+    &self,
+    request: Request<proto::purchase::CartRemoveUplRequest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_remove_upl(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn cart_set_document(
+    &self,
+    request: Request<proto::purchase::CartSetDocumentRequest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_set_document(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn cart_set_payment(
+    &self,
+    request: Request<proto::purchase::CartSetPaymentRequest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_set_payment(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn cart_set_date_completion(
+    &self,
+    request: Request<proto::purchase::CartSetDateCompletionRequest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_set_date_completion(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn cart_set_invoice_dates(
+    &self,
+    request: Request<proto::purchase::CartSetInvoiceDatesRequest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_set_invoice_dates(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn cart_add_payment(
+    &self,
+    request: Request<proto::purchase::CartAddPaymentRequest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_add_payment(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
-  async fn cart_get_all(
+  async fn cart_set_sku_price_override(
     &self,
-    _request: Request<()>,
-  ) -> Result<Response<proto::purchase::CartIds>, Status> {
-    let cart_ids = self.cart_get_all().await?;
-    Ok(Response::new(CartIds { cart_ids }))
+    request: Request<proto::purchase::CartSetSkuPriceOverrideRequest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_set_sku_price_override(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
   }
 
-  async fn cart_get_by_id(
+  async fn cart_remove_sku_price_override(
     &self,
-    request: Request<proto::purchase::CartByIdRequest>,
+    request: Request<proto::purchase::CartRemoveSkuPriceOverrideRequest>,
   ) -> Result<Response<proto::purchase::CartObject>, Status> {
-    let res = self.cart_get_by_id(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .cart_remove_sku_price_override(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
-  type CartGetInfoBulkStream = ReceiverStream<Result<CartInfoObject, Status>>;
-
-  async fn cart_get_info_bulk(
+  async fn cart_set_upl_price_override(
     &self,
-    request: Request<proto::purchase::CartBulkRequest>,
-  ) -> Result<Response<Self::CartGetInfoBulkStream>, Status> {
-    // Create channel for stream response
-    let (mut tx, rx) = tokio::sync::mpsc::channel(100);
-
-    // Get resources as Vec<SourceObject>
-    let res = self.cart_get_info_bulk(request.into_inner()).await?;
-
-    // Send the result items through the channel
-    tokio::spawn(async move {
-      for ots in res.into_iter() {
-        tx.send(Ok(ots)).await.unwrap();
-      }
-    });
-
-    // Send back the receiver
-    Ok(Response::new(ReceiverStream::new(rx)))
+    request: Request<proto::purchase::CartSetUplPriceOverrideRequest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_set_upl_price_override(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
   }
 
-  async fn cart_add_customer(
+  async fn cart_remove_upl_price_override(
     &self,
-    request: Request<proto::purchase::CartAddCustomerReuqest>,
+    request: Request<proto::purchase::CartRemoveUplPriceOverrideRequest>,
   ) -> Result<Response<proto::purchase::CartObject>, Status> {
-    let res = self.cart_add_customer(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .cart_remove_upl_price_override(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
-  async fn cart_remove_customer(
+  async fn cart_add_cash_payment(
     &self,
-    request: Request<proto::purchase::CartRemoveCustomerRequest>,
+    request: Request<proto::purchase::CartAddCashPaymentRequest>,
   ) -> Result<Response<proto::purchase::CartObject>, Status> {
-    let res = self.cart_remove_customer(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .cart_add_cash_payment(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
-  async fn cart_add_sku(
+  async fn cart_reverse_payment(
     &self,
-    request: Request<proto::purchase::CartAddSkuRequest>,
+    request: Request<proto::purchase::CartReversePaymentRequest>,
   ) -> Result<Response<proto::purchase::CartObject>, Status> {
-    let res = self.cart_add_sku(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .cart_reverse_payment(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
-  async fn cart_set_sku_piece(
+  async fn cart_void_payment(
     &self,
-    request: Request<proto::purchase::CartSetSkuPieceRequest>,
-  ) -> Result<Response<CartObject>, Status> {
-    let res = self.cart_set_sku_piece(request.into_inner()).await?;
+    request: Request<proto::purchase::CartVoidPaymentRequest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_void_payment(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
-  async fn cart_remove_sku(
+  async fn cart_set_owner(
     &self,
-    request: Request<proto::purchase::CartRemoveSkuRequest>,
+    request: Request<proto::purchase::CartSetOwnerRequest>,
   ) -> Result<Response<proto::purchase::CartObject>, Status> {
-    let res = self.cart_remove_sku(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .cart_set_owner(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
-  async fn cart_add_upl(
+  async fn cart_set_store(
     &self,
-    request: Request<proto::purchase::CartAddUplRequest>,
+    request: Request<proto::purchase::CartSetStoreRequest>,
   ) -> Result<Response<proto::purchase::CartObject>, Status> {
-    let res = self.cart_add_upl(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .cart_set_store(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
-  async fn cart_remove_upl(
+  async fn cart_close(
     &self,
-    request: Request<proto::purchase::CartRemoveUplRequest>,
+    request: Request<proto::purchase::CartCloseRequest>,
   ) -> Result<Response<proto::purchase::CartObject>, Status> {
-    let res = self.cart_remove_upl(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .cart_close(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
-  async fn cart_set_document(
+  async fn cart_remove(
     &self,
-    request: Request<proto::purchase::CartSetDocumentRequest>,
+    request: Request<proto::purchase::CartRemoveRequest>,
+  ) -> Result<Response<()>, Status> {
+    let locale = get_locale(&request);
+    let _ = self
+      .cart_remove(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(()))
+  }
+
+  async fn cart_park(
+    &self,
+    request: Request<proto::purchase::CartParkRequest>,
   ) -> Result<Response<proto::purchase::CartObject>, Status> {
-    let res = self.cart_set_document(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .cart_park(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
-  async fn cart_set_payment(
+  async fn cart_resume(
     &self,
-    request: Request<proto::purchase::CartSetPaymentRequest>,
+    request: Request<proto::purchase::CartResumeRequest>,
   ) -> Result<Response<proto::purchase::CartObject>, Status> {
-    let res = self.cart_set_payment(request.into_inner()).await?;
-    Ok(Response::new(res.into()))
+    let locale = get_locale(&request);
+    let res = self
+      .cart_resume(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
   }
 
-  async fn cart_add_payment(
+  async fn cart_get_parked(
     &self,
-    request: Request<proto::purchase::CartAddPaymentRequest>,
-  ) -> Result<Response<proto::purchase::CartObject>, Status> {
-    let res = self.cart_add_payment(request.into_inner()).await?;
-    Ok(Response::new(res.into()))
+    request: Request<proto::purchase::CartGetParkedRequest>,
+  ) -> Result<Response<proto::purchase::ParkedCarts>, Status> {
+    let locale = get_locale(&request);
+    let carts = self
+      .cart_get_parked(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(ParkedCarts { carts }))
   }
 
-  async fn cart_set_owner(
+  async fn cart_set_preorder(
     &self,
-    request: Request<proto::purchase::CartSetOwnerRequest>,
+    request: Request<proto::purchase::CartSetPreorderRequest>,
   ) -> Result<Response<proto::purchase::CartObject>, Status> {
-    let res = self.cart_set_owner(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .cart_set_preorder(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
-  async fn cart_set_store(
+  async fn cart_remove_preorder(
     &self,
-    request: Request<proto::purchase::CartSetStoreRequest>,
+    request: Request<proto::purchase::CartRemovePreorderRequest>,
   ) -> Result<Response<proto::purchase::CartObject>, Status> {
-    let res = self.cart_set_store(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .cart_remove_preorder(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
-  async fn cart_close(
+  async fn cart_fulfil_preorder(
     &self,
-    request: Request<proto::purchase::CartCloseRequest>,
+    request: Request<proto::purchase::CartFulfilPreorderRequest>,
   ) -> Result<Response<proto::purchase::CartObject>, Status> {
-    let res = self.cart_close(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .cart_fulfil_preorder(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
-  async fn cart_remove(
+  async fn cart_get_preorders(
     &self,
-    request: Request<proto::purchase::CartRemoveRequest>,
-  ) -> Result<Response<()>, Status> {
-    todo!()
+    request: Request<proto::purchase::CartGetPreordersRequest>,
+  ) -> Result<Response<proto::purchase::PreorderCarts>, Status> {
+    let locale = get_locale(&request);
+    let carts = self
+      .cart_get_preorders(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(PreorderCarts { carts }))
+  }
+
+  async fn abandoned_cart_get_all(
+    &self,
+    _request: Request<()>,
+  ) -> Result<Response<proto::purchase::AbandonedCartIds>, Status> {
+    let cart_ids = self.abandoned_cart_get_all().await?;
+    Ok(Response::new(AbandonedCartIds { cart_ids }))
+  }
+
+  async fn abandoned_cart_get_by_id(
+    &self,
+    request: Request<proto::purchase::AbandonedCartByIdRequest>,
+  ) -> Result<Response<proto::purchase::AbandonedCartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .abandoned_cart_get_by_id(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn abandoned_cart_get_upls_to_release(
+    &self,
+    _request: Request<()>,
+  ) -> Result<Response<proto::purchase::AbandonedCartIds>, Status> {
+    let cart_ids = self.abandoned_cart_get_upls_to_release().await?;
+    Ok(Response::new(AbandonedCartIds { cart_ids }))
+  }
+
+  async fn abandoned_cart_set_upls_released(
+    &self,
+    request: Request<proto::purchase::AbandonedCartSetUplsReleasedRequest>,
+  ) -> Result<Response<proto::purchase::AbandonedCartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .abandoned_cart_set_upls_released(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
   }
 
   async fn purchase_get_by_id(
     &self,
     request: Request<proto::purchase::PurchaseByIdRequest>,
   ) -> Result<Response<proto::purchase::PurchaseObject>, Status> {
-    let res = self.purchase_get_by_id(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .purchase_get_by_id(request.into_inner(), locale)
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
   async fn purchase_get_all(
     &self,
-    request: Request<()>,
+    _request: Request<()>,
   ) -> Result<Response<proto::purchase::PurchaseIds>, Status> {
     let purchase_ids = self.purchase_get_all().await?;
     Ok(Response::new(PurchaseIds { purchase_ids }))
@@ -740,10 +2159,14 @@ impl Purchase for PurchaseService {
     request: Request<proto::purchase::PurchaseBulkRequest>,
   ) -> Result<Response<Self::PurchaseGetInfoBulkStream>, Status> {
     // Create channel for stream response
-    let (mut tx, rx) = tokio::sync::mpsc::channel(100);
+    let (tx, rx) = tokio::sync::mpsc::channel(100);
 
     // Get resources as Vec<SourceObject>
-    let res = self.purchase_get_info_bulk(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .purchase_get_info_bulk(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
 
     // Send the result items through the channel
     tokio::spawn(async move {
@@ -758,14 +2181,14 @@ impl Purchase for PurchaseService {
 
   async fn puchase_create_invoice(
     &self,
-    request: Request<proto::purchase::PurchaseCreateInvoiceRequest>,
+    _request: Request<proto::purchase::PurchaseCreateInvoiceRequest>,
   ) -> Result<Response<proto::purchase::PurchaseObject>, Status> {
     todo!()
   }
 
   async fn purchase_add_payment(
     &self,
-    request: Request<proto::purchase::PurchaseAddPaymentRequest>,
+    _request: Request<proto::purchase::PurchaseAddPaymentRequest>,
   ) -> Result<Response<proto::purchase::PurchaseObject>, Status> {
     todo!()
   }
@@ -774,12 +2197,189 @@ impl Purchase for PurchaseService {
     &self,
     request: Request<proto::purchase::PurchaseRestoreRequest>,
   ) -> Result<Response<proto::purchase::PurchaseObject>, Status> {
-    todo!()
+    let locale = get_locale(&request);
+    let res = self
+      .purchase_restore(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn purchase_refund(
+    &self,
+    request: Request<proto::purchase::PurchaseRefundRequest>,
+  ) -> Result<Response<proto::purchase::RefundObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .purchase_refund(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn refund_get_by_id(
+    &self,
+    request: Request<proto::purchase::RefundByIdRequest>,
+  ) -> Result<Response<proto::purchase::RefundObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .refund_get_by_id(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn purchase_get_returned_upls(
+    &self,
+    request: Request<proto::purchase::PurchaseByIdRequest>,
+  ) -> Result<Response<proto::purchase::ReturnedUpls>, Status> {
+    let locale = get_locale(&request);
+    let upl_ids = self
+      .purchase_get_returned_upls(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(ReturnedUpls { upl_ids }))
+  }
+
+  async fn cart_get_events(
+    &self,
+    request: Request<proto::purchase::CartByIdRequest>,
+  ) -> Result<Response<proto::purchase::CartEvents>, Status> {
+    let locale = get_locale(&request);
+    let events = self
+      .cart_get_events(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(CartEvents { events }))
+  }
+
+  async fn cart_validate_close(
+    &self,
+    request: Request<proto::purchase::CartByIdRequest>,
+  ) -> Result<Response<proto::purchase::CartCloseValidation>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_validate_close(request.into_inner(), locale)
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn purchase_get_events(
+    &self,
+    request: Request<proto::purchase::PurchaseByIdRequest>,
+  ) -> Result<Response<proto::purchase::CartEvents>, Status> {
+    let locale = get_locale(&request);
+    let events = self
+      .purchase_get_events(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(CartEvents { events }))
+  }
+
+  async fn cart_refresh_promotions(
+    &self,
+    request: Request<proto::purchase::CartRefreshPromotionsRequest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_refresh_promotions(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn quote_new(
+    &self,
+    request: Request<proto::purchase::QuoteNewRequest>,
+  ) -> Result<Response<proto::purchase::QuoteObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .quote_new(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn quote_get_all(
+    &self,
+    _request: Request<()>,
+  ) -> Result<Response<proto::purchase::QuoteIds>, Status> {
+    let quote_ids = self.quote_get_all().await?;
+    Ok(Response::new(QuoteIds { quote_ids }))
+  }
+
+  async fn quote_get_by_id(
+    &self,
+    request: Request<proto::purchase::QuoteByIdRequest>,
+  ) -> Result<Response<proto::purchase::QuoteObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .quote_get_by_id(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn quote_convert_to_cart(
+    &self,
+    request: Request<proto::purchase::QuoteConvertRequest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .quote_convert_to_cart(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn promotion_new(
+    &self,
+    request: Request<proto::purchase::PromotionNewRequest>,
+  ) -> Result<Response<proto::purchase::PromotionObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .promotion_new(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn promotion_get_all(
+    &self,
+    _request: Request<()>,
+  ) -> Result<Response<proto::purchase::PromotionIds>, Status> {
+    let promotion_ids = self.promotion_get_all().await?;
+    Ok(Response::new(PromotionIds { promotion_ids }))
+  }
+
+  async fn promotion_get_by_id(
+    &self,
+    request: Request<proto::purchase::PromotionByIdRequest>,
+  ) -> Result<Response<proto::purchase::PromotionObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .promotion_get_by_id(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn promotion_remove(
+    &self,
+    request: Request<proto::purchase::PromotionRemoveRequest>,
+  ) -> Result<Response<()>, Status> {
+    let locale = get_locale(&request);
+    let _ = self
+      .promotion_remove(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(()))
   }
 
   async fn purchase_get_stat_by_interval(
     &self,
-    request: Request<proto::purchase::E>,
+    _request: Request<proto::purchase::E>,
   ) -> Result<Response<proto::purchase::PurchaseStatResponse>, Status> {
     todo!()
   }
@@ -788,7 +2388,11 @@ impl Purchase for PurchaseService {
     &self,
     request: Request<proto::purchase::LoyaltyCardAddRequest>,
   ) -> Result<Response<CartObject>, Status> {
-    let res = self.cart_loyalty_card_add(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .cart_loyalty_card_add(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
@@ -796,7 +2400,11 @@ impl Purchase for PurchaseService {
     &self,
     request: Request<proto::purchase::LoyaltyCardRemoveRequest>,
   ) -> Result<Response<CartObject>, Status> {
-    let res = self.cart_loyalty_card_remove(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .cart_loyalty_card_remove(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
@@ -804,7 +2412,23 @@ impl Purchase for PurchaseService {
     &self,
     request: Request<proto::purchase::BurnPointsRequest>,
   ) -> Result<Response<CartObject>, Status> {
-    let res = self.cart_burn_points(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .cart_burn_points(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
+    Ok(Response::new(res))
+  }
+
+  async fn cart_undo(
+    &self,
+    request: Request<proto::purchase::CartUndoRequest>,
+  ) -> Result<Response<proto::purchase::CartObject>, Status> {
+    let locale = get_locale(&request);
+    let res = self
+      .cart_undo(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
@@ -812,7 +2436,11 @@ impl Purchase for PurchaseService {
     &self,
     request: Request<proto::purchase::AddCommitmentRequest>,
   ) -> Result<Response<CartObject>, Status> {
-    let res = self.cart_commitment_add(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .cart_commitment_add(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
@@ -820,7 +2448,11 @@ impl Purchase for PurchaseService {
     &self,
     request: Request<proto::purchase::RemoveCommitmentRequest>,
   ) -> Result<Response<CartObject>, Status> {
-    let res = self.cart_commitment_remove(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .cart_commitment_remove(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
@@ -828,7 +2460,11 @@ impl Purchase for PurchaseService {
     &self,
     request: Request<proto::purchase::PurchaseSetInvoiceIdRequest>,
   ) -> Result<Response<PurchaseObject>, Status> {
-    let res = self.purchase_set_invoice_id(request.into_inner()).await?;
+    let locale = get_locale(&request);
+    let res = self
+      .purchase_set_invoice_id(request.into_inner())
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 
@@ -836,24 +2472,131 @@ impl Purchase for PurchaseService {
     &self,
     request: Request<proto::loyalty::PurchaseSummary>,
   ) -> Result<Response<PurchaseObject>, Status> {
+    let locale = get_locale(&request);
     let res = self
       .purchase_set_loyalty_summary(request.into_inner())
-      .await?;
+      .await
+      .map_err(|e| e.into_status(locale))?;
     Ok(Response::new(res))
   }
 }
 
+// Archive carts idle longer than max_idle
+// Carts with payments or burned points are kept.
+// UPLs of the archived carts are listed to release
+// by the abandoned cart store, so they can be reshelved.
+async fn expire_idle_carts(
+  carts: &Mutex<VecPack<cart::Cart>>,
+  abandoned_carts: &Mutex<VecPack<abandoned::AbandonedCart>>,
+  max_idle: Duration,
+) -> ServiceResult<()> {
+  let mut carts = carts.lock().await;
+
+  let idle_carts = carts
+    .iter()
+    .map(|c| c.unpack())
+    .filter(|c| c.is_idle(max_idle) && c.check_removable().is_ok())
+    .cloned()
+    .collect::<Vec<cart::Cart>>();
+
+  for cart in idle_carts {
+    abandoned_carts
+      .lock()
+      .await
+      .insert(abandoned::AbandonedCart::expired(cart.clone()))?;
+    carts.remove_pack(&cart.id)?;
+  }
+
+  Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
   // Init CARTS database
-  let carts: VecPack<cart::Cart> =
-    VecPack::load_or_init(PathBuf::from("data/carts")).expect("Error while loading carts db");
+  let carts: Arc<Mutex<VecPack<cart::Cart>>> = Arc::new(Mutex::new(
+    VecPack::load_or_init(PathBuf::from("data/carts")).expect("Error while loading carts db"),
+  ));
+
+  // Init ABANDONED CARTS database
+  let abandoned_carts: Arc<Mutex<VecPack<abandoned::AbandonedCart>>> = Arc::new(Mutex::new(
+    VecPack::load_or_init(PathBuf::from("data/abandoned_carts"))
+      .expect("Error while loading abandoned carts db"),
+  ));
 
   // Init PURCHASES database
   let purchases: VecPack<purchase::Purchase> =
     VecPack::load_or_init(PathBuf::from("data/purchases"))
       .expect("Error while loading purchases db");
 
+  // Init REFUNDS database
+  let refunds: VecPack<refund::Refund> =
+    VecPack::load_or_init(PathBuf::from("data/refunds")).expect("Error while loading refunds db");
+
+  // Init PROMOTIONS database
+  let promotions: VecPack<promotion::Promotion> =
+    VecPack::load_or_init(PathBuf::from("data/promotions"))
+      .expect("Error while loading promotions db");
+
+  // Init QUOTES database
+  let quotes: VecPack<quote::Quote> =
+    VecPack::load_or_init(PathBuf::from("data/quotes")).expect("Error while loading quotes db");
+
+  // Parked carts are flagged after this many minutes
+  let max_parked = Duration::minutes(
+    env::var("CART_MAX_PARKED_MINUTES")
+      .ok()
+      .and_then(|m| m.parse::<i64>().ok())
+      .unwrap_or(30),
+  );
+
+  // UPLs are flagged this many days before their best before date
+  let near_expiry = Duration::days(
+    env::var("UPL_NEAR_EXPIRY_DAYS")
+      .ok()
+      .and_then(|d| d.parse::<i64>().ok())
+      .unwrap_or(cart::UPL_NEAR_EXPIRY_DAYS),
+  );
+
+  // Invoice completion date can be set this many days before or after today
+  let date_limits = cart::DateCompletionLimits {
+    max_days_back: env::var("DATE_COMPLETION_MAX_DAYS_BACK")
+      .ok()
+      .and_then(|d| d.parse::<i64>().ok())
+      .unwrap_or(cart::DATE_COMPLETION_MAX_DAYS_BACK),
+    max_days_forward: env::var("DATE_COMPLETION_MAX_DAYS_FORWARD")
+      .ok()
+      .and_then(|d| d.parse::<i64>().ok())
+      .unwrap_or(cart::DATE_COMPLETION_MAX_DAYS_FORWARD),
+  };
+
+  // Carts are expired after this many minutes without modification
+  let max_idle = Duration::minutes(
+    env::var("CART_MAX_IDLE_MINUTES")
+      .ok()
+      .and_then(|m| m.parse::<i64>().ok())
+      .unwrap_or(240),
+  );
+
+  // Archive expired carts or just report them by cart_get_idle
+  let auto_archive = env::var("CART_EXPIRY_AUTO_ARCHIVE")
+    .map(|v| v == "true" || v == "1")
+    .unwrap_or(false);
+
+  // Spawn the cart expiry task
+  if auto_archive {
+    let expiry_carts = carts.clone();
+    let expiry_abandoned_carts = abandoned_carts.clone();
+    tokio::task::spawn(async move {
+      let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+      loop {
+        interval.tick().await;
+        if let Err(e) = expire_idle_carts(&expiry_carts, &expiry_abandoned_carts, max_idle).await {
+          eprintln!("Error while expiring idle carts: {}", e);
+        }
+      }
+    });
+  }
+
   let addr = env::var("SERVICE_ADDR_PURCHASE")
     .unwrap_or("[::1]:50072".into())
     .parse()
@@ -865,7 +2608,18 @@ async fn main() -> Result<(), Box<dyn Error>> {
   // Spawn the server into a runtime
   tokio::task::spawn(async move {
     Server::builder()
-      .add_service(PurchaseServer::new(PurchaseService::init(carts, purchases)))
+      .add_service(PurchaseServer::new(PurchaseService::init(
+        carts,
+        abandoned_carts,
+        purchases,
+        refunds,
+        promotions,
+        quotes,
+        max_parked,
+        max_idle,
+        near_expiry,
+        date_limits,
+      )))
       .serve_with_shutdown(addr, async {
         let _ = rx.await;
       })
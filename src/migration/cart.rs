@@ -9,26 +9,29 @@ use uuid::Uuid;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct CartOld {
-  pub ancestor: Option<Uuid>,           // If this is a restored Cart
-  pub id: Uuid,                         // Cart ID UUID?
-  pub customer: Option<Customer>,       // Only if there is any related one
-  pub discount_percentage: Option<u32>, // Discount in percentage 20% => 20
-  pub shopping_list: Vec<ListItem>,     // Shopping list
-  pub upls_sku: Vec<UplInfoObject>,     // UPLs that are healty
-  pub upls_unique: Vec<UplInfoObject>,  // Upls that are depreciated or opened
-  pub total_net: u32,                   // Total cart net value in HUF
-  pub total_vat: u32,                   // Total VAT
-  pub total_gross: u32,                 // Total cart gross value in HUF
-  pub document_kind: DocumentKind,      // Receipt or Invoice
-  pub payment_kind: PaymentKind,        // cash, transfer, card
-  pub payments: Vec<Payment>,           // Payment vector
-  pub payable: i32,                     // Payable amount
-  pub owner_uid: u32,                   // Shop assistant UID
-  pub store_id: Option<u32>,            // Now its stock ID
-  pub date_completion: DateTime<Utc>,   // Invoice Completion date
-  pub payment_duedate: DateTime<Utc>,   // Invoice Payment duedate
-  pub created_by: u32,                  // UID
-  pub created_at: DateTime<Utc>,        // When cart created
+  pub ancestor: Option<Uuid>,                 // If this is a restored Cart
+  pub id: Uuid,                               // Cart ID UUID?
+  pub customer: Option<CustomerOld>,          // Only if there is any related one
+  pub commitment: Option<Commitment>,         // Applied customer commitment
+  pub commitment_discount_value: u32,         // Commitment value
+  pub loyalty_card: Option<LoyaltyCard>,      // Applied loyalty card
+  pub shopping_list: Vec<ListItemOld>,        // Shopping list
+  pub upls_sku: Vec<UplInfoObjectOld>,        // UPLs that are healty
+  pub upls_unique: Vec<UplInfoObjectOld>,     // Upls that are depreciated or opened
+  pub total_net: u32,                         // Total cart net value in HUF
+  pub total_vat: u32,                         // Total VAT
+  pub total_gross: u32,                       // Total cart gross value in HUF
+  pub document_kind: DocumentKind,            // Receipt or Invoice
+  pub payment_kind: PaymentKind,              // cash, transfer, card
+  pub payments: Vec<PaymentOld>,              // Payment vector
+  pub burned_points: Vec<LoyaltyTransaction>, // Burned payment points
+  pub payable: i32,                           // Payable amount
+  pub owner_uid: u32,                         // Shop assistant UID
+  pub store_id: Option<u32>,                  // Now its stock ID
+  pub date_completion: DateTime<Utc>,         // Invoice Completion date
+  pub payment_duedate: DateTime<Utc>,         // Invoice Payment duedate
+  pub created_by: u32,                        // UID
+  pub created_at: DateTime<Utc>,              // When cart created
 }
 
 impl Default for CartOld {
@@ -37,7 +40,9 @@ impl Default for CartOld {
       ancestor: None,
       id: Uuid::default(),
       customer: None,
-      discount_percentage: None,
+      commitment: None,
+      commitment_discount_value: 0,
+      loyalty_card: None,
       shopping_list: Vec::new(),
       upls_sku: Vec::new(),
       upls_unique: Vec::new(),
@@ -47,6 +52,7 @@ impl Default for CartOld {
       document_kind: DocumentKind::default(),
       payment_kind: PaymentKind::default(),
       payments: Vec::default(),
+      burned_points: Vec::default(),
       payable: 0,
       owner_uid: 0,
       store_id: None,
@@ -66,32 +72,214 @@ impl VecPackMember for CartOld {
   }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ListItemOld {
+  pub sku: u32,
+  pub name: String,
+  pub piece: u32,
+  pub vat: VAT,
+  pub unit_price_net: u32,
+  pub unit_price_vat: u32,
+  pub unit_price_gross: u32,
+  pub total_price_net: u32,
+  pub total_price_vat: u32,
+  pub total_price_gross: u32,
+}
+
+impl Default for ListItemOld {
+  fn default() -> Self {
+    Self {
+      sku: 0,
+      name: String::default(),
+      piece: 0,
+      vat: VAT::default(),
+      unit_price_net: 0,
+      unit_price_vat: 0,
+      unit_price_gross: 0,
+      total_price_net: 0,
+      total_price_vat: 0,
+      total_price_gross: 0,
+    }
+  }
+}
+
+impl From<ListItemOld> for ListItem {
+  fn from(f: ListItemOld) -> Self {
+    Self {
+      sku: f.sku,
+      name: f.name,
+      piece: f.piece,
+      vat: f.vat,
+      unit_price_net: f.unit_price_net,
+      unit_price_vat: f.unit_price_vat,
+      unit_price_gross: f.unit_price_gross,
+      total_price_net: f.total_price_net,
+      total_price_vat: f.total_price_vat,
+      total_price_gross: f.total_price_gross,
+      price_override: None,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CustomerOld {
+  pub id: u32,
+  pub name: String,
+  pub zip: String,
+  pub location: String,
+  pub street: String,
+  pub tax_number: String,
+}
+
+impl Default for CustomerOld {
+  fn default() -> Self {
+    Self {
+      id: 0,
+      name: String::default(),
+      zip: String::default(),
+      location: String::default(),
+      street: String::default(),
+      tax_number: String::default(),
+    }
+  }
+}
+
+impl From<CustomerOld> for Customer {
+  fn from(f: CustomerOld) -> Self {
+    Self {
+      id: f.id,
+      name: f.name,
+      zip: f.zip,
+      location: f.location,
+      street: f.street,
+      tax_number: f.tax_number,
+      payment_term: 0,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PaymentOld {
+  pub payment_id: String,
+  pub amount: i32,
+}
+
+impl Default for PaymentOld {
+  fn default() -> Self {
+    Self {
+      payment_id: String::default(),
+      amount: 0,
+    }
+  }
+}
+
+// Payments had no method,
+// they are paid by the payment kind of the cart
+impl From<(PaymentOld, PaymentKind)> for Payment {
+  fn from(f: (PaymentOld, PaymentKind)) -> Self {
+    let (payment, kind) = f;
+    Self {
+      payment_id: payment.payment_id,
+      amount: payment.amount,
+      kind,
+      tendered: 0,
+      change: 0,
+      reversal: None,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UplInfoObjectOld {
+  pub upl_id: String,
+  pub kind: UplKind,
+  pub name: String,
+  pub retail_net_price: u32,
+  pub vat: VAT,
+  pub retail_gross_price: u32,
+  pub procurement_net_price: u32,
+  pub best_before: Option<DateTime<Utc>>,
+  pub depreciated: bool,
+}
+
+impl Default for UplInfoObjectOld {
+  fn default() -> Self {
+    Self {
+      upl_id: String::default(),
+      kind: UplKind::default(),
+      name: String::default(),
+      retail_net_price: 0,
+      vat: VAT::default(),
+      retail_gross_price: 0,
+      procurement_net_price: 0,
+      best_before: None,
+      depreciated: false,
+    }
+  }
+}
+
+impl From<UplInfoObjectOld> for UplInfoObject {
+  fn from(f: UplInfoObjectOld) -> Self {
+    Self {
+      upl_id: f.upl_id,
+      kind: f.kind,
+      name: f.name,
+      retail_net_price: f.retail_net_price,
+      vat: f.vat,
+      retail_gross_price: f.retail_gross_price,
+      procurement_net_price: f.procurement_net_price,
+      best_before: f.best_before,
+      depreciated: f.depreciated,
+      price_override: None,
+      unit: None,
+      expiry_authorized_by: None,
+    }
+  }
+}
+
 impl From<CartOld> for Cart {
   fn from(f: CartOld) -> Self {
-    Self {
+    let payment_kind = f.payment_kind;
+    let mut res = Self {
       ancestor: f.ancestor,
       id: f.id,
-      customer: f.customer,
-      shopping_list: f.shopping_list,
-      upls_sku: f.upls_sku,
-      upls_unique: f.upls_unique,
+      customer: f.customer.map(|c| c.into()),
+      commitment: f.commitment,
+      commitment_discount_value: f.commitment_discount_value,
+      loyalty_card: f.loyalty_card,
+      promotions: Vec::new(),
+      promotion_time: None,
+      promotion_lines: Vec::new(),
+      shopping_list: f.shopping_list.into_iter().map(|i| i.into()).collect(),
+      upls_sku: f.upls_sku.into_iter().map(|u| u.into()).collect(),
+      upls_unique: f.upls_unique.into_iter().map(|u| u.into()).collect(),
       total_net: f.total_net,
       total_vat: f.total_vat,
       total_gross: f.total_gross,
+      vat_totals: Vec::new(),
       document_kind: f.document_kind,
-      payment_kind: f.payment_kind,
-      payments: f.payments,
+      payment_kind,
+      payments: f
+        .payments
+        .into_iter()
+        .map(|p| (p, payment_kind).into())
+        .collect(),
+      burned_points: f.burned_points,
       payable: f.payable,
       owner_uid: f.owner_uid,
       store_id: f.store_id,
       date_completion: f.date_completion,
       payment_duedate: f.payment_duedate,
+      payment_duedate_explicit: false,
+      parked: None,
+      preorder: None,
       created_by: f.created_by,
       created_at: f.created_at,
-      commitment: None,
-      commitment_discount_value: 0,
-      loyalty_card: None,
-      burned_points: Vec::new(),
-    }
+      updated_at: Utc::now(),
+      version: 1,
+      events: Vec::new(),
+    };
+    res.vat_totals = res.compute_vat_totals();
+    res
   }
 }
@@ -9,27 +9,33 @@ use uuid::Uuid;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct PurchaseOld {
-  pub id: Uuid,                             // Cart ID UUID?
-  pub customer: Option<Customer>,           // Only if there is any related one
-  pub discount_percentage: Option<u32>,     // Applied discount
-  pub items: Vec<Item>,                     // Cart items (All items: shopping list + unique)
-  pub upl_info_objects: Vec<UplInfoObject>, // ALL UPL info objects
-  pub total_net: u32,                       // Total cart net value in HUF
-  pub total_vat: u32,                       // Total VAT
-  pub total_gross: u32,                     // Total cart gross value in HUF
-  pub document_kind: DocumentKind,          // Receipt or Invoice
-  pub payment_kind: PaymentKind,            // cash, transfer, card
-  pub payments: Vec<Payment>,               // Payment vector
-  pub payable: i32,                         // Payable amount
-  pub balance: i32,                         // Payment balance
-  pub profit_net: i32,                      // Net profit
-  pub owner_uid: u32,                       // Shop assistant UID
-  pub store_id: Option<u32>,                // Now its stock ID
-  pub date_completion: DateTime<Utc>,       // Completion date
-  pub payment_duedate: DateTime<Utc>,       // Payment duedate
-  pub restored: Option<Uuid>,               // Some(_) if its restored
-  pub created_by: u32,                      // UID
-  pub created_at: DateTime<Utc>,            // When cart created
+  pub id: Uuid,                                  // Cart ID UUID?
+  pub customer: Option<CustomerOld>,             // Only if there is any related one
+  pub commitment: Option<Commitment>,            // Applied customer commitment
+  pub commitment_discount_value: u32,            //
+  pub loyalty_card: Option<LoyaltyCard>,         // Applied loyalty card
+  pub items: Vec<ItemOld>,                       // Cart items (All items: shopping list + unique)
+  pub upl_info_objects: Vec<UplInfoObjectOld>,   // ALL UPL info objects
+  pub total_net: u32,                            // Total cart net value in HUF
+  pub total_vat: u32,                            // Total VAT
+  pub total_gross: u32,                          // Total cart gross value in HUF
+  pub document_kind: DocumentKind,               // Receipt or Invoice
+  pub payment_kind: PaymentKind,                 // cash, transfer, card
+  pub payments: Vec<PaymentOld>,                 // Payment vector
+  pub burned_points: Vec<LoyaltyTransactionOld>, // Burned payment points
+  pub burned_loyalty_points: u32,                // Burned loyalty points total (gross)
+  pub payable: i32,                              // Payable amount
+  pub balance: i32,                              // Payment balance
+  pub profit_net: i32,                           // Net profit
+  pub owner_uid: u32,                            // Shop assistant UID
+  pub store_id: Option<u32>,                     // Now its stock ID
+  pub date_completion: DateTime<Utc>,            // Completion date
+  pub payment_duedate: DateTime<Utc>,            // Payment duedate
+  pub restored: Option<Uuid>,                    // Some(_) if its restored
+  pub invoice: Option<String>,                   // Invoice
+  pub storno_invoice: Option<String>,            // Storno invoice
+  pub created_by: u32,                           // UID
+  pub created_at: DateTime<Utc>,                 // When cart created
 }
 
 impl Default for PurchaseOld {
@@ -37,7 +43,9 @@ impl Default for PurchaseOld {
     Self {
       id: Uuid::default(),
       customer: None,
-      discount_percentage: None,
+      commitment: None,
+      commitment_discount_value: 0,
+      loyalty_card: None,
       items: Vec::new(),
       upl_info_objects: Vec::new(),
       total_net: 0,
@@ -46,6 +54,8 @@ impl Default for PurchaseOld {
       document_kind: DocumentKind::default(),
       payment_kind: PaymentKind::default(),
       payments: Vec::new(),
+      burned_points: Vec::new(),
+      burned_loyalty_points: 0,
       payable: 0,
       balance: 0,
       profit_net: 0,
@@ -54,6 +64,8 @@ impl Default for PurchaseOld {
       date_completion: Utc::today().and_hms(0, 0, 0),
       payment_duedate: Utc::today().and_hms(0, 0, 0),
       restored: None,
+      invoice: None,
+      storno_invoice: None,
       created_by: 0,
       created_at: Utc::now(),
     }
@@ -68,24 +80,262 @@ impl VecPackMember for PurchaseOld {
   }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CustomerOld {
+  pub id: u32,
+  pub name: String,
+  pub zip: String,
+  pub location: String,
+  pub street: String,
+  pub tax_number: String,
+}
+
+impl Default for CustomerOld {
+  fn default() -> Self {
+    Self {
+      id: 0,
+      name: String::default(),
+      zip: String::default(),
+      location: String::default(),
+      street: String::default(),
+      tax_number: String::default(),
+    }
+  }
+}
+
+impl From<CustomerOld> for Customer {
+  fn from(f: CustomerOld) -> Self {
+    Self {
+      id: f.id,
+      name: f.name,
+      zip: f.zip,
+      location: f.location,
+      street: f.street,
+      tax_number: f.tax_number,
+      payment_term: 0,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PaymentOld {
+  pub payment_id: String,
+  pub amount: i32,
+}
+
+impl Default for PaymentOld {
+  fn default() -> Self {
+    Self {
+      payment_id: String::default(),
+      amount: 0,
+    }
+  }
+}
+
+// Payments had no method,
+// they are paid by the payment kind of the purchase
+impl From<(PaymentOld, PaymentKind)> for Payment {
+  fn from(f: (PaymentOld, PaymentKind)) -> Self {
+    let (payment, kind) = f;
+    Self {
+      payment_id: payment.payment_id,
+      amount: payment.amount,
+      kind,
+      tendered: 0,
+      change: 0,
+      reversal: None,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LoyaltyTransactionOld {
+  pub loyalty_account_id: Uuid,
+  pub transaction_id: Uuid,
+  pub burned_points: i32,
+  pub created_at: DateTime<Utc>,
+}
+
+impl Default for LoyaltyTransactionOld {
+  fn default() -> Self {
+    Self {
+      loyalty_account_id: Uuid::default(),
+      transaction_id: Uuid::default(),
+      burned_points: 0,
+      created_at: Utc::now(),
+    }
+  }
+}
+
+// Baseline reversals copied the burn, they are not linked to it
+impl From<LoyaltyTransactionOld> for LoyaltyTransaction {
+  fn from(f: LoyaltyTransactionOld) -> Self {
+    Self {
+      loyalty_account_id: f.loyalty_account_id,
+      transaction_id: f.transaction_id,
+      burned_points: f.burned_points,
+      reversed_transaction_id: None,
+      created_at: f.created_at,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ItemOld {
+  pub kind: ItemKind,
+  pub product_id: u32,
+  pub name: String,
+  pub piece: u32,
+  pub retail_price_net: u32,
+  pub vat: String,
+  pub retail_price_gross: u32,
+  pub total_retail_price_net: u32,
+  pub total_retail_price_gross: u32,
+}
+
+impl Default for ItemOld {
+  fn default() -> Self {
+    Self {
+      kind: ItemKind::default(),
+      product_id: 0,
+      name: String::default(),
+      piece: 0,
+      retail_price_net: 0,
+      vat: String::default(),
+      retail_price_gross: 0,
+      total_retail_price_net: 0,
+      total_retail_price_gross: 0,
+    }
+  }
+}
+
+// Items had no SKU and amount,
+// they are taken from the sold UPLs of the item
+impl From<(ItemOld, &Vec<UplInfoObjectOld>)> for Item {
+  fn from(f: (ItemOld, &Vec<UplInfoObjectOld>)) -> Self {
+    let (item, upls) = f;
+    let sku = upls
+      .iter()
+      .find_map(|u| match u.kind {
+        UplKind::Sku { sku, piece: _ } if u.name == item.name => Some(sku),
+        _ => None,
+      })
+      .unwrap_or(0);
+    let amount = match item.kind {
+      ItemKind::DerivedProduct => upls
+        .iter()
+        .find_map(|u| match u.kind {
+          UplKind::DerivedProduct { product_id, amount } if product_id == item.product_id => {
+            Some(amount)
+          }
+          _ => None,
+        })
+        .unwrap_or(item.piece),
+      _ => item.piece,
+    };
+    Self {
+      unit: match item.kind {
+        ItemKind::Sku => Some(Unit::Piece),
+        _ => None,
+      },
+      kind: item.kind,
+      sku,
+      product_id: item.product_id,
+      name: item.name,
+      piece: item.piece,
+      retail_price_net: item.retail_price_net,
+      vat: item.vat,
+      retail_price_gross: item.retail_price_gross,
+      total_retail_price_net: item.total_retail_price_net,
+      total_retail_price_gross: item.total_retail_price_gross,
+      price_override: None,
+      amount,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UplInfoObjectOld {
+  pub upl_id: String,
+  pub kind: UplKind,
+  pub name: String,
+  pub retail_net_price: u32,
+  pub vat: String,
+  pub retail_gross_price: u32,
+  pub procurement_net_price: u32,
+  pub best_before: Option<DateTime<Utc>>,
+  pub depreciated: bool,
+}
+
+impl Default for UplInfoObjectOld {
+  fn default() -> Self {
+    Self {
+      upl_id: String::default(),
+      kind: UplKind::default(),
+      name: String::default(),
+      retail_net_price: 0,
+      vat: String::default(),
+      retail_gross_price: 0,
+      procurement_net_price: 0,
+      best_before: None,
+      depreciated: false,
+    }
+  }
+}
+
+impl From<UplInfoObjectOld> for UplInfoObject {
+  fn from(f: UplInfoObjectOld) -> Self {
+    Self {
+      upl_id: f.upl_id,
+      kind: f.kind,
+      name: f.name,
+      retail_net_price: f.retail_net_price,
+      vat: f.vat,
+      retail_gross_price: f.retail_gross_price,
+      procurement_net_price: f.procurement_net_price,
+      best_before: f.best_before,
+      depreciated: f.depreciated,
+      price_override: None,
+      unit: None,
+      expiry_authorized_by: None,
+    }
+  }
+}
+
 impl From<PurchaseOld> for purchase::Purchase {
   fn from(f: PurchaseOld) -> Self {
-    Self {
+    let payment_kind = f.payment_kind;
+    let upls = &f.upl_info_objects;
+    let items = f
+      .items
+      .into_iter()
+      .map(|i| (i, upls).into())
+      .collect::<Vec<Item>>();
+    let mut res = Self {
       id: f.id,
-      customer: f.customer,
-      commitment: None,
-      commitment_discount_value: 0,
-      loyalty_card: None,
-      items: f.items,
-      upl_info_objects: f.upl_info_objects,
+      customer: f.customer.map(|c| c.into()),
+      commitment: f.commitment,
+      commitment_discount_value: f.commitment_discount_value,
+      loyalty_card: f.loyalty_card,
+      promotions: Vec::new(),
+      promotion_time: f.created_at,
+      promotion_lines: Vec::new(),
+      promotion_discount_value: 0,
+      items,
+      upl_info_objects: f.upl_info_objects.into_iter().map(|u| u.into()).collect(),
       total_net: f.total_net,
       total_vat: f.total_vat,
       total_gross: f.total_gross,
+      vat_totals: Vec::new(),
       document_kind: f.document_kind,
-      payment_kind: f.payment_kind,
-      payments: f.payments,
-      burned_points: Vec::new(),
-      burned_loyalty_points: 0,
+      payment_kind,
+      payments: f
+        .payments
+        .into_iter()
+        .map(|p| (p, payment_kind).into())
+        .collect(),
+      burned_points: f.burned_points.into_iter().map(|t| t.into()).collect(),
+      burned_loyalty_points: f.burned_loyalty_points,
       payable: f.payable,
       balance: f.balance,
       profit_net: f.profit_net,
@@ -93,11 +343,20 @@ impl From<PurchaseOld> for purchase::Purchase {
       store_id: f.store_id,
       date_completion: f.date_completion,
       payment_duedate: f.payment_duedate,
+      preorder: None,
       restored: f.restored,
-      invoice: None,
-      storno_invoice: None,
+      refunds: Vec::new(),
+      returned_upls: Vec::new(),
+      refunded_net: 0,
+      refunded_gross: 0,
+      invoice: f.invoice,
+      storno_invoice: f.storno_invoice,
       created_by: f.created_by,
       created_at: f.created_at,
-    }
+      events: Vec::new(),
+    };
+    // Discounts are split between the VAT rates as in the cart
+    res.vat_totals = res.compute_vat_totals();
+    res
   }
 }
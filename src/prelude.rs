@@ -1,17 +1,29 @@
-use chrono::Utc;
-use gzlib::proto::{
+use crate::proto::{
   self,
   purchase::{
-    cart_object, upl_info_object, CartInfoObject, CartObject, Customer, LoyaltyTransaction,
-    Payment, PaymentKind, PurchaseInfoObject, PurchaseObject, UplInfoObject,
+    cart_object, upl_info_object, AbandonedCartObject, CartInfoObject, CartObject, Customer,
+    LoyaltyTransaction, Payment, PaymentKind, PurchaseInfoObject, PurchaseObject, UplInfoObject,
   },
 };
-use proto::purchase::purchase_object;
+use chrono::{Duration, Utc};
+use proto::purchase::{
+  promotion_object, purchase_object, PromotionBundleItem, PromotionBundlePrice, PromotionBuyXPayY,
+  PromotionCategoryPercentage, PromotionObject,
+};
+use std::convert::TryFrom;
 use upl_info_object::{UplKindOpenedSku, UplKindSku};
 
 use crate::{
+  abandoned,
   cart::{self, CartMethods},
+  error::DomainError,
+  event,
+  i18n::{self, Locale, Localize},
+  promotion,
   purchase::{self},
+  quote, refund,
+  unit::{self, Unit},
+  validation,
 };
 
 pub enum ServiceError {
@@ -19,6 +31,8 @@ pub enum ServiceError {
   NotFound(String),
   AlreadyExists(String),
   BadRequest(String),
+  Conflict(String),
+  Domain(DomainError),
 }
 
 impl ServiceError {
@@ -34,6 +48,9 @@ impl ServiceError {
   pub fn bad_request(msg: &str) -> Self {
     ServiceError::BadRequest(msg.to_string())
   }
+  pub fn conflict(msg: &str) -> Self {
+    ServiceError::Conflict(msg.to_string())
+  }
 }
 
 impl std::fmt::Display for ServiceError {
@@ -43,6 +60,8 @@ impl std::fmt::Display for ServiceError {
       ServiceError::NotFound(msg) => write!(f, "{}", msg),
       ServiceError::AlreadyExists(msg) => write!(f, "{}", msg),
       ServiceError::BadRequest(msg) => write!(f, "{}", msg),
+      ServiceError::Conflict(msg) => write!(f, "{}", msg),
+      ServiceError::Domain(error) => write!(f, "{}", error),
     }
   }
 }
@@ -58,15 +77,51 @@ impl std::fmt::Debug for ServiceError {
 
 impl From<ServiceError> for ::tonic::Status {
   fn from(error: ServiceError) -> Self {
-    match error {
+    error.into_status(Locale::default())
+  }
+}
+
+impl ServiceError {
+  /// gRPC status with domain error texts in the given locale
+  pub fn into_status(self, locale: Locale) -> ::tonic::Status {
+    match self {
       ServiceError::InternalError(msg) => ::tonic::Status::internal(msg),
       ServiceError::NotFound(msg) => ::tonic::Status::not_found(msg),
       ServiceError::AlreadyExists(msg) => ::tonic::Status::already_exists(msg),
       ServiceError::BadRequest(msg) => ::tonic::Status::invalid_argument(msg),
+      ServiceError::Conflict(msg) => ::tonic::Status::aborted(msg),
+      ServiceError::Domain(error) => {
+        // Error code and params as machine readable metadata
+        // e.g. error-code: UPL_ALREADY_IN_CART, error-upl-id: 123
+        let mut metadata = ::tonic::metadata::MetadataMap::new();
+        if let Ok(code) = error.code().parse() {
+          metadata.insert("error-code", code);
+        }
+        for (key, value) in error.params() {
+          let key = ::tonic::metadata::AsciiMetadataKey::from_bytes(
+            format!("error-{}", key.replace('_', "-")).as_bytes(),
+          );
+          if let (Ok(key), Ok(value)) = (key, value.parse()) {
+            metadata.insert(key, value);
+          }
+        }
+        // Concurrent modification is retryable, the rest is a client error
+        let code = match error {
+          DomainError::VersionConflict { .. } => ::tonic::Code::Aborted,
+          _ => ::tonic::Code::InvalidArgument,
+        };
+        ::tonic::Status::with_metadata(code, error.localize(locale), metadata)
+      }
     }
   }
 }
 
+impl From<DomainError> for ServiceError {
+  fn from(error: DomainError) -> Self {
+    ServiceError::Domain(error)
+  }
+}
+
 impl From<::packman::PackError> for ServiceError {
   fn from(error: ::packman::PackError) -> Self {
     match error {
@@ -108,13 +163,17 @@ impl From<crate::cart::Cart> for CartInfoObject {
       upl_count: f.upls_sku.len() as u32 + f.upls_unique.len() as u32,
       item_names: names,
       owner: f.owner_uid,
+      parked: f.parked.is_some(),
+      preorder: f.preorder.is_some(),
       created_by: f.created_by,
       created_at: f.created_at.to_rfc3339(),
+      updated_at: f.updated_at.to_rfc3339(),
+      version: f.version,
     }
   }
 }
 
-impl From<crate::cart::LoyaltyCard> for gzlib::proto::purchase::cart_object::LoyaltyCard {
+impl From<crate::cart::LoyaltyCard> for proto::purchase::cart_object::LoyaltyCard {
   fn from(f: crate::cart::LoyaltyCard) -> Self {
     Self {
       account_id: f.account_id.to_string(),
@@ -124,142 +183,534 @@ impl From<crate::cart::LoyaltyCard> for gzlib::proto::purchase::cart_object::Loy
   }
 }
 
-impl From<crate::cart::Cart> for CartObject {
-  fn from(f: crate::cart::Cart) -> Self {
+impl From<event::CartEvent> for proto::purchase::CartEventObject {
+  fn from(f: event::CartEvent) -> Self {
     Self {
-      ancestor: match f.ancestor {
-        Some(anc) => anc.to_string(),
+      event_id: f.id.to_string(),
+      kind: f.kind.name(),
+      details: f.kind.details(),
+      total_net_before: f.before.total_net,
+      total_gross_before: f.before.total_gross,
+      payable_before: f.before.payable,
+      balance_before: f.before.balance,
+      total_net_after: f.after.total_net,
+      total_gross_after: f.after.total_gross,
+      payable_after: f.after.payable,
+      balance_after: f.after.balance,
+      created_by: f.created_by,
+      created_at: f.created_at.to_rfc3339(),
+    }
+  }
+}
+
+impl From<validation::CloseIssue> for proto::purchase::CartCloseIssue {
+  fn from(f: validation::CloseIssue) -> Self {
+    let mut res = Self {
+      code: f.code(),
+      message: f.message(),
+      ..Default::default()
+    };
+    match f {
+      validation::CloseIssue::SkuUplMismatch {
+        sku,
+        name,
+        list_piece,
+        upl_piece,
+      } => {
+        res.sku = sku;
+        res.name = name;
+        res.list_piece = list_piece;
+        res.upl_piece = upl_piece;
+      }
+      validation::CloseIssue::TotalsMismatch {
+        figure,
+        stored,
+        computed,
+      } => {
+        res.figure = figure;
+        res.expected = computed as i32;
+        res.actual = stored as i32;
+      }
+      validation::CloseIssue::CashNotRounded { cash_total } => {
+        res.expected = crate::rounding::round_huf(cash_total);
+        res.actual = cash_total;
+      }
+      validation::CloseIssue::PaymentNegative {
+        payment_kind,
+        total,
+      } => {
+        res.figure = payment_kind.to_string();
+        res.expected = 0;
+        res.actual = total;
+      }
+      validation::CloseIssue::PaymentOverpaid { balance } => {
+        res.expected = 0;
+        res.actual = balance;
+      }
+      validation::CloseIssue::PaymentUnbalanced { balance, .. } => {
+        res.expected = 0;
+        res.actual = balance;
+      }
+      _ => (),
+    }
+    res
+  }
+}
+
+impl From<cart::ParkInfo> for proto::purchase::ParkInfo {
+  fn from(f: cart::ParkInfo) -> Self {
+    Self {
+      label: f.label,
+      parked_by: f.parked_by,
+      parked_at: f.parked_at.to_rfc3339(),
+    }
+  }
+}
+
+impl From<cart::Preorder> for proto::purchase::PreorderInfo {
+  fn from(f: cart::Preorder) -> Self {
+    Self {
+      expected_arrival: f.expected_arrival.to_rfc3339(),
+      deposit: f.deposit,
+      created_by: f.created_by,
+      created_at: f.created_at.to_rfc3339(),
+      fulfilled_by: f.fulfilled_by.unwrap_or(0),
+      fulfilled_at: match f.fulfilled_at {
+        Some(fa) => fa.to_rfc3339(),
         None => "".to_string(),
       },
-      id: f.id.to_string(),
-      customer: match &f.customer {
-        Some(c) => Some(Customer {
-          customer_id: c.id.clone(),
-          name: c.name.clone(),
-          zip: c.zip.clone(),
-          location: c.location.clone(),
-          street: c.street.clone(),
-          tax_number: c.tax_number.clone(),
-        }),
-        None => None,
-      },
-      shopping_list: f
-        .shopping_list
-        .iter()
-        .map(|i| cart_object::Item {
-          sku: i.sku,
-          name: i.name.clone(),
-          piece: i.piece,
-          retail_price_net: i.unit_price_net,
-          vat: i.vat.to_string(),
-          retail_price_gross: i.unit_price_gross,
-          total_retail_price_net: i.total_price_net,
-          total_retail_price_gross: i.total_price_gross,
-        })
-        .collect(),
-      upls_sku: f
-        .upls_sku
-        .iter()
-        .map(|uio| UplInfoObject {
-          upl_id: uio.upl_id.clone(),
-          upl_kind: Some(match uio.kind {
-            cart::UplKind::Sku { sku, piece } => upl_info_object::UplKind::Sku(UplKindSku {
-              sku: sku,
-              piece: piece,
-            }),
-            cart::UplKind::DerivedProduct { product_id, amount } => {
-              upl_info_object::UplKind::OpenedSku(UplKindOpenedSku {
-                product_id: product_id,
-                amount: amount,
-              })
-            }
-          }),
-          name: uio.name.clone(),
-          retail_net_price: uio.retail_net_price,
-          vat: uio.vat.to_string(),
-          retail_gross_price: uio.retail_gross_price,
-          procurement_net_price: uio.procurement_net_price,
-          best_before: match uio.best_before {
-            Some(bb) => bb.to_rfc3339(),
-            None => "".to_string(),
-          },
-          depreciated: uio.depreciated,
-        })
-        .collect(),
-      upls_unique: f
-        .upls_unique
-        .iter()
-        .map(|uio| UplInfoObject {
-          upl_id: uio.upl_id.clone(),
-          upl_kind: Some(match uio.kind {
-            cart::UplKind::Sku { sku, piece } => upl_info_object::UplKind::Sku(UplKindSku {
-              sku: sku,
-              piece: piece,
-            }),
-            cart::UplKind::DerivedProduct { product_id, amount } => {
-              upl_info_object::UplKind::OpenedSku(UplKindOpenedSku {
-                product_id: product_id,
-                amount: amount,
-              })
-            }
-          }),
-          name: uio.name.clone(),
-          retail_net_price: uio.retail_net_price,
-          vat: uio.vat.to_string(),
-          retail_gross_price: uio.retail_gross_price,
-          procurement_net_price: uio.procurement_net_price,
-          best_before: match uio.best_before {
-            Some(bb) => bb.to_rfc3339(),
-            None => "".to_string(),
-          },
-          depreciated: uio.depreciated,
-        })
-        .collect(),
-      total_net: f.total_net,
-      total_vat: f.total_vat,
-      total_gross: f.total_gross,
-      need_invoice: match f.document_kind {
-        crate::cart::DocumentKind::Receipt => false,
-        crate::cart::DocumentKind::Invoice => true,
-      },
-      payment_kind: match f.payment_kind {
-        crate::cart::PaymentKind::Cash => PaymentKind::Cash,
-        crate::cart::PaymentKind::Card => PaymentKind::Card,
-        crate::cart::PaymentKind::Transfer => PaymentKind::Transfer,
-      } as i32,
-      payments: f
-        .payments
-        .iter()
-        .map(|p| Payment {
-          payment_id: p.payment_id.clone(),
-          amount: p.amount,
-        })
-        .collect(),
-      payable: f.get_payable(),
-      payment_balance: f.get_balance(),
-      profit_net: f.get_profit_net(),
-      owner_uid: f.owner_uid,
-      store_id: f.store_id.unwrap_or(0), // 0 means no store
-      date_completion: f.date_completion.to_rfc3339(),
-      payment_duedate: f.payment_duedate.to_rfc3339(),
+    }
+  }
+}
+
+impl From<purchase::Preorder> for proto::purchase::PreorderInfo {
+  fn from(f: purchase::Preorder) -> Self {
+    Self {
+      expected_arrival: f.expected_arrival.to_rfc3339(),
+      deposit: f.deposit,
       created_by: f.created_by,
       created_at: f.created_at.to_rfc3339(),
-      commitment_id: match f.commitment.clone() {
-        Some(c) => c.commitment_id.to_string(),
+      fulfilled_by: f.fulfilled_by.unwrap_or(0),
+      fulfilled_at: match f.fulfilled_at {
+        Some(fa) => fa.to_rfc3339(),
         None => "".to_string(),
       },
-      commitment_discount_percentage: match f.commitment.clone() {
-        Some(c) => c.commitment_percentage,
+    }
+  }
+}
+
+impl From<cart::Preorder> for purchase::Preorder {
+  fn from(f: cart::Preorder) -> Self {
+    Self {
+      expected_arrival: f.expected_arrival,
+      deposit: f.deposit,
+      created_by: f.created_by,
+      created_at: f.created_at,
+      fulfilled_by: f.fulfilled_by,
+      fulfilled_at: f.fulfilled_at,
+    }
+  }
+}
+
+// Cart view without a near expiry setting
+// quoted and abandoned carts use the default one
+impl From<crate::cart::Cart> for CartObject {
+  fn from(f: crate::cart::Cart) -> Self {
+    cart_object(f, Duration::days(cart::UPL_NEAR_EXPIRY_DAYS))
+  }
+}
+
+/// Cart view with the given near expiry setting
+/// UPLs are flagged as near expiry by the current time
+/// when the cart object is built
+pub fn cart_object(f: crate::cart::Cart, near_expiry: Duration) -> CartObject {
+  CartObject {
+    ancestor: match f.ancestor {
+      Some(anc) => anc.to_string(),
+      None => "".to_string(),
+    },
+    id: f.id.to_string(),
+    customer: f.customer.as_ref().map(|c| Customer {
+      customer_id: c.id,
+      name: c.name.clone(),
+      zip: c.zip.clone(),
+      location: c.location.clone(),
+      street: c.street.clone(),
+      tax_number: c.tax_number.clone(),
+      payment_term: c.payment_term,
+    }),
+    shopping_list: f
+      .shopping_list
+      .iter()
+      .map(|i| cart_object::Item {
+        sku: i.sku,
+        name: i.name.clone(),
+        piece: i.piece,
+        retail_price_net: i.unit_price_net,
+        vat: i.vat.to_string(),
+        retail_price_gross: i.unit_price_gross,
+        total_retail_price_net: i.total_price_net,
+        total_retail_price_gross: i.total_price_gross,
+        price_override: i.price_override.clone().map(|po| po.into()),
+      })
+      .collect(),
+    upls_sku: f
+      .upls_sku
+      .iter()
+      .map(|uio| UplInfoObject {
+        upl_id: uio.upl_id.clone(),
+        upl_kind: Some(match uio.kind {
+          cart::UplKind::Sku { sku, piece } => {
+            upl_info_object::UplKind::Sku(UplKindSku { sku, piece })
+          }
+          cart::UplKind::DerivedProduct { product_id, amount } => {
+            upl_info_object::UplKind::OpenedSku(UplKindOpenedSku { product_id, amount })
+          }
+        }),
+        name: uio.name.clone(),
+        retail_net_price: uio.retail_net_price,
+        vat: uio.vat.to_string(),
+        retail_gross_price: uio.retail_gross_price,
+        procurement_net_price: uio.procurement_net_price,
+        best_before: match uio.best_before {
+          Some(bb) => bb.to_rfc3339(),
+          None => "".to_string(),
+        },
+        depreciated: uio.depreciated,
+        price_override: uio.price_override.clone().map(|po| po.into()),
+        unit: unit::code(uio.unit),
+        near_expiry: uio.is_near_expiry(near_expiry),
+        expiry_authorized_by: uio.expiry_authorized_by.unwrap_or(0),
+      })
+      .collect(),
+    upls_unique: f
+      .upls_unique
+      .iter()
+      .map(|uio| UplInfoObject {
+        upl_id: uio.upl_id.clone(),
+        upl_kind: Some(match uio.kind {
+          cart::UplKind::Sku { sku, piece } => {
+            upl_info_object::UplKind::Sku(UplKindSku { sku, piece })
+          }
+          cart::UplKind::DerivedProduct { product_id, amount } => {
+            upl_info_object::UplKind::OpenedSku(UplKindOpenedSku { product_id, amount })
+          }
+        }),
+        name: uio.name.clone(),
+        retail_net_price: uio.retail_net_price,
+        vat: uio.vat.to_string(),
+        retail_gross_price: uio.retail_gross_price,
+        procurement_net_price: uio.procurement_net_price,
+        best_before: match uio.best_before {
+          Some(bb) => bb.to_rfc3339(),
+          None => "".to_string(),
+        },
+        depreciated: uio.depreciated,
+        price_override: uio.price_override.clone().map(|po| po.into()),
+        unit: unit::code(uio.unit),
+        near_expiry: uio.is_near_expiry(near_expiry),
+        expiry_authorized_by: uio.expiry_authorized_by.unwrap_or(0),
+      })
+      .collect(),
+    total_net: f.total_net,
+    total_vat: f.total_vat,
+    total_gross: f.total_gross,
+    need_invoice: match f.document_kind {
+      crate::cart::DocumentKind::Receipt => false,
+      crate::cart::DocumentKind::Invoice => true,
+    },
+    payment_kind: PaymentKind::from(f.payment_kind) as i32,
+    payments: f
+      .payments
+      .iter()
+      .map(|p| p.clone().into())
+      .collect::<Vec<Payment>>(),
+    payable: f.get_payable(),
+    payment_balance: f.get_balance(),
+    profit_net: f.get_profit_net(),
+    owner_uid: f.owner_uid,
+    store_id: f.store_id.unwrap_or(0), // 0 means no store
+    date_completion: f.date_completion.to_rfc3339(),
+    payment_duedate: f.payment_duedate.to_rfc3339(),
+    parked: f.parked.clone().map(|p| p.into()),
+    preorder: f.preorder.clone().map(|p| p.into()),
+    deposit_due: f.get_deposit_due(),
+    near_expiry_warning: f
+      .upls_sku
+      .iter()
+      .chain(f.upls_unique.iter())
+      .any(|u| u.is_near_expiry(near_expiry)),
+    created_by: f.created_by,
+    created_at: f.created_at.to_rfc3339(),
+    updated_at: f.updated_at.to_rfc3339(),
+    version: f.version,
+    commitment_id: match f.commitment.clone() {
+      Some(c) => c.commitment_id.to_string(),
+      None => "".to_string(),
+    },
+    commitment_discount_percentage: match f.commitment.clone() {
+      Some(c) => c.commitment_percentage,
+      None => 0,
+    },
+    loyalty_card: f.loyalty_card.clone().map(|l| l.into()),
+    commitment_discount_amount_gross: f.commitment_discount_value,
+    promotion_lines: f
+      .promotion_lines
+      .iter()
+      .map(|l| l.clone().into())
+      .collect::<Vec<proto::purchase::PromotionLine>>(),
+    promotion_discount_amount_gross: f.get_promotion_discount_gross(),
+    burned_loyalty_points: f.get_burned_points_balance(),
+    burned_points: f
+      .burned_points
+      .into_iter()
+      .map(|t| t.into())
+      .collect::<Vec<LoyaltyTransaction>>(),
+    vat_totals: f
+      .vat_totals
+      .into_iter()
+      .map(|t| t.into())
+      .collect::<Vec<proto::purchase::VatTotal>>(),
+  }
+}
+
+impl From<abandoned::AbandonedCart> for AbandonedCartObject {
+  fn from(f: abandoned::AbandonedCart) -> Self {
+    Self {
+      cart: Some(f.cart.into()),
+      reason: match &f.reason {
+        abandoned::RemoveReason::Manual(reason) => reason.to_string(),
+        abandoned::RemoveReason::Expired => String::default(),
+      },
+      removed_by: match f.removed_by {
+        abandoned::RemovedBy::User(uid) => uid,
+        abandoned::RemovedBy::System => 0,
+      },
+      expired: f.reason == abandoned::RemoveReason::Expired,
+      removed_by_system: f.removed_by == abandoned::RemovedBy::System,
+      removed_at: f.removed_at.to_rfc3339(),
+      upls_to_release: f.upls_to_release,
+      upls_released_by: match &f.upls_released {
+        Some(r) => r.released_by,
         None => 0,
       },
-      loyalty_card: f.loyalty_card.clone().map(|l| l.into()),
-      commitment_discount_amount_gross: f.commitment_discount_value,
-      burned_loyalty_points: f.get_burned_points_balance(),
-      burned_points: f
-        .burned_points
-        .into_iter()
-        .map(|t| t.into())
-        .collect::<Vec<LoyaltyTransaction>>(),
+      upls_released_at: match &f.upls_released {
+        Some(r) => r.released_at.to_rfc3339(),
+        None => "".to_string(),
+      },
+    }
+  }
+}
+
+impl From<quote::Quote> for proto::purchase::QuoteObject {
+  fn from(f: quote::Quote) -> Self {
+    Self {
+      quote_id: f.id.to_string(),
+      quote_number: f.quote_number.to_string(),
+      valid: f.is_valid(),
+      cart: Some(f.cart.into()),
+      valid_till: f.valid_till.to_rfc3339(),
+      converted_cart_id: match f.converted_to {
+        Some(id) => id.to_string(),
+        None => "".to_string(),
+      },
+      created_by: f.created_by,
+      created_at: f.created_at.to_rfc3339(),
+    }
+  }
+}
+
+impl From<promotion::PromotionLine> for proto::purchase::PromotionLine {
+  fn from(f: promotion::PromotionLine) -> Self {
+    Self {
+      promotion_id: f.promotion_id.to_string(),
+      name: f.name,
+      skus: f.skus,
+      discount_net: f.discount_net,
+      discount_gross: f.discount_gross,
+    }
+  }
+}
+
+impl From<purchase::PromotionLine> for proto::purchase::PromotionLine {
+  fn from(f: purchase::PromotionLine) -> Self {
+    Self {
+      promotion_id: f.promotion_id.to_string(),
+      name: f.name,
+      skus: f.skus,
+      discount_net: f.discount_net,
+      discount_gross: f.discount_gross,
+    }
+  }
+}
+
+impl From<promotion::Promotion> for PromotionObject {
+  fn from(f: promotion::Promotion) -> Self {
+    Self {
+      promotion_id: f.id.to_string(),
+      name: f.name,
+      kind: Some(match f.kind {
+        promotion::PromotionKind::BuyXPayY { sku, buy, pay } => {
+          promotion_object::Kind::BuyXPayY(PromotionBuyXPayY { sku, buy, pay })
+        }
+        promotion::PromotionKind::BundlePrice { items, price_gross } => {
+          promotion_object::Kind::BundlePrice(PromotionBundlePrice {
+            items: items
+              .into_iter()
+              .map(|i| PromotionBundleItem {
+                sku: i.sku,
+                piece: i.piece,
+              })
+              .collect::<Vec<PromotionBundleItem>>(),
+            price_gross,
+          })
+        }
+        promotion::PromotionKind::CategoryPercentage {
+          category_id,
+          skus,
+          percentage,
+        } => promotion_object::Kind::CategoryPercentage(PromotionCategoryPercentage {
+          category_id,
+          skus,
+          percentage,
+        }),
+      }),
+      valid_from: f.valid_from.to_rfc3339(),
+      valid_till: f.valid_till.to_rfc3339(),
+      created_by: f.created_by,
+      created_at: f.created_at.to_rfc3339(),
+    }
+  }
+}
+
+impl From<cart::PaymentKind> for PaymentKind {
+  fn from(f: cart::PaymentKind) -> Self {
+    match f {
+      cart::PaymentKind::Cash => PaymentKind::Cash,
+      cart::PaymentKind::Card => PaymentKind::Card,
+      cart::PaymentKind::Transfer => PaymentKind::Transfer,
+    }
+  }
+}
+
+impl From<purchase::PaymentKind> for PaymentKind {
+  fn from(f: purchase::PaymentKind) -> Self {
+    match f {
+      purchase::PaymentKind::Cash => PaymentKind::Cash,
+      purchase::PaymentKind::Card => PaymentKind::Card,
+      purchase::PaymentKind::Transfer => PaymentKind::Transfer,
+    }
+  }
+}
+
+impl From<PaymentKind> for cart::PaymentKind {
+  fn from(f: PaymentKind) -> Self {
+    match f {
+      PaymentKind::Cash => cart::PaymentKind::Cash,
+      PaymentKind::Card => cart::PaymentKind::Card,
+      PaymentKind::Transfer => cart::PaymentKind::Transfer,
+    }
+  }
+}
+
+impl From<PaymentKind> for purchase::PaymentKind {
+  fn from(f: PaymentKind) -> Self {
+    match f {
+      PaymentKind::Cash => purchase::PaymentKind::Cash,
+      PaymentKind::Card => purchase::PaymentKind::Card,
+      PaymentKind::Transfer => purchase::PaymentKind::Transfer,
+    }
+  }
+}
+
+impl From<cart::PaymentKind> for purchase::PaymentKind {
+  fn from(f: cart::PaymentKind) -> Self {
+    match f {
+      cart::PaymentKind::Cash => purchase::PaymentKind::Cash,
+      cart::PaymentKind::Card => purchase::PaymentKind::Card,
+      cart::PaymentKind::Transfer => purchase::PaymentKind::Transfer,
+    }
+  }
+}
+
+impl From<purchase::PaymentKind> for cart::PaymentKind {
+  fn from(f: purchase::PaymentKind) -> Self {
+    match f {
+      purchase::PaymentKind::Cash => cart::PaymentKind::Cash,
+      purchase::PaymentKind::Card => cart::PaymentKind::Card,
+      purchase::PaymentKind::Transfer => cart::PaymentKind::Transfer,
+    }
+  }
+}
+
+impl From<cart::Payment> for Payment {
+  fn from(f: cart::Payment) -> Self {
+    Self {
+      payment_id: f.payment_id,
+      amount: f.amount,
+      payment_kind: PaymentKind::from(f.kind) as i32,
+      amount_tendered: f.tendered,
+      change: f.change,
+      reversal: f.reversal.map(|r| proto::purchase::PaymentReversal {
+        payment_id: r.payment_id,
+        reason: r.reason,
+        created_by: r.created_by,
+        created_at: r.created_at.to_rfc3339(),
+      }),
+    }
+  }
+}
+
+impl From<purchase::Payment> for Payment {
+  fn from(f: purchase::Payment) -> Self {
+    Self {
+      payment_id: f.payment_id,
+      amount: f.amount,
+      payment_kind: PaymentKind::from(f.kind) as i32,
+      amount_tendered: f.tendered,
+      change: f.change,
+      reversal: f.reversal.map(|r| proto::purchase::PaymentReversal {
+        payment_id: r.payment_id,
+        reason: r.reason,
+        created_by: r.created_by,
+        created_at: r.created_at.to_rfc3339(),
+      }),
+    }
+  }
+}
+
+impl From<cart::Payment> for purchase::Payment {
+  fn from(f: cart::Payment) -> Self {
+    Self {
+      payment_id: f.payment_id,
+      amount: f.amount,
+      kind: f.kind.into(),
+      tendered: f.tendered,
+      change: f.change,
+      reversal: f.reversal.map(|r| purchase::PaymentReversal {
+        payment_id: r.payment_id,
+        reason: r.reason,
+        created_by: r.created_by,
+        created_at: r.created_at,
+      }),
+    }
+  }
+}
+
+impl From<purchase::Payment> for cart::Payment {
+  fn from(f: purchase::Payment) -> Self {
+    Self {
+      payment_id: f.payment_id,
+      amount: f.amount,
+      kind: f.kind.into(),
+      tendered: f.tendered,
+      change: f.change,
+      reversal: f.reversal.map(|r| cart::PaymentReversal {
+        payment_id: r.payment_id,
+        reason: r.reason,
+        created_by: r.created_by,
+        created_at: r.created_at,
+      }),
     }
   }
 }
@@ -270,6 +721,7 @@ impl From<cart::LoyaltyTransaction> for LoyaltyTransaction {
       loyalty_account_id: f.loyalty_account_id.to_string(),
       transaction_id: f.transaction_id.to_string(),
       burned_points: f.burned_points,
+      reversed_transaction_id: "".to_string(),
     }
   }
 }
@@ -280,12 +732,19 @@ impl From<purchase::LoyaltyTransaction> for LoyaltyTransaction {
       loyalty_account_id: f.loyalty_account_id.to_string(),
       transaction_id: f.transaction_id.to_string(),
       burned_points: f.burned_points,
+      reversed_transaction_id: match f.reversed_transaction_id {
+        Some(id) => id.to_string(),
+        None => "".to_string(),
+      },
     }
   }
 }
 
 impl From<cart::Cart> for purchase::Purchase {
   fn from(f: cart::Cart) -> Self {
+    // Promotions are last evaluated at close
+    let promotion_time = f.promotion_time.unwrap_or_else(Utc::now);
+
     let mut items: Vec<purchase::Item> = Vec::new();
 
     items.extend(
@@ -293,6 +752,7 @@ impl From<cart::Cart> for purchase::Purchase {
         .iter()
         .map(|i| purchase::Item {
           kind: purchase::ItemKind::Sku, // Its just SKU
+          sku: i.sku,
           product_id: 0, // 0 as its normal shopping list items, we wont need pid for invoice
           name: i.name.to_string(),
           piece: i.piece,
@@ -301,6 +761,9 @@ impl From<cart::Cart> for purchase::Purchase {
           retail_price_gross: i.unit_price_gross,
           total_retail_price_net: i.total_price_net,
           total_retail_price_gross: i.total_price_gross,
+          price_override: i.price_override.clone().map(|po| po.into()),
+          amount: i.piece,
+          unit: Some(Unit::Piece),
         })
         .collect::<Vec<purchase::Item>>(),
     );
@@ -310,7 +773,7 @@ impl From<cart::Cart> for purchase::Purchase {
         .iter()
         .map(|i| purchase::Item {
           kind: match &i.kind {
-            cart::UplKind::Sku { sku, piece } => match i.depreciated {
+            cart::UplKind::Sku { sku: _, piece: _ } => match i.depreciated {
               true => purchase::ItemKind::DerivedProduct,
               false => purchase::ItemKind::Sku,
             },
@@ -319,6 +782,7 @@ impl From<cart::Cart> for purchase::Purchase {
               amount: _,
             } => purchase::ItemKind::DerivedProduct,
           },
+          sku: i.get_sku().unwrap_or(0),
           product_id: match &i.kind {
             cart::UplKind::DerivedProduct {
               product_id,
@@ -333,6 +797,9 @@ impl From<cart::Cart> for purchase::Purchase {
           retail_price_gross: i.retail_gross_price,
           total_retail_price_net: i.get_price_net(),
           total_retail_price_gross: i.get_price_gross(),
+          price_override: i.price_override.clone().map(|po| po.into()),
+          amount: i.get_amount(),
+          unit: i.unit,
         })
         .collect::<Vec<purchase::Item>>(),
     );
@@ -355,11 +822,11 @@ impl From<cart::Cart> for purchase::Purchase {
           vat: u.vat.to_string(),
           retail_gross_price: u.retail_gross_price,
           procurement_net_price: u.procurement_net_price,
-          best_before: match u.best_before {
-            Some(bb) => Some(bb),
-            None => None,
-          },
+          best_before: u.best_before,
           depreciated: u.depreciated,
+          price_override: u.price_override.clone().map(|po| po.into()),
+          unit: u.unit,
+          expiry_authorized_by: u.expiry_authorized_by,
         })
         .collect::<Vec<purchase::UplInfoObject>>(),
     );
@@ -382,66 +849,84 @@ impl From<cart::Cart> for purchase::Purchase {
           procurement_net_price: u.procurement_net_price,
           best_before: u.best_before,
           depreciated: u.depreciated,
+          price_override: u.price_override.clone().map(|po| po.into()),
+          unit: u.unit,
+          expiry_authorized_by: u.expiry_authorized_by,
         })
         .collect::<Vec<purchase::UplInfoObject>>(),
     );
 
     Self {
       id: f.id,
-      customer: match &f.customer {
-        Some(c) => Some(purchase::Customer {
-          id: c.id,
-          name: c.name.to_string(),
-          zip: c.zip.to_string(),
-          location: c.location.to_string(),
-          street: c.street.to_string(),
-          tax_number: c.tax_number.to_string(),
-        }),
-        None => None,
-      },
-      items: items,
+      customer: f.customer.as_ref().map(|c| purchase::Customer {
+        id: c.id,
+        name: c.name.to_string(),
+        zip: c.zip.to_string(),
+        location: c.location.to_string(),
+        street: c.street.to_string(),
+        tax_number: c.tax_number.to_string(),
+        payment_term: c.payment_term,
+      }),
+      items,
       upl_info_objects: upls,
       total_net: f.total_net,
       total_vat: f.total_vat,
       total_gross: f.total_gross,
+      vat_totals: f.vat_totals.clone(),
       document_kind: match f.document_kind {
         cart::DocumentKind::Receipt => purchase::DocumentKind::Receipt,
         cart::DocumentKind::Invoice => purchase::DocumentKind::Invoice,
       },
-      payment_kind: match f.payment_kind {
-        cart::PaymentKind::Cash => purchase::PaymentKind::Cash,
-        cart::PaymentKind::Card => purchase::PaymentKind::Card,
-        cart::PaymentKind::Transfer => purchase::PaymentKind::Transfer,
-      },
+      payment_kind: f.payment_kind.into(),
       payments: f
         .payments
         .iter()
-        .map(|p| purchase::Payment {
-          payment_id: p.payment_id.to_string(),
-          amount: p.amount,
-        })
-        .collect(),
+        .map(|p| p.clone().into())
+        .collect::<Vec<purchase::Payment>>(),
       payable: f.get_payable(),
       balance: f.get_balance(),
       profit_net: f.get_profit_net(),
       owner_uid: f.owner_uid,
       store_id: f.store_id,
-      date_completion: Utc::today().and_hms(0, 0, 0),
+      date_completion: f.date_completion,
       payment_duedate: match f.payment_duedate > Utc::now() {
         true => f.payment_duedate,
         false => Utc::today().and_hms(0, 0, 0),
       },
+      preorder: f.preorder.clone().map(|p| p.into()),
       restored: None,
+      refunds: Vec::new(),
+      returned_upls: Vec::new(),
+      refunded_net: 0,
+      refunded_gross: 0,
       created_by: f.created_by,
       created_at: f.created_at,
-      commitment: match f.commitment.clone() {
-        Some(c) => Some(purchase::Commitment {
-          commitment_id: c.commitment_id,
-          commitment_percentage: c.commitment_percentage,
-        }),
-        None => None,
-      },
+      events: f.events.clone(),
+      commitment: f.commitment.clone().map(|c| purchase::Commitment {
+        commitment_id: c.commitment_id,
+        commitment_percentage: c.commitment_percentage,
+      }),
       commitment_discount_value: f.commitment_discount_value,
+      // Keep the rules active at sale to evaluate them again on restore
+      promotions: f
+        .promotions
+        .iter()
+        .filter(|p| p.is_active(promotion_time))
+        .cloned()
+        .collect(),
+      promotion_time,
+      promotion_lines: f
+        .promotion_lines
+        .iter()
+        .map(|l| purchase::PromotionLine {
+          promotion_id: l.promotion_id,
+          name: l.name.to_string(),
+          skus: l.skus.clone(),
+          discount_net: l.discount_net,
+          discount_gross: l.discount_gross,
+        })
+        .collect::<Vec<purchase::PromotionLine>>(),
+      promotion_discount_value: f.get_promotion_discount_gross(),
       loyalty_card: match f.loyalty_card.clone() {
         Some(lc) => Some(purchase::LoyaltyCard {
           account_id: lc.account_id,
@@ -470,12 +955,206 @@ impl From<cart::Cart> for purchase::Purchase {
   }
 }
 
+impl From<cart::PriceOverride> for proto::purchase::PriceOverride {
+  fn from(f: cart::PriceOverride) -> Self {
+    Self {
+      original_price_net: f.original_price_net,
+      original_price_gross: f.original_price_gross,
+      price_net: f.price_net,
+      price_gross: f.price_gross,
+      reason: f.reason.to_string(),
+      authorized_by: f.authorized_by,
+      created_at: f.created_at.to_rfc3339(),
+    }
+  }
+}
+
+impl From<purchase::PriceOverride> for proto::purchase::PriceOverride {
+  fn from(f: purchase::PriceOverride) -> Self {
+    Self {
+      original_price_net: f.original_price_net,
+      original_price_gross: f.original_price_gross,
+      price_net: f.price_net,
+      price_gross: f.price_gross,
+      reason: f.reason.to_string(),
+      authorized_by: f.authorized_by,
+      created_at: f.created_at.to_rfc3339(),
+    }
+  }
+}
+
+impl From<cart::PriceOverride> for purchase::PriceOverride {
+  fn from(f: cart::PriceOverride) -> Self {
+    Self {
+      original_price_net: f.original_price_net,
+      original_price_gross: f.original_price_gross,
+      price_net: f.price_net,
+      price_gross: f.price_gross,
+      reason: match f.reason {
+        cart::PriceOverrideReason::Damaged => purchase::PriceOverrideReason::Damaged,
+        cart::PriceOverrideReason::PriceMatch => purchase::PriceOverrideReason::PriceMatch,
+        cart::PriceOverrideReason::Other => purchase::PriceOverrideReason::Other,
+      },
+      authorized_by: f.authorized_by,
+      created_at: f.created_at,
+    }
+  }
+}
+
+impl From<purchase::PriceOverride> for cart::PriceOverride {
+  fn from(f: purchase::PriceOverride) -> Self {
+    Self {
+      original_price_net: f.original_price_net,
+      original_price_gross: f.original_price_gross,
+      price_net: f.price_net,
+      price_gross: f.price_gross,
+      reason: match f.reason {
+        purchase::PriceOverrideReason::Damaged => cart::PriceOverrideReason::Damaged,
+        purchase::PriceOverrideReason::PriceMatch => cart::PriceOverrideReason::PriceMatch,
+        purchase::PriceOverrideReason::Other => cart::PriceOverrideReason::Other,
+      },
+      authorized_by: f.authorized_by,
+      created_at: f.created_at,
+    }
+  }
+}
+
+// Restored cart of a purchase
+// fails if a sold UPL cannot be put back into the cart
+impl TryFrom<purchase::Purchase> for cart::Cart {
+  type Error = DomainError;
+
+  fn try_from(f: purchase::Purchase) -> Result<Self, Self::Error> {
+    let mut cart = cart::Cart::new(f.owner_uid, f.store_id, f.created_by);
+
+    // Set the restored purchase as ancestor
+    cart.ancestor = Some(f.id);
+
+    cart.add_customer(match f.customer {
+      Some(c) => Some(cart::Customer {
+        id: c.id,
+        name: c.name,
+        zip: c.zip,
+        location: c.location,
+        street: c.street,
+        tax_number: c.tax_number,
+        payment_term: c.payment_term,
+      }),
+      None => None,
+    });
+
+    // Rebuild UPLs
+    // SKU UPLs are added to the shopping list as well
+    // Sold UPLs passed the expiry check at sale time
+    for u in f.upl_info_objects {
+      cart.attach_upl(cart::UplInfoObject {
+        upl_id: u.upl_id,
+        kind: match u.kind {
+          purchase::UplKind::Sku { sku, piece } => cart::UplKind::Sku { sku, piece },
+          purchase::UplKind::DerivedProduct { product_id, amount } => {
+            cart::UplKind::DerivedProduct { product_id, amount }
+          }
+        },
+        name: u.name,
+        retail_net_price: u.retail_net_price,
+        vat: cart::VAT::from_str(&u.vat).unwrap_or_default(),
+        retail_gross_price: u.retail_gross_price,
+        procurement_net_price: u.procurement_net_price,
+        best_before: u.best_before,
+        depreciated: u.depreciated,
+        // Keep price override for audit
+        price_override: u.price_override.map(|po| po.into()),
+        unit: u.unit,
+        expiry_authorized_by: u.expiry_authorized_by,
+      })?;
+    }
+
+    // Restore shopping list prices
+    // Purchases without SKU in items keep the UPL prices
+    for i in &f.items {
+      if let purchase::ItemKind::Sku = i.kind {
+        if i.sku != 0 {
+          cart.add_sku(
+            i.sku,
+            i.piece,
+            i.name.to_string(),
+            cart::VAT::from_str(&i.vat).unwrap_or_default(),
+            i.retail_price_net,
+            i.retail_price_gross,
+          );
+          // Keep price override for audit
+          if let Some(po) = &i.price_override {
+            if let Some(item) = cart.shopping_list.iter_mut().find(|li| li.sku == i.sku) {
+              item.price_override = Some(po.clone().into());
+            }
+          }
+        }
+      }
+    }
+
+    cart.set_document(match f.document_kind {
+      purchase::DocumentKind::Receipt => cart::DocumentKind::Receipt,
+      purchase::DocumentKind::Invoice => cart::DocumentKind::Invoice,
+    });
+
+    cart.set_payment(f.payment_kind.into());
+
+    cart.commitment = f
+      .commitment
+      .map(|c| cart::Commitment::new(c.commitment_id, c.commitment_percentage));
+
+    cart.loyalty_card = f.loyalty_card.map(|lc| {
+      cart::LoyaltyCard::new(
+        lc.account_id,
+        lc.card_id,
+        match lc.level {
+          purchase::LoyaltyLevel::L1 => cart::LoyaltyLevel::L1,
+          purchase::LoyaltyLevel::L2 => cart::LoyaltyLevel::L2,
+        },
+      )
+    });
+
+    // Move burned points to the new cart
+    cart.burned_points = f
+      .burned_points
+      .into_iter()
+      .map(|t| cart::LoyaltyTransaction {
+        loyalty_account_id: t.loyalty_account_id,
+        transaction_id: t.transaction_id,
+        burned_points: t.burned_points,
+        created_at: t.created_at,
+      })
+      .collect::<Vec<cart::LoyaltyTransaction>>();
+
+    // Move payments to the new cart
+    cart.payments = f
+      .payments
+      .iter()
+      .map(|p| p.clone().into())
+      .collect::<Vec<cart::Payment>>();
+
+    cart.date_completion = f.date_completion;
+    cart.payment_duedate = f.payment_duedate;
+    // Restored cart keeps the duedate of the sale
+    cart.payment_duedate_explicit = true;
+
+    // Restored cart gets the promotions of the sale, not the current ones
+    cart.promotions = f.promotions;
+    cart.promotion_time = Some(f.promotion_time);
+
+    cart.calculate_totals();
+
+    Ok(cart)
+  }
+}
+
 impl From<cart::LoyaltyTransaction> for purchase::LoyaltyTransaction {
   fn from(f: cart::LoyaltyTransaction) -> Self {
     Self {
       loyalty_account_id: f.loyalty_account_id,
       transaction_id: f.transaction_id,
       burned_points: f.burned_points,
+      reversed_transaction_id: None,
       created_at: f.created_at,
     }
   }
@@ -493,6 +1172,7 @@ impl From<purchase::Purchase> for PurchaseInfoObject {
           location: c.location,
           street: c.street,
           tax_number: c.tax_number,
+          payment_term: c.payment_term,
         }),
         None => None,
       },
@@ -521,7 +1201,7 @@ impl From<purchase::Purchase> for PurchaseInfoObject {
   }
 }
 
-impl From<crate::purchase::LoyaltyCard> for gzlib::proto::purchase::purchase_object::LoyaltyCard {
+impl From<crate::purchase::LoyaltyCard> for proto::purchase::purchase_object::LoyaltyCard {
   fn from(f: crate::purchase::LoyaltyCard) -> Self {
     Self {
       account_id: f.account_id.to_string(),
@@ -535,8 +1215,62 @@ impl From<crate::purchase::LoyaltyCard> for gzlib::proto::purchase::purchase_obj
   }
 }
 
+impl From<purchase::Item> for purchase_object::Item {
+  fn from(i: purchase::Item) -> Self {
+    Self {
+      kind: match i.kind {
+        purchase::ItemKind::Sku => purchase_object::ItemKind::Sku,
+        purchase::ItemKind::SkuDepreciated => purchase_object::ItemKind::DepreciatedSku,
+        purchase::ItemKind::DerivedProduct => purchase_object::ItemKind::DerivedProduct,
+      } as i32,
+      sku: i.sku,
+      product_id: i.product_id,
+      name: i.name,
+      piece: i.piece,
+      retail_price_net: i.retail_price_net,
+      vat: i.vat,
+      retail_price_gross: i.retail_price_gross,
+      total_retail_price_net: i.total_retail_price_net,
+      total_retail_price_gross: i.total_retail_price_gross,
+      price_override: i.price_override.map(|po| po.into()),
+      upl_ids: Vec::new(), // TODO remove this
+      amount: i.amount,
+      unit: unit::code(i.unit),
+    }
+  }
+}
+
+impl From<refund::Refund> for proto::purchase::RefundObject {
+  fn from(f: refund::Refund) -> Self {
+    Self {
+      refund_id: f.id.to_string(),
+      purchase_id: f.purchase_id.to_string(),
+      upl_ids: f.get_upl_ids(),
+      items: f
+        .items
+        .into_iter()
+        .map(|i| i.into())
+        .collect::<Vec<purchase_object::Item>>(),
+      total_net: f.total_net,
+      total_vat: f.total_vat,
+      total_gross: f.total_gross,
+      profit_net: f.profit_net,
+      payments: f
+        .payments
+        .iter()
+        .map(|p| p.clone().into())
+        .collect::<Vec<proto::purchase::Payment>>(),
+      payable: f.payable,
+      payment_balance: f.balance,
+      created_by: f.created_by,
+      created_at: f.created_at.to_rfc3339(),
+    }
+  }
+}
+
 impl From<purchase::Purchase> for PurchaseObject {
   fn from(f: purchase::Purchase) -> Self {
+    let receipt_texts = i18n::receipt_texts(&f, Locale::default());
     Self {
       id: f.id.to_string(),
       customer: match f.customer {
@@ -547,28 +1281,14 @@ impl From<purchase::Purchase> for PurchaseObject {
           location: c.location,
           street: c.street,
           tax_number: c.tax_number,
+          payment_term: c.payment_term,
         }),
         None => None,
       },
       items: f
         .items
         .iter()
-        .map(|i| proto::purchase::purchase_object::Item {
-          kind: match i.kind {
-            purchase::ItemKind::Sku => purchase_object::ItemKind::Sku,
-            purchase::ItemKind::SkuDepreciated => purchase_object::ItemKind::DepreciatedSku,
-            purchase::ItemKind::DerivedProduct => purchase_object::ItemKind::DerivedProduct,
-          } as i32,
-          product_id: i.product_id,
-          name: i.name.clone(),
-          piece: i.piece,
-          retail_price_net: i.retail_price_net,
-          vat: i.vat.clone(),
-          retail_price_gross: i.retail_price_gross,
-          total_retail_price_net: i.total_retail_price_net,
-          total_retail_price_gross: i.total_retail_price_gross,
-          upl_ids: Vec::new(), // TODO remove this
-        })
+        .map(|i| i.clone().into())
         .collect::<Vec<proto::purchase::purchase_object::Item>>(),
       upl_info_objects: Vec::new(),
       need_invoice: match f.document_kind {
@@ -582,18 +1302,11 @@ impl From<purchase::Purchase> for PurchaseObject {
       total_net: f.total_net,
       total_vat: f.total_vat,
       total_gross: f.total_gross,
-      payment_kind: match f.payment_kind {
-        purchase::PaymentKind::Cash => proto::purchase::PaymentKind::Cash,
-        purchase::PaymentKind::Card => proto::purchase::PaymentKind::Card,
-        purchase::PaymentKind::Transfer => proto::purchase::PaymentKind::Transfer,
-      } as i32,
+      payment_kind: PaymentKind::from(f.payment_kind) as i32,
       payments: f
         .payments
         .iter()
-        .map(|p| proto::purchase::Payment {
-          payment_id: p.payment_id.clone(),
-          amount: p.amount,
-        })
+        .map(|p| p.clone().into())
         .collect::<Vec<proto::purchase::Payment>>(),
       payable: f.payable,
       payment_balance: f.balance,
@@ -602,7 +1315,20 @@ impl From<purchase::Purchase> for PurchaseObject {
       store_id: f.store_id.unwrap_or(0),
       date_completion: f.date_completion.to_rfc3339(),
       payment_duedate: f.payment_duedate.to_rfc3339(),
+      preorder: f.preorder.map(|p| p.into()),
       restored: f.restored.is_some(),
+      restored_cart_id: match f.restored {
+        Some(cart_id) => cart_id.to_string(),
+        None => "".to_string(),
+      },
+      refund_ids: f
+        .refunds
+        .iter()
+        .map(|r| r.to_string())
+        .collect::<Vec<String>>(),
+      returned_upl_ids: f.returned_upls.clone(),
+      refunded_net: f.refunded_net,
+      refunded_gross: f.refunded_gross,
       created_by: f.created_by,
       created_at: f.created_at.to_rfc3339(),
       commitment_id: match f.commitment.clone() {
@@ -614,6 +1340,12 @@ impl From<purchase::Purchase> for PurchaseObject {
         None => 0,
       },
       commitment_discount_amount_gross: f.commitment_discount_value,
+      promotion_lines: f
+        .promotion_lines
+        .into_iter()
+        .map(|l| l.into())
+        .collect::<Vec<proto::purchase::PromotionLine>>(),
+      promotion_discount_amount_gross: f.promotion_discount_value,
       loyalty_card: f.loyalty_card.map(|lc| lc.into()),
       burned_loyalty_points: f.burned_loyalty_points,
       burned_points: f
@@ -621,6 +1353,23 @@ impl From<purchase::Purchase> for PurchaseObject {
         .into_iter()
         .map(|tr| tr.into())
         .collect::<Vec<LoyaltyTransaction>>(),
+      receipt_texts,
+      vat_totals: f
+        .vat_totals
+        .into_iter()
+        .map(|t| t.into())
+        .collect::<Vec<proto::purchase::VatTotal>>(),
+    }
+  }
+}
+
+impl From<cart::VatTotal> for proto::purchase::VatTotal {
+  fn from(f: cart::VatTotal) -> Self {
+    Self {
+      vat: f.vat.to_string(),
+      total_net: f.total_net,
+      total_vat: f.total_vat,
+      total_gross: f.total_gross,
     }
   }
 }
@@ -0,0 +1,232 @@
+// Promotion rules
+// Buy X pay Y, Bundle price, Category percentage
+
+use crate::cart::ListItem;
+use crate::error::DomainError;
+use crate::rounding::{div_round, percent_of};
+use chrono::prelude::*;
+use packman::VecPackMember;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Promotion {
+  pub id: Uuid,                  // Promotion ID
+  pub name: String,              // Shown on the discount line
+  pub kind: PromotionKind,       // Promotion rule
+  pub valid_from: DateTime<Utc>, // Valid from
+  pub valid_till: DateTime<Utc>, // Valid till
+  pub created_by: u32,           // UID
+  pub created_at: DateTime<Utc>, // When promotion created
+}
+
+impl Default for Promotion {
+  fn default() -> Self {
+    Self {
+      id: Uuid::default(),
+      name: String::default(),
+      kind: PromotionKind::default(),
+      valid_from: Utc::now(),
+      valid_till: Utc::now(),
+      created_by: 0,
+      created_at: Utc::now(),
+    }
+  }
+}
+
+impl VecPackMember for Promotion {
+  type Out = Uuid;
+
+  fn get_id(&self) -> &Self::Out {
+    &self.id
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum PromotionKind {
+  // Buy `buy` pieces of a SKU, pay only `pay` pieces
+  BuyXPayY {
+    sku: u32,
+    buy: u32,
+    pay: u32,
+  },
+  // Given SKUs together for a fixed gross price
+  BundlePrice {
+    items: Vec<BundleItem>,
+    price_gross: u32,
+  },
+  // Percentage discount for every SKU of a category
+  // The SKUs of the category are resolved when the promotion is created,
+  // as products and categories are not known here;
+  // category_id is kept for reference and reporting only
+  CategoryPercentage {
+    category_id: u32,
+    skus: Vec<u32>,
+    percentage: u32,
+  },
+}
+
+impl Default for PromotionKind {
+  fn default() -> Self {
+    Self::CategoryPercentage {
+      category_id: 0,
+      skus: Vec::new(),
+      percentage: 0,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BundleItem {
+  pub sku: u32,
+  pub piece: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PromotionLine {
+  pub promotion_id: Uuid,  // Applied promotion ID
+  pub name: String,        // Promotion name
+  pub skus: Vec<u32>,      // Discounted SKUs
+  pub discount_net: u32,   // Discount net value
+  pub discount_gross: u32, // Discount gross value
+}
+
+impl Default for PromotionLine {
+  fn default() -> Self {
+    Self {
+      promotion_id: Uuid::default(),
+      name: String::default(),
+      skus: Vec::new(),
+      discount_net: 0,
+      discount_gross: 0,
+    }
+  }
+}
+
+impl Promotion {
+  pub fn new(
+    name: String,
+    kind: PromotionKind,
+    valid_from: DateTime<Utc>,
+    valid_till: DateTime<Utc>,
+    created_by: u32,
+  ) -> Result<Self, DomainError> {
+    if valid_from >= valid_till {
+      return Err(DomainError::PromotionInvalidPeriod);
+    }
+    match &kind {
+      PromotionKind::BuyXPayY { sku: _, buy, pay } => {
+        if *pay == 0 || pay >= buy {
+          return Err(DomainError::PromotionInvalidBuyXPayY);
+        }
+      }
+      PromotionKind::BundlePrice { items, price_gross } => {
+        if items.is_empty() || *price_gross == 0 {
+          return Err(DomainError::PromotionBundleEmpty);
+        }
+        if items.iter().any(|i| i.piece == 0) {
+          return Err(DomainError::PromotionBundleZeroPiece);
+        }
+      }
+      PromotionKind::CategoryPercentage {
+        category_id: _,
+        skus,
+        percentage,
+      } => {
+        if *percentage == 0 || *percentage > 100 {
+          return Err(DomainError::PromotionInvalidPercentage);
+        }
+        if skus.is_empty() {
+          return Err(DomainError::PromotionCategoryNoSkus);
+        }
+      }
+    }
+    Ok(Self {
+      id: Uuid::new_v4(),
+      name,
+      kind,
+      valid_from,
+      valid_till,
+      created_by,
+      created_at: Utc::now(),
+    })
+  }
+  /// Check if promotion is valid at the given time
+  pub fn is_active(&self, at: DateTime<Utc>) -> bool {
+    self.valid_from <= at && at < self.valid_till
+  }
+  /// Try to apply promotion to the shopping list
+  /// SKUs in `used_skus` are already discounted by another promotion
+  pub fn evaluate(&self, shopping_list: &[ListItem], used_skus: &[u32]) -> Option<PromotionLine> {
+    let find = |sku: u32| {
+      shopping_list
+        .iter()
+        .find(|i| i.sku == sku && !used_skus.contains(&sku))
+    };
+    let (skus, discount_net, discount_gross) = match &self.kind {
+      PromotionKind::BuyXPayY { sku, buy, pay } => {
+        let item = find(*sku)?;
+        let free_piece = (item.piece / buy) * (buy - pay);
+        (
+          vec![*sku],
+          free_piece * item.unit_price_net,
+          free_piece * item.unit_price_gross,
+        )
+      }
+      PromotionKind::BundlePrice { items, price_gross } => {
+        let mut bundle_count = u32::MAX;
+        let mut regular_net = 0;
+        let mut regular_gross = 0;
+        for bundle_item in items {
+          let item = find(bundle_item.sku)?;
+          bundle_count = bundle_count.min(item.piece / bundle_item.piece);
+          regular_net += item.unit_price_net * bundle_item.piece;
+          regular_gross += item.unit_price_gross * bundle_item.piece;
+        }
+        if regular_gross <= *price_gross {
+          return None;
+        }
+        let discount_gross = bundle_count * (regular_gross - price_gross);
+        (
+          items.iter().map(|i| i.sku).collect(),
+          div_round(
+            discount_gross as u64 * regular_net as u64,
+            regular_gross as u64,
+          ) as u32,
+          discount_gross,
+        )
+      }
+      PromotionKind::CategoryPercentage {
+        category_id: _,
+        skus,
+        percentage,
+      } => {
+        let items = skus
+          .iter()
+          .filter_map(|sku| find(*sku))
+          .collect::<Vec<&ListItem>>();
+        (
+          items.iter().map(|i| i.sku).collect(),
+          items
+            .iter()
+            .map(|i| percent_of(i.total_price_net, *percentage))
+            .sum(),
+          items
+            .iter()
+            .map(|i| percent_of(i.total_price_gross, *percentage))
+            .sum(),
+        )
+      }
+    };
+    match discount_gross > 0 {
+      true => Some(PromotionLine {
+        promotion_id: self.id,
+        name: self.name.to_string(),
+        skus,
+        discount_net,
+        discount_gross,
+      }),
+      false => None,
+    }
+  }
+}
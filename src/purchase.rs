@@ -1,6 +1,15 @@
 // SKU to CART
 // SKU, Derived Product, Depreciated
 
+pub use crate::cart::VatTotal;
+use crate::cart::VAT;
+use crate::error::DomainError;
+use crate::event::CartEvent;
+use crate::i18n::ReceiptText;
+use crate::promotion::Promotion;
+use crate::rounding::allocate;
+use crate::unit;
+pub use crate::unit::Unit;
 use chrono::prelude::*;
 use packman::VecPackMember;
 use serde::{Deserialize, Serialize};
@@ -8,7 +17,7 @@ use uuid::Uuid;
 
 pub trait PurchaseExt {
   /// Set related invoice ID
-  fn set_invoice_id(&mut self, invoice_id: String) -> Result<&Self, String>;
+  fn set_invoice_id(&mut self, invoice_id: String) -> Result<&Self, DomainError>;
   /// Set loyalty summary info
   /// given by the loyalty service
   fn set_loyalty_summary(
@@ -17,7 +26,25 @@ pub trait PurchaseExt {
     burned_points: i32,
     earned_points: i32,
     balance_closing: i32,
-  ) -> Result<&Self, String>;
+  ) -> Result<&Self, DomainError>;
+  /// Set purchase as restored
+  /// and reverse its payments and burned points
+  /// as they are moved to the restored cart
+  /// Purchase with refunds cannot be restored
+  fn set_restored(&mut self, cart_id: Uuid, restored_by: u32) -> Result<&Self, DomainError>;
+  /// Add refund (partial return) to purchase
+  /// and update its net and profit figures
+  fn add_refund(&mut self, refund: &crate::refund::Refund) -> Result<&Self, DomainError>;
+  /// Get the already returned UPL IDs
+  fn get_returned_upls(&self) -> Vec<String>;
+  /// Get the total of the reversals linked to the given payment
+  fn get_reversed_amount(&self, payment_id: &str) -> i32;
+  /// Get the discounted net and gross value of every item
+  /// in the order of the items
+  fn get_discounted_items(&self) -> Vec<(u32, u32)>;
+  /// Net, VAT and gross subtotals per VAT rate
+  /// from the discounted items
+  fn compute_vat_totals(&self) -> Vec<VatTotal>;
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -27,11 +54,16 @@ pub struct Purchase {
   pub commitment: Option<Commitment>,         // Applied customer commitment
   pub commitment_discount_value: u32,         //
   pub loyalty_card: Option<LoyaltyCard>,      // Applied loyalty card
+  pub promotions: Vec<Promotion>,             // Promotion rules active at sale
+  pub promotion_time: DateTime<Utc>,          // When promotions were evaluated
+  pub promotion_lines: Vec<PromotionLine>,    // Applied promotion discount lines
+  pub promotion_discount_value: u32,          // Promotions total gross discount
   pub items: Vec<Item>,                       // Cart items (All items: shopping list + unique)
   pub upl_info_objects: Vec<UplInfoObject>,   // ALL UPL info objects
   pub total_net: u32,                         // Total cart net value in HUF
   pub total_vat: u32,                         // Total VAT
   pub total_gross: u32,                       // Total cart gross value in HUF
+  pub vat_totals: Vec<VatTotal>,              // Totals per VAT rate
   pub document_kind: DocumentKind,            // Receipt or Invoice
   pub payment_kind: PaymentKind,              // cash, transfer, card
   pub payments: Vec<Payment>,                 // Payment vector
@@ -44,17 +76,23 @@ pub struct Purchase {
   pub store_id: Option<u32>,                  // Now its stock ID
   pub date_completion: DateTime<Utc>,         // Completion date
   pub payment_duedate: DateTime<Utc>,         // Payment duedate
+  pub preorder: Option<Preorder>,             // Some(_) if it was a pre-order
   pub restored: Option<Uuid>,                 // Some(_) if its restored
+  pub refunds: Vec<Uuid>,                     // Related refund IDs
+  pub returned_upls: Vec<String>,             // Already returned UPL IDs
+  pub refunded_net: u32,                      // Total returned net value
+  pub refunded_gross: u32,                    // Total returned gross value
   pub invoice: Option<String>,                // Invoice
   pub storno_invoice: Option<String>,         // Storno invoice
   pub created_by: u32,                        // UID
   pub created_at: DateTime<Utc>,              // When cart created
+  pub events: Vec<CartEvent>,                 // Audit trail of the cart
 }
 
 impl PurchaseExt for Purchase {
-  fn set_invoice_id(&mut self, invoice_id: String) -> Result<&Self, String> {
+  fn set_invoice_id(&mut self, invoice_id: String) -> Result<&Self, DomainError> {
     match self.invoice {
-      Some(_) => Err("A vásárlás már rendelkezik számlával".to_string()),
+      Some(_) => Err(DomainError::InvoiceAlreadySet),
       None => {
         self.invoice = Some(invoice_id);
         Ok(self)
@@ -68,16 +106,12 @@ impl PurchaseExt for Purchase {
     burned_points: i32,
     earned_points: i32,
     balance_closing: i32,
-  ) -> Result<&Self, String> {
+  ) -> Result<&Self, DomainError> {
     match &mut self.loyalty_card {
       Some(loyalty) => {
         // Check if burned points are ok
         if self.burned_loyalty_points as i32 != burned_points {
-          return Err(
-            "A vásárláshoz rendelt felhasznált pontok összege nem egyezik meg az
-          összefoglaló szerinti felhasznált pontok összegével."
-              .to_string(),
-          );
+          return Err(DomainError::BurnedPointsMismatch);
         }
         loyalty.balance_opening = balance_opening;
         loyalty.burned_points = burned_points;
@@ -85,13 +119,183 @@ impl PurchaseExt for Purchase {
         loyalty.balance_closing = balance_closing;
         Ok(self)
       }
-      None => Err(
-        "A vásárláshoz nem tartozik törzsvásárlói kártya,
-        így nem lehet hozzá adatokat adni."
-          .to_string(),
-      ),
+      None => Err(DomainError::PurchaseNoLoyaltyCard),
     }
   }
+
+  fn set_restored(&mut self, cart_id: Uuid, restored_by: u32) -> Result<&Self, DomainError> {
+    if self.restored.is_some() {
+      return Err(DomainError::AlreadyRestored);
+    }
+    // Refunded UPLs and payments are already settled,
+    // restoring them again would refund them twice
+    if !self.refunds.is_empty() {
+      return Err(DomainError::RestoreOfRefunded);
+    }
+    // Reverse what is left of each payment,
+    // amounts already voided or reversed on the cart are not refunded again
+    let reversed_payments = self
+      .payments
+      .iter()
+      .filter(|p| p.reversal.is_none())
+      .map(|p| (p, p.amount + self.get_reversed_amount(&p.payment_id)))
+      .filter(|(_, remaining)| *remaining != 0)
+      .map(|(p, remaining)| Payment {
+        payment_id: Uuid::new_v4().to_string(),
+        amount: -remaining,
+        kind: p.kind,
+        tendered: 0,
+        change: 0,
+        reversal: Some(PaymentReversal {
+          payment_id: p.payment_id.to_string(),
+          // Localized on display by the receipt texts
+          reason: ReceiptText::Restored.key(),
+          created_by: restored_by,
+          created_at: Utc::now(),
+        }),
+      })
+      .collect::<Vec<Payment>>();
+    self.payments.extend(reversed_payments);
+    // Reverse burned points
+    // each reversal is a new transaction linked to the burn
+    let reversed_points = self
+      .burned_points
+      .iter()
+      .map(|t| LoyaltyTransaction {
+        loyalty_account_id: t.loyalty_account_id,
+        transaction_id: Uuid::new_v4(),
+        burned_points: -t.burned_points,
+        reversed_transaction_id: Some(t.transaction_id),
+        created_at: Utc::now(),
+      })
+      .collect::<Vec<LoyaltyTransaction>>();
+    self.burned_points.extend(reversed_points);
+    self.burned_loyalty_points = 0;
+    // Update balance
+    self.balance = self.payable - self.payments.iter().map(|p| p.amount).sum::<i32>();
+    // Set restored cart ID
+    self.restored = Some(cart_id);
+    Ok(self)
+  }
+
+  fn add_refund(&mut self, refund: &crate::refund::Refund) -> Result<&Self, DomainError> {
+    if refund.purchase_id != self.id {
+      return Err(DomainError::RefundNotForPurchase);
+    }
+    if self.restored.is_some() {
+      return Err(DomainError::RefundOfRestored);
+    }
+    // Check if any of the UPLs is already returned
+    for upl_id in refund.get_upl_ids() {
+      if self.returned_upls.contains(&upl_id) {
+        return Err(DomainError::UplAlreadyReturned { upl_id });
+      }
+    }
+    // Update returned figures
+    self.refunds.push(refund.id);
+    self.returned_upls.extend(refund.get_upl_ids());
+    self.refunded_net += refund.total_net;
+    self.refunded_gross += refund.total_gross;
+    self.profit_net -= refund.profit_net;
+    Ok(self)
+  }
+
+  fn get_returned_upls(&self) -> Vec<String> {
+    self.returned_upls.clone()
+  }
+
+  fn get_reversed_amount(&self, payment_id: &str) -> i32 {
+    self
+      .payments
+      .iter()
+      .filter(|p| match &p.reversal {
+        Some(r) => r.payment_id == payment_id,
+        None => false,
+      })
+      .map(|p| p.amount)
+      .sum::<i32>()
+  }
+
+  fn get_discounted_items(&self) -> Vec<(u32, u32)> {
+    let mut res = self
+      .items
+      .iter()
+      .map(|i| (i.total_retail_price_net, i.total_retail_price_gross))
+      .collect::<Vec<(u32, u32)>>();
+
+    // Promotion discounts are split between the discounted SKUs
+    // by their gross value, as in the cart VAT totals
+    for line in &self.promotion_lines {
+      let indexes = (0..self.items.len())
+        .filter(|k| match self.items[*k].kind {
+          ItemKind::Sku => line.skus.contains(&self.items[*k].sku),
+          _ => false,
+        })
+        .collect::<Vec<usize>>();
+      let weights = indexes
+        .iter()
+        .map(|k| self.items[*k].total_retail_price_gross)
+        .collect::<Vec<u32>>();
+      let nets = allocate(line.discount_net, &weights);
+      let grosses = allocate(line.discount_gross, &weights);
+      for (n, k) in indexes.iter().enumerate() {
+        res[*k].0 = res[*k].0.saturating_sub(nets[n]);
+        res[*k].1 = res[*k].1.saturating_sub(grosses[n]);
+      }
+    }
+
+    // Commitment and loyalty discounts are split between the VAT groups
+    // by their gross value, then between the items of the group
+    let vats = self
+      .items
+      .iter()
+      .map(|i| VAT::from_str(&i.vat).unwrap_or_default())
+      .collect::<Vec<VAT>>();
+    let mut groups: Vec<VAT> = Vec::new();
+    for vat in &vats {
+      if !groups.contains(vat) {
+        groups.push(*vat);
+      }
+    }
+    let group_weights = groups
+      .iter()
+      .map(|vat| {
+        (0..res.len())
+          .filter(|k| vats[*k] == *vat)
+          .map(|k| res[k].1)
+          .sum::<u32>()
+      })
+      .collect::<Vec<u32>>();
+    let discount_gross = self.commitment_discount_value + self.burned_loyalty_points;
+    for (vat, share) in groups.iter().zip(allocate(discount_gross, &group_weights)) {
+      let indexes = (0..res.len())
+        .filter(|k| vats[*k] == *vat)
+        .collect::<Vec<usize>>();
+      let weights = indexes.iter().map(|k| res[*k].1).collect::<Vec<u32>>();
+      let nets = allocate(vat.net_of(share), &weights);
+      let grosses = allocate(share, &weights);
+      for (n, k) in indexes.iter().enumerate() {
+        res[*k].0 = res[*k].0.saturating_sub(nets[n]);
+        res[*k].1 = res[*k].1.saturating_sub(grosses[n]);
+      }
+    }
+
+    res
+  }
+
+  fn compute_vat_totals(&self) -> Vec<VatTotal> {
+    let mut res: Vec<VatTotal> = Vec::new();
+    for (item, (net, gross)) in self.items.iter().zip(self.get_discounted_items()) {
+      let group = VatTotal::find_or_insert(&mut res, VAT::from_str(&item.vat).unwrap_or_default());
+      group.total_net += net;
+      group.total_gross += gross;
+    }
+    for group in &mut res {
+      group.total_vat = group.total_gross.saturating_sub(group.total_net);
+    }
+    res.sort_by_key(|g| g.vat as u8);
+    res
+  }
 }
 
 impl Default for Purchase {
@@ -102,11 +306,16 @@ impl Default for Purchase {
       commitment: None,
       commitment_discount_value: 0,
       loyalty_card: None,
+      promotions: Vec::new(),
+      promotion_time: Utc::now(),
+      promotion_lines: Vec::new(),
+      promotion_discount_value: 0,
       items: Vec::new(),
       upl_info_objects: Vec::new(),
       total_net: 0,
       total_vat: 0,
       total_gross: 0,
+      vat_totals: Vec::new(),
       document_kind: DocumentKind::default(),
       payment_kind: PaymentKind::default(),
       payments: Vec::new(),
@@ -119,11 +328,17 @@ impl Default for Purchase {
       store_id: None,
       date_completion: Utc::today().and_hms(0, 0, 0),
       payment_duedate: Utc::today().and_hms(0, 0, 0),
+      preorder: None,
       restored: None,
+      refunds: Vec::new(),
+      returned_upls: Vec::new(),
+      refunded_net: 0,
+      refunded_gross: 0,
       invoice: None,
       storno_invoice: None,
       created_by: 0,
       created_at: Utc::now(),
+      events: Vec::new(),
     }
   }
 }
@@ -156,6 +371,7 @@ pub struct Customer {
   pub location: String,
   pub street: String,
   pub tax_number: String,
+  pub payment_term: u32, // Payment term in days; 0 if default
 }
 
 impl Default for Customer {
@@ -167,11 +383,12 @@ impl Default for Customer {
       location: String::default(),
       street: String::default(),
       tax_number: String::default(),
+      payment_term: 0,
     }
   }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub enum PaymentKind {
   Cash,
   Card,
@@ -188,6 +405,10 @@ impl Default for PaymentKind {
 pub struct Payment {
   pub payment_id: String,
   pub amount: i32,
+  pub kind: PaymentKind,
+  pub tendered: i32, // Cash handed over by the customer; 0 if not recorded
+  pub change: i32,   // Cash given back to the customer
+  pub reversal: Option<PaymentReversal>, // Some(_) if it reverses another payment
 }
 
 impl Default for Payment {
@@ -195,6 +416,52 @@ impl Default for Payment {
     Self {
       payment_id: String::default(),
       amount: 0,
+      kind: PaymentKind::default(),
+      tendered: 0,
+      change: 0,
+      reversal: None,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PaymentReversal {
+  pub payment_id: String,        // Reversed payment ID
+  pub reason: String,            // Why it was reversed, RESTORED key if restored
+  pub created_by: u32,           // UID
+  pub created_at: DateTime<Utc>, // When payment reversed
+}
+
+impl Default for PaymentReversal {
+  fn default() -> Self {
+    Self {
+      payment_id: String::default(),
+      reason: String::default(),
+      created_by: 0,
+      created_at: Utc::now(),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Preorder {
+  pub expected_arrival: DateTime<Utc>, // When stock was expected
+  pub deposit: i32,                    // Deposit paid in advance
+  pub created_by: u32,                 // UID
+  pub created_at: DateTime<Utc>,       // When pre-order created
+  pub fulfilled_by: Option<u32>,       // UID who marked it arrived
+  pub fulfilled_at: Option<DateTime<Utc>>, // When stock arrived
+}
+
+impl Default for Preorder {
+  fn default() -> Self {
+    Self {
+      expected_arrival: Utc::now(),
+      deposit: 0,
+      created_by: 0,
+      created_at: Utc::now(),
+      fulfilled_by: None,
+      fulfilled_at: None,
     }
   }
 }
@@ -202,6 +469,7 @@ impl Default for Payment {
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Item {
   pub kind: ItemKind,
+  pub sku: u32,
   pub product_id: u32,
   pub name: String,
   pub piece: u32,
@@ -210,12 +478,16 @@ pub struct Item {
   pub retail_price_gross: u32,
   pub total_retail_price_net: u32,
   pub total_retail_price_gross: u32,
+  pub price_override: Option<PriceOverride>,
+  pub amount: u32,        // Piece or DerivedProduct amount
+  pub unit: Option<Unit>, // Unit of amount
 }
 
 impl Default for Item {
   fn default() -> Self {
     Self {
       kind: ItemKind::default(),
+      sku: 0,
       product_id: 0,
       name: String::default(),
       piece: 0,
@@ -224,6 +496,57 @@ impl Default for Item {
       retail_price_gross: 0,
       total_retail_price_net: 0,
       total_retail_price_gross: 0,
+      price_override: None,
+      amount: 0,
+      unit: None,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum PriceOverrideReason {
+  Damaged,
+  PriceMatch,
+  Other,
+}
+
+impl Default for PriceOverrideReason {
+  fn default() -> Self {
+    Self::Other
+  }
+}
+
+impl ToString for PriceOverrideReason {
+  fn to_string(&self) -> String {
+    match self {
+      PriceOverrideReason::Damaged => "DAMAGED".to_string(),
+      PriceOverrideReason::PriceMatch => "PRICE_MATCH".to_string(),
+      PriceOverrideReason::Other => "OTHER".to_string(),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PriceOverride {
+  pub original_price_net: u32,     // Price before override
+  pub original_price_gross: u32,   // Price before override
+  pub price_net: u32,              // Overridden price
+  pub price_gross: u32,            // Overridden price
+  pub reason: PriceOverrideReason, // Reason code
+  pub authorized_by: u32,          // UID
+  pub created_at: DateTime<Utc>,   // When price overridden
+}
+
+impl Default for PriceOverride {
+  fn default() -> Self {
+    Self {
+      original_price_net: 0,
+      original_price_gross: 0,
+      price_net: 0,
+      price_gross: 0,
+      reason: PriceOverrideReason::default(),
+      authorized_by: 0,
+      created_at: Utc::now(),
     }
   }
 }
@@ -257,6 +580,9 @@ pub struct UplInfoObject {
   pub procurement_net_price: u32,
   pub best_before: Option<DateTime<Utc>>,
   pub depreciated: bool,
+  pub price_override: Option<PriceOverride>, // Some(_) if retail prices are overridden
+  pub unit: Option<Unit>, // DerivedProduct amount unit, retail prices are per unit
+  pub expiry_authorized_by: Option<u32>, // Manager UID allowing an expired UPL
 }
 
 impl Default for UplInfoObject {
@@ -271,6 +597,45 @@ impl Default for UplInfoObject {
       procurement_net_price: 0,
       best_before: None,
       depreciated: false,
+      price_override: None,
+      unit: None,
+      expiry_authorized_by: None,
+    }
+  }
+}
+
+impl UplInfoObject {
+  /// Get UPL price net
+  /// DerivedProduct is priced by its amount
+  pub fn get_price_net(&self) -> u32 {
+    match self.kind {
+      UplKind::Sku { sku: _, piece } => self.retail_net_price * piece,
+      UplKind::DerivedProduct {
+        product_id: _,
+        amount,
+      } => unit::derived_price(self.unit, self.retail_net_price, amount),
+    }
+  }
+  /// Get UPL price gross
+  /// DerivedProduct is priced by its amount
+  pub fn get_price_gross(&self) -> u32 {
+    match self.kind {
+      UplKind::Sku { sku: _, piece } => self.retail_gross_price * piece,
+      UplKind::DerivedProduct {
+        product_id: _,
+        amount,
+      } => unit::derived_price(self.unit, self.retail_gross_price, amount),
+    }
+  }
+  /// Get piece
+  /// DerivedProduct measured by weight, volume or length is 1 piece
+  pub fn get_piece(&self) -> u32 {
+    match self.kind {
+      UplKind::Sku { sku: _, piece } => piece,
+      UplKind::DerivedProduct {
+        product_id: _,
+        amount,
+      } => unit::derived_piece(self.unit, amount),
     }
   }
 }
@@ -304,6 +669,27 @@ impl Default for Commitment {
   }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PromotionLine {
+  pub promotion_id: Uuid,  // Applied promotion ID
+  pub name: String,        // Promotion name
+  pub skus: Vec<u32>,      // Discounted SKUs
+  pub discount_net: u32,   // Discount net value
+  pub discount_gross: u32, // Discount gross value
+}
+
+impl Default for PromotionLine {
+  fn default() -> Self {
+    Self {
+      promotion_id: Uuid::default(),
+      name: String::default(),
+      skus: Vec::new(),
+      discount_net: 0,
+      discount_gross: 0,
+    }
+  }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub enum LoyaltyLevel {
   L1,
@@ -355,6 +741,7 @@ pub struct LoyaltyTransaction {
   pub loyalty_account_id: Uuid,
   pub transaction_id: Uuid,
   pub burned_points: i32,
+  pub reversed_transaction_id: Option<Uuid>, // Some(_) if it reverses a burn
   pub created_at: DateTime<Utc>,
 }
 
@@ -364,6 +751,7 @@ impl Default for LoyaltyTransaction {
       loyalty_account_id: Uuid::default(),
       transaction_id: Uuid::default(),
       burned_points: 0,
+      reversed_transaction_id: None,
       created_at: Utc::now(),
     }
   }
@@ -0,0 +1,170 @@
+// Price offers for customers
+// Built from a cart snapshot, can be converted back to a cart
+
+use crate::cart::{Cart, CartMethods};
+use crate::error::DomainError;
+use chrono::prelude::*;
+use packman::VecPackMember;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Quote {
+  pub id: Uuid,                   // Quote ID
+  pub quote_number: String,       // Human readable quote number
+  pub cart: Cart,                 // Cart as it was at the time of quoting
+  pub valid_till: DateTime<Utc>,  // Offered prices are valid till
+  pub converted_to: Option<Uuid>, // Some(cart_id) if converted to cart
+  pub created_by: u32,            // UID
+  pub created_at: DateTime<Utc>,  // When quote created
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SkuPrice {
+  pub sku: u32,
+  pub price_net: u32,
+  pub price_gross: u32,
+}
+
+impl Quote {
+  pub fn new(
+    cart: Cart,
+    quote_number: String,
+    valid_till: DateTime<Utc>,
+    created_by: u32,
+  ) -> Result<Self, DomainError> {
+    if cart.shopping_list.is_empty() {
+      return Err(DomainError::QuoteEmptyCart);
+    }
+    check_unique_upls(&cart)?;
+    if valid_till <= Utc::now() {
+      return Err(DomainError::QuoteValidTillInPast);
+    }
+    Ok(Self {
+      id: Uuid::new_v4(),
+      quote_number,
+      cart,
+      valid_till,
+      converted_to: None,
+      created_by,
+      created_at: Utc::now(),
+    })
+  }
+  /// Check if quote can be converted to cart
+  pub fn is_valid(&self) -> bool {
+    Utc::now() < self.valid_till && self.converted_to.is_none()
+  }
+  /// Re-create the shopping list as a new cart
+  /// If prices is Some(_), every SKU is priced by it,
+  /// otherwise the quoted prices are kept
+  /// UPLs are not reserved by quotes, so they need to be added again
+  /// Quote is not marked as converted, call set_converted
+  /// once the new cart is stored
+  pub fn to_cart(
+    &self,
+    owner_uid: u32,
+    store_id: Option<u32>,
+    prices: Option<Vec<SkuPrice>>,
+    created_by: u32,
+  ) -> Result<Cart, DomainError> {
+    if self.converted_to.is_some() {
+      return Err(DomainError::QuoteAlreadyConverted);
+    }
+    if Utc::now() >= self.valid_till {
+      return Err(DomainError::QuoteExpired);
+    }
+    check_unique_upls(&self.cart)?;
+
+    let mut cart = Cart::new(owner_uid, store_id, created_by);
+    cart.add_customer(self.cart.customer.clone());
+    cart.set_document(self.cart.document_kind.clone());
+    cart.set_payment(self.cart.payment_kind);
+
+    for i in &self.cart.shopping_list {
+      match &prices {
+        Some(prices) => {
+          let price = prices
+            .iter()
+            .find(|p| p.sku == i.sku)
+            .ok_or(DomainError::QuoteMissingPrice { sku: i.sku })?;
+          cart.add_sku(
+            i.sku,
+            i.piece,
+            i.name.to_string(),
+            i.vat,
+            price.price_net,
+            price.price_gross,
+          );
+        }
+        None => {
+          // Quoted price is the original one, if it was overridden
+          let (price_net, price_gross) = match &i.price_override {
+            Some(po) => (po.original_price_net, po.original_price_gross),
+            None => (i.unit_price_net, i.unit_price_gross),
+          };
+          cart.add_sku(
+            i.sku,
+            i.piece,
+            i.name.to_string(),
+            i.vat,
+            price_net,
+            price_gross,
+          );
+          // Keep price override for audit
+          if let Some(po) = &i.price_override {
+            cart.set_sku_price_override(
+              i.sku,
+              po.price_net,
+              po.price_gross,
+              po.reason.clone(),
+              po.authorized_by,
+            )?;
+          }
+        }
+      }
+    }
+
+    Ok(cart)
+  }
+  /// Mark quote as converted to the given cart
+  pub fn set_converted(&mut self, cart_id: Uuid) -> Result<&Self, DomainError> {
+    if self.converted_to.is_some() {
+      return Err(DomainError::QuoteAlreadyConverted);
+    }
+    self.converted_to = Some(cart_id);
+    Ok(self)
+  }
+}
+
+// Unique UPLs (depreciated ones and derived products) have no SKU
+// to re-create them from, and quotes do not reserve UPLs
+fn check_unique_upls(cart: &Cart) -> Result<(), DomainError> {
+  match cart.upls_unique.first() {
+    Some(upl) => Err(DomainError::QuoteUniqueUpl {
+      upl_id: upl.upl_id.to_string(),
+    }),
+    None => Ok(()),
+  }
+}
+
+impl Default for Quote {
+  fn default() -> Self {
+    Self {
+      id: Uuid::default(),
+      quote_number: String::default(),
+      cart: Cart::default(),
+      valid_till: Utc::now(),
+      converted_to: None,
+      created_by: 0,
+      created_at: Utc::now(),
+    }
+  }
+}
+
+impl VecPackMember for Quote {
+  type Out = Uuid;
+
+  fn get_id(&self) -> &Self::Out {
+    &self.id
+  }
+}
@@ -0,0 +1,269 @@
+// Partial return of a closed purchase
+// Returned UPLs, Refund payments
+
+use crate::error::DomainError;
+use crate::purchase::*;
+use crate::rounding::div_round;
+use chrono::prelude::*;
+use packman::VecPackMember;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Refund {
+  pub id: Uuid,                             // Refund ID
+  pub purchase_id: Uuid,                    // Related purchase ID
+  pub items: Vec<Item>,                     // Returned items
+  pub upl_info_objects: Vec<UplInfoObject>, // Returned UPL info objects
+  pub total_net: u32,                       // Total returned net value in HUF
+  pub total_vat: u32,                       // Total returned VAT
+  pub total_gross: u32,                     // Total returned gross value in HUF
+  pub profit_net: i32,                      // Net profit of the returned items
+  pub payments: Vec<Payment>,               // Refund payments
+  pub payable: i32,                         // Amount to pay back
+  pub balance: i32,                         // Refund payment balance
+  pub created_by: u32,                      // UID
+  pub created_at: DateTime<Utc>,            // When refund created
+}
+
+impl Refund {
+  /// Create new refund for the given UPLs of a purchase
+  pub fn new(
+    purchase: &Purchase,
+    upl_ids: Vec<String>,
+    payments: Vec<Payment>,
+    created_by: u32,
+  ) -> Result<Self, DomainError> {
+    if purchase.restored.is_some() {
+      return Err(DomainError::RefundOfRestored);
+    }
+
+    if upl_ids.is_empty() {
+      return Err(DomainError::RefundNoUpls);
+    }
+
+    let mut items: Vec<Item> = Vec::new();
+    let mut upls: Vec<UplInfoObject> = Vec::new();
+
+    for upl_id in &upl_ids {
+      // Check if UPL is already returned
+      if purchase.returned_upls.contains(upl_id) || upls.iter().any(|u| &u.upl_id == upl_id) {
+        return Err(DomainError::UplAlreadyReturned {
+          upl_id: upl_id.to_string(),
+        });
+      }
+      // Try to find UPL in purchase
+      let upl = purchase
+        .upl_info_objects
+        .iter()
+        .find(|u| &u.upl_id == upl_id)
+        .ok_or_else(|| DomainError::UplNotInPurchase {
+          upl_id: upl_id.to_string(),
+        })?;
+      items.push(upl_to_item(purchase, upl));
+      upls.push(upl.clone());
+    }
+
+    // Refund the discounted value the customer paid,
+    // promotion, commitment and loyalty discounts included
+    let discounted = purchase.get_discounted_items();
+    let mut returned = purchase.returned_upls.clone();
+    let mut total_net = 0;
+    let mut total_gross = 0;
+    for upl in &upls {
+      let (net, gross) = discounted_value(purchase, &discounted, &returned, upl);
+      total_net += net;
+      total_gross += gross;
+      returned.push(upl.upl_id.to_string());
+    }
+
+    // Cash rounding applies only to the part paid back in cash
+    let non_cash = payments
+      .iter()
+      .filter(|p| p.kind != PaymentKind::Cash)
+      .map(|p| p.amount)
+      .sum::<i32>();
+    let payable = match purchase.payment_kind {
+      PaymentKind::Cash => non_cash + crate::rounding::round_huf(total_gross as i32 - non_cash),
+      _ => total_gross as i32,
+    };
+
+    let profit_net = total_net as i32
+      - upls
+        .iter()
+        .map(|u| u.procurement_net_price as i32)
+        .sum::<i32>();
+
+    let balance = payable - payments.iter().map(|p| p.amount).sum::<i32>();
+
+    Ok(Self {
+      id: Uuid::new_v4(),
+      purchase_id: purchase.id,
+      items,
+      upl_info_objects: upls,
+      total_net,
+      total_vat: total_gross - total_net,
+      total_gross,
+      profit_net,
+      payments,
+      payable,
+      balance,
+      created_by,
+      created_at: Utc::now(),
+    })
+  }
+  /// Get returned UPL IDs
+  pub fn get_upl_ids(&self) -> Vec<String> {
+    self
+      .upl_info_objects
+      .iter()
+      .map(|u| u.upl_id.to_string())
+      .collect()
+  }
+}
+
+// Discounted value of a returned UPL
+// SKU UPLs get their pieces' part of the shopping list item,
+// so all the returns of an item add up to its discounted value
+fn discounted_value(
+  purchase: &Purchase,
+  discounted: &[(u32, u32)],
+  returned: &[String],
+  upl: &UplInfoObject,
+) -> (u32, u32) {
+  let is_unique = |u: &UplInfoObject| match u.kind {
+    UplKind::Sku { sku: _, piece: _ } => u.depreciated,
+    UplKind::DerivedProduct {
+      product_id: _,
+      amount: _,
+    } => true,
+  };
+  let item_index = match upl.kind {
+    UplKind::Sku { sku, piece: _ } if !upl.depreciated => {
+      purchase.items.iter().position(|i| match i.kind {
+        ItemKind::Sku => i.sku == sku && i.sku != 0,
+        _ => false,
+      })
+    }
+    // Unique UPLs are listed in the same order as their items
+    _ => purchase
+      .upl_info_objects
+      .iter()
+      .filter(|u| is_unique(u))
+      .position(|u| u.upl_id == upl.upl_id)
+      .and_then(|n| {
+        (0..purchase.items.len())
+          .filter(|k| !matches!(purchase.items[*k].kind, ItemKind::Sku))
+          .nth(n)
+      }),
+  };
+  let k = match item_index {
+    Some(k) => k,
+    None => return (upl.get_price_net(), upl.get_price_gross()),
+  };
+  match upl.kind {
+    UplKind::Sku { sku, piece } if !upl.depreciated && purchase.items[k].piece > 0 => {
+      let total_piece = purchase.items[k].piece as u64;
+      let before = purchase
+        .upl_info_objects
+        .iter()
+        .filter(|u| !is_unique(u) && returned.contains(&u.upl_id))
+        .map(|u| match u.kind {
+          UplKind::Sku { sku: s, piece } if s == sku => piece as u64,
+          _ => 0,
+        })
+        .sum::<u64>();
+      let after = before + piece as u64;
+      let part = |value: u32, pieces: u64| div_round(value as u64 * pieces, total_piece) as u32;
+      // Gross and VAT are split, so no part has more net than gross
+      let (net, gross) = discounted[k];
+      let vat = gross.saturating_sub(net);
+      let gross = part(gross, after) - part(gross, before);
+      let vat = part(vat, after) - part(vat, before);
+      (gross.saturating_sub(vat), gross)
+    }
+    _ => discounted[k],
+  }
+}
+
+// Create returned item from a purchase UPL
+// SKU UPLs are priced by the purchase shopping list
+fn upl_to_item(purchase: &Purchase, upl: &UplInfoObject) -> Item {
+  match upl.kind {
+    UplKind::Sku { sku, piece } => {
+      let list_item = purchase.items.iter().find(|i| match i.kind {
+        ItemKind::Sku => i.sku == sku && i.sku != 0,
+        _ => false,
+      });
+      let (price_net, price_gross, price_override) = match (list_item, upl.depreciated) {
+        (Some(i), false) => (
+          i.retail_price_net,
+          i.retail_price_gross,
+          i.price_override.clone(),
+        ),
+        _ => (upl.retail_net_price, upl.retail_gross_price, None),
+      };
+      Item {
+        kind: match upl.depreciated {
+          true => ItemKind::SkuDepreciated,
+          false => ItemKind::Sku,
+        },
+        sku,
+        product_id: 0,
+        name: upl.name.to_string(),
+        piece,
+        retail_price_net: price_net,
+        vat: upl.vat.to_string(),
+        retail_price_gross: price_gross,
+        total_retail_price_net: price_net * piece,
+        total_retail_price_gross: price_gross * piece,
+        price_override,
+        amount: piece,
+        unit: Some(Unit::Piece),
+      }
+    }
+    UplKind::DerivedProduct { product_id, amount } => Item {
+      kind: ItemKind::DerivedProduct,
+      sku: 0,
+      product_id,
+      name: upl.name.to_string(),
+      piece: upl.get_piece(),
+      retail_price_net: upl.retail_net_price,
+      vat: upl.vat.to_string(),
+      retail_price_gross: upl.retail_gross_price,
+      total_retail_price_net: upl.get_price_net(),
+      total_retail_price_gross: upl.get_price_gross(),
+      price_override: None,
+      amount,
+      unit: upl.unit,
+    },
+  }
+}
+
+impl Default for Refund {
+  fn default() -> Self {
+    Self {
+      id: Uuid::default(),
+      purchase_id: Uuid::default(),
+      items: Vec::new(),
+      upl_info_objects: Vec::new(),
+      total_net: 0,
+      total_vat: 0,
+      total_gross: 0,
+      profit_net: 0,
+      payments: Vec::new(),
+      payable: 0,
+      balance: 0,
+      created_by: 0,
+      created_at: Utc::now(),
+    }
+  }
+}
+
+impl VecPackMember for Refund {
+  type Out = Uuid;
+
+  fn get_id(&self) -> &Self::Out {
+    &self.id
+  }
+}
@@ -1,3 +1,47 @@
+// HUF rounding rules
+// Money is integer HUF everywhere, no floating point math
+
+/// Integer division rounded half up
+/// e.g. 12.5 -> 13, 12.49 -> 12
+/// Every derived HUF amount (VAT, net part, percentage) is rounded by this rule
+pub fn div_round(numerator: u64, denominator: u64) -> u64 {
+  (numerator + denominator / 2) / denominator
+}
+
+/// Percentage of an amount rounded half up
+pub fn percent_of(amount: u32, percentage: u32) -> u32 {
+  div_round(amount as u64 * percentage as u64, 100) as u32
+}
+
+/// Split an amount proportionally to the weights
+/// Shares are rounded down, the remainder goes one by one
+/// to the largest fractions, so the shares always add up to the amount
+/// Without any weight the first share gets the whole amount
+pub fn allocate(amount: u32, weights: &[u32]) -> Vec<u32> {
+  let total = weights.iter().map(|w| *w as u64).sum::<u64>();
+  let mut res = vec![0; weights.len()];
+  if total == 0 {
+    if let Some(first) = res.first_mut() {
+      *first = amount;
+    }
+    return res;
+  }
+  let mut fractions: Vec<(usize, u64)> = Vec::new();
+  for (i, w) in weights.iter().enumerate() {
+    let share = amount as u64 * *w as u64;
+    res[i] = (share / total) as u32;
+    fractions.push((i, share % total));
+  }
+  // Stable sort, equal fractions keep the weights order
+  fractions.sort_by_key(|f| std::cmp::Reverse(f.1));
+  let rest = amount - res.iter().sum::<u32>();
+  for (i, _) in fractions.into_iter().take(rest as usize) {
+    res[i] += 1;
+  }
+  res
+}
+
+/// Cash rounding to 0 or 5 HUF
 pub fn round_huf(n: i32) -> i32 {
   let a = n.abs();
   (match n < 0 {
@@ -0,0 +1,86 @@
+// Unit of measure of a DerivedProduct amount
+// Shared by cart and purchase UPLs
+
+use crate::error::DomainError;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Unit {
+  Piece,
+  Gram,
+  Milliliter,
+  Millimeter,
+}
+
+impl Unit {
+  pub fn from_str(str: &str) -> Result<Unit, DomainError> {
+    match str {
+      "PIECE" => Ok(Unit::Piece),
+      "GRAM" => Ok(Unit::Gram),
+      "MILLILITER" => Ok(Unit::Milliliter),
+      "MILLIMETER" => Ok(Unit::Millimeter),
+      _ => Err(DomainError::InvalidUnit),
+    }
+  }
+  /// Amount the unit price refers to
+  /// Piece is priced per piece, the rest per kg, l or m
+  pub fn price_base(&self) -> u32 {
+    match self {
+      Unit::Piece => 1,
+      Unit::Gram | Unit::Milliliter | Unit::Millimeter => 1000,
+    }
+  }
+  /// Price of the given amount by unit price
+  /// rounded to the nearest HUF
+  pub fn price_of(&self, unit_price: u32, amount: u32) -> u32 {
+    let base = self.price_base() as u64;
+    ((unit_price as u64 * amount as u64 + base / 2) / base) as u32
+  }
+}
+
+impl ToString for Unit {
+  fn to_string(&self) -> String {
+    match self {
+      Unit::Piece => "PIECE".to_string(),
+      Unit::Gram => "GRAM".to_string(),
+      Unit::Milliliter => "MILLILITER".to_string(),
+      Unit::Millimeter => "MILLIMETER".to_string(),
+    }
+  }
+}
+
+/// Parse an optional unit code
+/// Empty means no unit, as older clients do not send it
+pub fn parse(str: &str) -> Result<Option<Unit>, DomainError> {
+  match !str.is_empty() {
+    true => Ok(Some(Unit::from_str(str)?)),
+    false => Ok(None),
+  }
+}
+
+/// Unit code of an optional unit
+/// empty if there is no unit
+pub fn code(unit: Option<Unit>) -> String {
+  match unit {
+    Some(unit) => unit.to_string(),
+    None => String::default(),
+  }
+}
+
+/// Price of a DerivedProduct amount
+/// Without unit the retail price is the price of the whole UPL
+pub fn derived_price(unit: Option<Unit>, retail_price: u32, amount: u32) -> u32 {
+  match unit {
+    Some(unit) => unit.price_of(retail_price, amount),
+    None => retail_price,
+  }
+}
+
+/// Piece of a DerivedProduct amount
+/// Measured by weight, volume or length or without unit its 1 piece
+pub fn derived_piece(unit: Option<Unit>, amount: u32) -> u32 {
+  match unit {
+    Some(Unit::Piece) => amount,
+    _ => 1,
+  }
+}
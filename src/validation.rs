@@ -0,0 +1,61 @@
+// Cart close validation
+// Every blocking issue as structured data
+
+use crate::cart::PaymentKind;
+use crate::i18n::{Locale, Localize};
+
+#[derive(Clone)]
+pub enum CloseIssue {
+  Parked,
+  PreorderNotFulfilled,
+  MissingCustomer,
+  SkuUplMismatch {
+    sku: u32,
+    name: String,
+    list_piece: u32, // Piece on the shopping list
+    upl_piece: u32,  // Piece covered by UPLs
+  },
+  TotalsMismatch {
+    figure: String, // total_net, total_vat or total_gross
+    stored: u32,    // Stored cart figure
+    computed: u32,  // Figure computed from the items
+  },
+  CashNotRounded {
+    cash_total: i32,
+  },
+  TransferNeedsInvoice,
+  PaymentNegative {
+    payment_kind: PaymentKind,
+    total: i32, // Payments total of the method
+  },
+  PaymentOverpaid {
+    balance: i32,
+  },
+  PaymentUnbalanced {
+    payment_kind: PaymentKind,
+    balance: i32,
+  },
+}
+
+impl CloseIssue {
+  /// Issue code in SCREAMING_SNAKE_CASE
+  pub fn code(&self) -> String {
+    match self {
+      CloseIssue::Parked => "PARKED",
+      CloseIssue::PreorderNotFulfilled => "PREORDER_NOT_FULFILLED",
+      CloseIssue::MissingCustomer => "MISSING_CUSTOMER",
+      CloseIssue::SkuUplMismatch { .. } => "SKU_UPL_MISMATCH",
+      CloseIssue::TotalsMismatch { .. } => "TOTALS_MISMATCH",
+      CloseIssue::CashNotRounded { .. } => "CASH_NOT_ROUNDED",
+      CloseIssue::TransferNeedsInvoice => "TRANSFER_NEEDS_INVOICE",
+      CloseIssue::PaymentNegative { .. } => "PAYMENT_NEGATIVE",
+      CloseIssue::PaymentOverpaid { .. } => "PAYMENT_OVERPAID",
+      CloseIssue::PaymentUnbalanced { .. } => "PAYMENT_UNBALANCED",
+    }
+    .to_string()
+  }
+  /// Human readable message in the default locale
+  pub fn message(&self) -> String {
+    self.localize(Locale::default())
+  }
+}
@@ -0,0 +1,85 @@
+// Cart close validation
+// every blocking issue is reported, not only the first one
+
+use purchase_microservice::cart::{
+  Cart, CartMethods, Payment, PaymentKind, UplInfoObject, UplKind, VAT,
+};
+use purchase_microservice::error::DomainError;
+use purchase_microservice::validation::CloseIssue;
+
+fn upl(upl_id: &str, sku: u32) -> UplInfoObject {
+  UplInfoObject {
+    upl_id: upl_id.to_string(),
+    kind: UplKind::Sku { sku, piece: 1 },
+    name: "Test".to_string(),
+    retail_net_price: 1000,
+    vat: VAT::_27,
+    retail_gross_price: 1270,
+    ..UplInfoObject::default()
+  }
+}
+
+#[test]
+fn every_mismatched_sku_is_listed() {
+  let mut cart = Cart::new(1, None, 1);
+  cart.set_payment(PaymentKind::Card);
+  for sku in 1..=3 {
+    cart.add_upl(upl(&sku.to_string(), sku)).unwrap();
+  }
+  cart.set_sku_piece(1, 3).unwrap();
+  cart.set_sku_piece(2, 2).unwrap();
+
+  let mismatches = cart
+    .validate_close()
+    .into_iter()
+    .filter_map(|i| match i {
+      CloseIssue::SkuUplMismatch {
+        sku,
+        list_piece,
+        upl_piece,
+        ..
+      } => Some((sku, list_piece, upl_piece)),
+      _ => None,
+    })
+    .collect::<Vec<(u32, u32, u32)>>();
+  assert_eq!(mismatches, vec![(1, 3, 1), (2, 2, 1)]);
+}
+
+#[test]
+fn close_is_refused_by_the_first_issue() {
+  let mut cart = Cart::new(1, None, 1);
+  cart.set_payment(PaymentKind::Card);
+  cart.add_sku(1, 1, "First".to_string(), VAT::_27, 1000, 1270);
+  cart.add_payment(Payment {
+    payment_id: "1".to_string(),
+    amount: 1000,
+    kind: PaymentKind::Card,
+    ..Payment::default()
+  });
+
+  let codes = cart
+    .validate_close()
+    .iter()
+    .map(|i| i.code())
+    .collect::<Vec<String>>();
+  assert_eq!(codes, vec!["SKU_UPL_MISMATCH", "PAYMENT_UNBALANCED"]);
+  assert!(matches!(
+    cart.close_cart(),
+    Err(DomainError::NotClosable(CloseIssue::SkuUplMismatch {
+      sku: 1,
+      ..
+    }))
+  ));
+
+  // UPL adds its piece to the list
+  cart.remove_sku(1).unwrap();
+  cart.add_upl(upl("1", 1)).unwrap();
+  cart.add_payment(Payment {
+    payment_id: "2".to_string(),
+    amount: 270,
+    kind: PaymentKind::Card,
+    ..Payment::default()
+  });
+  assert!(cart.validate_close().is_empty());
+  assert!(cart.close_cart().is_ok());
+}
@@ -0,0 +1,54 @@
+// DerivedProduct pricing
+// retail prices are per unit, the UPL is priced by its amount
+
+use purchase_microservice::cart::{Cart, CartMethods, UplInfoObject, UplKind, VAT};
+use purchase_microservice::purchase::Purchase;
+use purchase_microservice::unit::Unit;
+
+fn derived(amount: u32, unit: Option<Unit>) -> UplInfoObject {
+  UplInfoObject {
+    upl_id: "1".to_string(),
+    kind: UplKind::DerivedProduct {
+      product_id: 7,
+      amount,
+    },
+    name: "Derived".to_string(),
+    retail_net_price: 4000,
+    vat: VAT::_27,
+    retail_gross_price: 5080,
+    unit,
+    ..UplInfoObject::default()
+  }
+}
+
+#[test]
+fn price_is_amount_times_unit_price() {
+  // 250 g of 4000 HUF / kg
+  let upl = derived(250, Some(Unit::Gram));
+  assert_eq!(upl.get_price_net(), 1000);
+  assert_eq!(upl.get_price_gross(), 1270);
+  assert_eq!(upl.get_piece(), 1);
+
+  // Rounded to the nearest HUF
+  assert_eq!(derived(333, Some(Unit::Milliliter)).get_price_net(), 1332);
+
+  let upl = derived(3, Some(Unit::Piece));
+  assert_eq!(upl.get_price_gross(), 15240);
+  assert_eq!(upl.get_piece(), 3);
+
+  // Without unit the retail price is the price of the UPL
+  assert_eq!(derived(250, None).get_price_gross(), 5080);
+}
+
+#[test]
+fn cart_and_purchase_use_the_amount_price() {
+  let mut cart = Cart::new(1, None, 1);
+  cart.add_upl(derived(250, Some(Unit::Gram))).unwrap();
+  assert_eq!(cart.total_net, 1000);
+  assert_eq!(cart.total_gross, 1270);
+
+  let purchase: Purchase = cart.into();
+  assert_eq!(purchase.items[0].amount, 250);
+  assert_eq!(purchase.items[0].total_retail_price_gross, 1270);
+  assert_eq!(purchase.total_gross, 1270);
+}
@@ -0,0 +1,67 @@
+// Domain errors on the API
+// code and params are sent as status metadata
+
+use purchase_microservice::cart::{Cart, CartMethods, UplInfoObject, UplKind, VAT};
+use purchase_microservice::error::DomainError;
+use purchase_microservice::i18n::Locale;
+use purchase_microservice::prelude::ServiceError;
+use tonic::Code;
+
+fn upl() -> UplInfoObject {
+  UplInfoObject {
+    upl_id: "123".to_string(),
+    kind: UplKind::Sku { sku: 1, piece: 1 },
+    name: "Test".to_string(),
+    retail_net_price: 1000,
+    vat: VAT::_27,
+    retail_gross_price: 1270,
+    ..UplInfoObject::default()
+  }
+}
+
+#[test]
+fn status_carries_the_error_code_and_params() {
+  let mut cart = Cart::new(1, None, 1);
+  cart.add_upl(upl()).unwrap();
+  let error = match cart.add_upl(upl()) {
+    Err(e) => e,
+    Ok(_) => panic!("UPL must not be added twice"),
+  };
+  assert_eq!(error.code(), "UPL_ALREADY_IN_CART");
+
+  let status = ServiceError::from(error).into_status(Locale::En);
+  assert_eq!(status.code(), Code::InvalidArgument);
+  assert_eq!(
+    status.message(),
+    "The requested UPL is already in the cart!"
+  );
+  let metadata = status.metadata();
+  assert_eq!(
+    metadata.get("error-code").unwrap().to_str().unwrap(),
+    "UPL_ALREADY_IN_CART"
+  );
+  assert_eq!(
+    metadata.get("error-upl-id").unwrap().to_str().unwrap(),
+    "123"
+  );
+}
+
+#[test]
+fn param_keys_are_kebab_case() {
+  let error = DomainError::DateCompletionTooLate { max_days: 8 };
+  assert_eq!(
+    error.params(),
+    vec![("max_days".to_string(), "8".to_string())]
+  );
+
+  let status = ServiceError::from(error).into_status(Locale::Hu);
+  assert_eq!(
+    status
+      .metadata()
+      .get("error-max-days")
+      .unwrap()
+      .to_str()
+      .unwrap(),
+    "8"
+  );
+}
@@ -0,0 +1,90 @@
+// Audit trail of the cart
+// every recorded modification keeps the totals around it
+
+use purchase_microservice::cart::{
+  Cart, CartMethods, Payment, PaymentKind, UplInfoObject, UplKind, VAT,
+};
+use purchase_microservice::event::CartEventKind;
+use purchase_microservice::proto::purchase::CartEventObject;
+use purchase_microservice::purchase::Purchase;
+
+fn recorded_cart() -> Cart {
+  let mut cart = Cart::new(1, None, 1);
+  cart.set_payment(PaymentKind::Card);
+  cart
+    .record(
+      3,
+      CartEventKind::AddUpl {
+        upl_id: "1".to_string(),
+      },
+      |c| {
+        c.add_upl(UplInfoObject {
+          upl_id: "1".to_string(),
+          kind: UplKind::Sku { sku: 1, piece: 1 },
+          name: "Test".to_string(),
+          retail_net_price: 1000,
+          vat: VAT::_27,
+          retail_gross_price: 1270,
+          ..UplInfoObject::default()
+        })
+        .map(|_| ())
+      },
+    )
+    .unwrap();
+  cart
+    .record(
+      4,
+      CartEventKind::AddPayment {
+        payment_id: "1".to_string(),
+        amount: 1270,
+      },
+      |c| {
+        c.add_payment(Payment {
+          payment_id: "1".to_string(),
+          amount: 1270,
+          kind: PaymentKind::Card,
+          ..Payment::default()
+        });
+        Ok(())
+      },
+    )
+    .unwrap();
+  cart
+}
+
+#[test]
+fn events_carry_the_totals_before_and_after() {
+  let cart = recorded_cart();
+  assert_eq!(cart.events.len(), 2);
+
+  let add_upl = &cart.events[0];
+  assert_eq!(add_upl.created_by, 3);
+  assert_eq!(add_upl.before.total_gross, 0);
+  assert_eq!(add_upl.after.total_net, 1000);
+  assert_eq!(add_upl.after.total_gross, 1270);
+  assert_eq!(add_upl.after.balance, 1270);
+
+  let add_payment = &cart.events[1];
+  assert_eq!(add_payment.created_by, 4);
+  assert_eq!(add_payment.before.balance, 1270);
+  assert_eq!(add_payment.after.payable, 1270);
+  assert_eq!(add_payment.after.balance, 0);
+
+  let object: CartEventObject = add_payment.clone().into();
+  assert_eq!(object.kind, "ADD_PAYMENT");
+  assert_eq!(object.balance_before, 1270);
+  assert_eq!(object.balance_after, 0);
+}
+
+#[test]
+fn events_are_copied_onto_the_purchase() {
+  let cart = recorded_cart();
+  let ids = cart.events.iter().map(|e| e.id).collect::<Vec<_>>();
+
+  let purchase: Purchase = cart.into();
+  assert_eq!(
+    purchase.events.iter().map(|e| e.id).collect::<Vec<_>>(),
+    ids
+  );
+  assert_eq!(purchase.events[1].after.balance, 0);
+}
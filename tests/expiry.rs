@@ -0,0 +1,57 @@
+// Expired UPLs
+// sold only with manager approval, kept by restore
+
+use chrono::{DateTime, Duration, Utc};
+use purchase_microservice::cart::{Cart, CartMethods, UplInfoObject, UplKind, VAT};
+use purchase_microservice::purchase::Purchase;
+use std::convert::TryFrom;
+
+fn upl(best_before: DateTime<Utc>, expiry_authorized_by: Option<u32>) -> UplInfoObject {
+  UplInfoObject {
+    upl_id: "1".to_string(),
+    kind: UplKind::Sku { sku: 1, piece: 1 },
+    name: "Test".to_string(),
+    retail_net_price: 1000,
+    vat: VAT::_27,
+    retail_gross_price: 1270,
+    best_before: Some(best_before),
+    expiry_authorized_by,
+    ..UplInfoObject::default()
+  }
+}
+
+#[test]
+fn expired_upl_needs_approval() {
+  let mut cart = Cart::new(1, None, 1);
+  let expired = Utc::now() - Duration::days(1);
+  assert!(cart.add_upl(upl(expired, None)).is_err());
+  assert!(cart.add_upl(upl(expired, Some(7))).is_ok());
+}
+
+#[test]
+fn restore_keeps_the_approval() {
+  let mut cart = Cart::new(1, None, 1);
+  cart
+    .add_upl(upl(Utc::now() - Duration::days(1), Some(7)))
+    .unwrap();
+  let purchase: Purchase = cart.into();
+  assert_eq!(purchase.upl_info_objects[0].expiry_authorized_by, Some(7));
+
+  let restored = Cart::try_from(purchase).unwrap();
+  assert_eq!(restored.upls_sku[0].expiry_authorized_by, Some(7));
+}
+
+#[test]
+fn restore_keeps_upls_expired_since_the_sale() {
+  let mut cart = Cart::new(1, None, 1);
+  cart
+    .add_upl(upl(Utc::now() + Duration::days(1), None))
+    .unwrap();
+  let mut purchase: Purchase = cart.into();
+  // Best before passed since the sale
+  purchase.upl_info_objects[0].best_before = Some(Utc::now() - Duration::days(1));
+
+  let restored = Cart::try_from(purchase).unwrap();
+  assert_eq!(restored.upls_sku.len(), 1);
+  assert_eq!(restored.upls_sku[0].expiry_authorized_by, None);
+}
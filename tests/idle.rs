@@ -0,0 +1,39 @@
+// Idle carts
+// idleness is measured from the last recorded event
+
+use chrono::{Duration, Utc};
+use purchase_microservice::abandoned::{AbandonedCart, RemoveReason, RemovedBy};
+use purchase_microservice::cart::{Cart, CartMethods};
+use purchase_microservice::event::CartEventKind;
+
+#[test]
+fn cart_is_idle_by_its_last_event() {
+  let mut cart = Cart::new(1, None, 1);
+  cart.created_at = Utc::now() - Duration::hours(2);
+  assert!(cart.is_idle(Duration::hours(1)));
+
+  cart
+    .record(1, CartEventKind::SetOwner { owner_uid: 2 }, |c| {
+      c.set_owner(2);
+      Ok(())
+    })
+    .unwrap();
+  // Unrecorded changes are not activity
+  cart.updated_at = Utc::now() - Duration::hours(2);
+  assert!(!cart.is_idle(Duration::hours(1)));
+
+  cart.events.last_mut().unwrap().created_at = Utc::now() - Duration::hours(2);
+  cart.updated_at = Utc::now();
+  assert!(cart.is_idle(Duration::hours(1)));
+}
+
+#[test]
+fn expired_cart_is_removed_by_the_system() {
+  let abandoned = AbandonedCart::expired(Cart::new(1, None, 1));
+  assert_eq!(abandoned.reason, RemoveReason::Expired);
+  assert_eq!(abandoned.removed_by, RemovedBy::System);
+
+  let abandoned = AbandonedCart::new(Cart::new(1, None, 1), "Test".to_string(), 2);
+  assert_eq!(abandoned.reason, RemoveReason::Manual("Test".to_string()));
+  assert_eq!(abandoned.removed_by, RemovedBy::User(2));
+}
@@ -0,0 +1,51 @@
+// Invoice dates
+// explicitly set duedate is kept by later cart changes
+#![allow(deprecated)]
+
+use chrono::{Duration, Utc};
+use purchase_microservice::cart::{Cart, CartMethods, Customer, DateCompletionLimits, PaymentKind};
+
+#[test]
+fn explicit_duedate_is_kept() {
+  let mut cart = Cart::new(1, None, 1);
+  cart.set_payment(PaymentKind::Transfer);
+  let today = Utc::today().and_hms(0, 0, 0);
+  let duedate = today + Duration::days(30);
+  cart
+    .set_invoice_dates(today, duedate, DateCompletionLimits::default())
+    .unwrap();
+
+  cart.add_customer(Some(Customer {
+    id: 1,
+    payment_term: 15,
+    ..Customer::default()
+  }));
+  assert_eq!(cart.payment_duedate, duedate);
+  cart.set_payment(PaymentKind::Transfer);
+  assert_eq!(cart.payment_duedate, duedate);
+}
+
+#[test]
+fn duedate_is_recalculated_without_transfer() {
+  let mut cart = Cart::new(1, None, 1);
+  cart.set_payment(PaymentKind::Transfer);
+  let today = Utc::today().and_hms(0, 0, 0);
+  cart
+    .set_invoice_dates(
+      today,
+      today + Duration::days(30),
+      DateCompletionLimits::default(),
+    )
+    .unwrap();
+
+  // Leaving transfer drops the explicit duedate
+  cart.set_payment(PaymentKind::Cash);
+  assert_eq!(cart.payment_duedate, today);
+  cart.add_customer(Some(Customer {
+    id: 1,
+    payment_term: 15,
+    ..Customer::default()
+  }));
+  cart.set_payment(PaymentKind::Transfer);
+  assert_eq!(cart.payment_duedate, today + Duration::days(15));
+}
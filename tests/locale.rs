@@ -0,0 +1,36 @@
+// Client locale
+// chosen by the first accept-language tag, Hungarian by default
+
+use purchase_microservice::error::DomainError;
+use purchase_microservice::i18n::{Locale, Localize};
+use tonic::metadata::MetadataMap;
+
+fn metadata(accept_language: &str) -> MetadataMap {
+  let mut metadata = MetadataMap::new();
+  metadata.insert("accept-language", accept_language.parse().unwrap());
+  metadata
+}
+
+#[test]
+fn locale_is_chosen_by_accept_language() {
+  assert!(Locale::from_metadata(&metadata("en-US,en;q=0.9")) == Locale::En);
+  assert!(Locale::from_metadata(&metadata("EN")) == Locale::En);
+  assert!(Locale::from_metadata(&metadata("hu-HU,en;q=0.8")) == Locale::Hu);
+  assert!(Locale::from_metadata(&metadata("de-DE")) == Locale::Hu);
+  assert!(Locale::from_metadata(&MetadataMap::new()) == Locale::Hu);
+}
+
+#[test]
+fn errors_are_localized() {
+  let error = DomainError::UplAlreadyInCart {
+    upl_id: "1".to_string(),
+  };
+  assert_eq!(
+    error.localize(Locale::from_metadata(&metadata("en"))),
+    "The requested UPL is already in the cart!"
+  );
+  assert_eq!(
+    error.localize(Locale::from_metadata(&metadata("hu"))),
+    "A kért UPL már a kosárban van!"
+  );
+}
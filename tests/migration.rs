@@ -0,0 +1,195 @@
+// Migration of the baseline records
+// stored carts and purchases are loaded in their old shape
+// and every new field gets its default
+
+use packman::*;
+use purchase_microservice::cart::{self, Cart, CartMethods, UplKind, VAT};
+use purchase_microservice::migration::cart::{
+  CartOld, CustomerOld, ListItemOld, PaymentOld, UplInfoObjectOld,
+};
+use purchase_microservice::migration::purchase::{self as purchase_old, ItemOld, PurchaseOld};
+use purchase_microservice::purchase::{self, Purchase};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+// Store the carts as the baseline did
+// then load them back like the migration binary
+fn reload_carts(carts: Vec<CartOld>) -> Vec<CartOld> {
+  let path = std::env::temp_dir().join(format!("carts-{}", Uuid::new_v4()));
+  {
+    let mut pack: VecPack<CartOld> = VecPack::load_or_init(PathBuf::from(&path)).unwrap();
+    for cart in carts {
+      pack.insert(cart).unwrap();
+    }
+  }
+  let pack: VecPack<CartOld> = VecPack::load_or_init(PathBuf::from(&path)).unwrap();
+  let res = pack.iter().map(|c| c.unpack().clone()).collect();
+  std::fs::remove_dir_all(&path).ok();
+  res
+}
+
+fn reload_purchases(purchases: Vec<PurchaseOld>) -> Vec<PurchaseOld> {
+  let path = std::env::temp_dir().join(format!("purchases-{}", Uuid::new_v4()));
+  {
+    let mut pack: VecPack<PurchaseOld> = VecPack::load_or_init(PathBuf::from(&path)).unwrap();
+    for purchase in purchases {
+      pack.insert(purchase).unwrap();
+    }
+  }
+  let pack: VecPack<PurchaseOld> = VecPack::load_or_init(PathBuf::from(&path)).unwrap();
+  let res = pack.iter().map(|p| p.unpack().clone()).collect();
+  std::fs::remove_dir_all(&path).ok();
+  res
+}
+
+#[test]
+fn baseline_cart_is_migrated() {
+  let old = CartOld {
+    id: Uuid::new_v4(),
+    customer: Some(CustomerOld {
+      id: 1,
+      name: "Test".to_string(),
+      ..CustomerOld::default()
+    }),
+    shopping_list: vec![ListItemOld {
+      sku: 1,
+      name: "Test".to_string(),
+      piece: 2,
+      vat: VAT::_27,
+      unit_price_net: 1000,
+      unit_price_vat: 270,
+      unit_price_gross: 1270,
+      total_price_net: 2000,
+      total_price_vat: 540,
+      total_price_gross: 2540,
+    }],
+    upls_sku: vec![UplInfoObjectOld {
+      upl_id: "1".to_string(),
+      kind: UplKind::Sku { sku: 1, piece: 2 },
+      name: "Test".to_string(),
+      retail_net_price: 1000,
+      vat: VAT::_27,
+      retail_gross_price: 1270,
+      ..UplInfoObjectOld::default()
+    }],
+    total_net: 2000,
+    total_vat: 540,
+    total_gross: 2540,
+    payment_kind: cart::PaymentKind::Card,
+    payments: vec![PaymentOld {
+      payment_id: "1".to_string(),
+      amount: 2540,
+    }],
+    payable: 2540,
+    ..CartOld::default()
+  };
+
+  let carts = reload_carts(vec![old.clone()]);
+  let cart: Cart = carts[0].clone().into();
+  assert_eq!(cart.id, old.id);
+  assert_eq!(cart.customer.as_ref().unwrap().payment_term, 0);
+  assert!(cart.shopping_list[0].price_override.is_none());
+  assert!(cart.upls_sku[0].unit.is_none());
+  assert!(cart.upls_sku[0].expiry_authorized_by.is_none());
+  assert!(cart.payments[0].kind == cart::PaymentKind::Card);
+  assert!(cart.payments[0].reversal.is_none());
+  assert!(!cart.payment_duedate_explicit);
+  assert_eq!(cart.version, 1);
+  assert_eq!(cart.vat_totals.len(), 1);
+  assert_eq!(cart.vat_totals[0].total_gross, 2540);
+  assert_eq!(cart.get_upl_piece(1), 2);
+}
+
+#[test]
+fn baseline_purchase_is_migrated() {
+  let old = PurchaseOld {
+    id: Uuid::new_v4(),
+    customer: Some(purchase_old::CustomerOld {
+      id: 1,
+      name: "Test".to_string(),
+      ..purchase_old::CustomerOld::default()
+    }),
+    items: vec![
+      ItemOld {
+        kind: purchase::ItemKind::Sku,
+        name: "Test".to_string(),
+        piece: 2,
+        retail_price_net: 1000,
+        vat: "27".to_string(),
+        retail_price_gross: 1270,
+        total_retail_price_net: 2000,
+        total_retail_price_gross: 2540,
+        ..ItemOld::default()
+      },
+      ItemOld {
+        kind: purchase::ItemKind::DerivedProduct,
+        product_id: 7,
+        name: "Derived".to_string(),
+        piece: 1,
+        retail_price_net: 500,
+        vat: "5".to_string(),
+        retail_price_gross: 525,
+        total_retail_price_net: 500,
+        total_retail_price_gross: 525,
+      },
+    ],
+    upl_info_objects: vec![
+      purchase_old::UplInfoObjectOld {
+        upl_id: "1".to_string(),
+        kind: purchase::UplKind::Sku { sku: 1, piece: 2 },
+        name: "Test".to_string(),
+        retail_net_price: 1000,
+        vat: "27".to_string(),
+        retail_gross_price: 1270,
+        ..purchase_old::UplInfoObjectOld::default()
+      },
+      purchase_old::UplInfoObjectOld {
+        upl_id: "2".to_string(),
+        kind: purchase::UplKind::DerivedProduct {
+          product_id: 7,
+          amount: 250,
+        },
+        name: "Derived".to_string(),
+        retail_net_price: 500,
+        vat: "5".to_string(),
+        retail_gross_price: 525,
+        depreciated: true,
+        ..purchase_old::UplInfoObjectOld::default()
+      },
+    ],
+    total_net: 2500,
+    total_vat: 565,
+    total_gross: 3065,
+    payment_kind: purchase::PaymentKind::Cash,
+    payments: vec![purchase_old::PaymentOld {
+      payment_id: "1".to_string(),
+      amount: 3065,
+    }],
+    payable: 3065,
+    invoice: Some("INV-1".to_string()),
+    ..PurchaseOld::default()
+  };
+
+  let purchases = reload_purchases(vec![old.clone()]);
+  let purchase: Purchase = purchases[0].clone().into();
+  assert_eq!(purchase.id, old.id);
+  assert_eq!(purchase.customer.as_ref().unwrap().payment_term, 0);
+  assert_eq!(purchase.invoice, Some("INV-1".to_string()));
+  assert_eq!(purchase.items[0].sku, 1);
+  assert_eq!(purchase.items[0].amount, 2);
+  assert!(purchase.items[0].unit == Some(purchase::Unit::Piece));
+  assert_eq!(purchase.items[1].sku, 0);
+  assert_eq!(purchase.items[1].amount, 250);
+  assert!(purchase.items[1].unit.is_none());
+  assert!(purchase.upl_info_objects[1].expiry_authorized_by.is_none());
+  assert!(purchase.payments[0].kind == purchase::PaymentKind::Cash);
+  assert_eq!(purchase.vat_totals.len(), 2);
+  assert_eq!(
+    purchase
+      .vat_totals
+      .iter()
+      .map(|t| t.total_gross)
+      .sum::<u32>(),
+    purchase.total_gross
+  );
+}
@@ -0,0 +1,59 @@
+// Parked carts
+// the resuming shop assistant takes the cart over
+
+use purchase_microservice::cart::{Cart, CartMethods};
+use purchase_microservice::error::DomainError;
+use purchase_microservice::event::CartEventKind;
+use purchase_microservice::validation::CloseIssue;
+
+#[test]
+fn resume_transfers_ownership() {
+  let mut cart = Cart::new(1, None, 1);
+  cart
+    .record(
+      1,
+      CartEventKind::Park {
+        label: "Red coat".to_string(),
+      },
+      |c| c.park(" Red coat ".to_string(), 1).map(|_| ()),
+    )
+    .unwrap();
+  let parked = cart.parked.as_ref().unwrap();
+  assert_eq!(parked.label, "Red coat");
+  assert_eq!(parked.parked_by, 1);
+  assert_eq!(cart.owner_uid, 1);
+
+  cart
+    .record(2, CartEventKind::Resume, |c| c.resume(2).map(|_| ()))
+    .unwrap();
+  assert!(cart.parked.is_none());
+  assert_eq!(cart.owner_uid, 2);
+  assert_eq!(cart.events.last().unwrap().created_by, 2);
+}
+
+#[test]
+fn parked_cart_is_not_closable() {
+  let mut cart = Cart::new(1, None, 1);
+  cart.park("Test".to_string(), 1).unwrap();
+  assert!(matches!(
+    cart.park("Test".to_string(), 1),
+    Err(DomainError::AlreadyParked)
+  ));
+  assert!(matches!(
+    cart.close_cart(),
+    Err(DomainError::NotClosable(CloseIssue::Parked))
+  ));
+
+  cart.resume(2).unwrap();
+  assert!(matches!(cart.resume(2), Err(DomainError::NotParked)));
+}
+
+#[test]
+fn park_needs_a_label() {
+  let mut cart = Cart::new(1, None, 1);
+  assert!(matches!(
+    cart.park("  ".to_string(), 1),
+    Err(DomainError::ParkLabelRequired)
+  ));
+  assert!(cart.parked.is_none());
+}
@@ -0,0 +1,55 @@
+// Pre-order fulfilment
+// arriving UPLs cover the ordered pieces
+
+use chrono::{Duration, Utc};
+use purchase_microservice::cart::{
+  Cart, CartMethods, Customer, Payment, UplInfoObject, UplKind, VAT,
+};
+
+fn upl(upl_id: &str, piece: u32) -> UplInfoObject {
+  UplInfoObject {
+    upl_id: upl_id.to_string(),
+    kind: UplKind::Sku { sku: 1, piece },
+    name: "Test".to_string(),
+    retail_net_price: 1000,
+    vat: VAT::_27,
+    retail_gross_price: 1270,
+    ..UplInfoObject::default()
+  }
+}
+
+fn preorder() -> Cart {
+  let mut cart = Cart::new(1, None, 1);
+  cart.add_customer(Some(Customer {
+    id: 1,
+    ..Customer::default()
+  }));
+  cart.add_sku(1, 3, "Test".to_string(), VAT::_27, 1000, 1270);
+  cart
+    .set_preorder(Utc::now() + Duration::days(7), 1000, 1)
+    .unwrap();
+  cart.add_payment(Payment {
+    payment_id: "1".to_string(),
+    amount: 1000,
+    ..Payment::default()
+  });
+  cart
+}
+
+#[test]
+fn upls_cover_the_ordered_pieces() {
+  let mut cart = preorder();
+  cart.add_upl(upl("1", 1)).unwrap();
+  cart.add_upl(upl("2", 2)).unwrap();
+  assert_eq!(cart.shopping_list[0].piece, 3);
+  assert_eq!(cart.total_gross, 3810);
+  assert!(cart.add_upl(upl("3", 1)).is_err());
+
+  cart.fulfil_preorder(1).unwrap();
+  cart.add_payment(Payment {
+    payment_id: "2".to_string(),
+    amount: 2810,
+    ..Payment::default()
+  });
+  assert!(cart.validate_close().is_empty());
+}
@@ -0,0 +1,75 @@
+// Promotion snapshot semantics
+// Carts evaluate their own snapshot of the rules,
+// restored carts the rules of their sale
+
+use chrono::{Duration, Utc};
+use purchase_microservice::cart::{Cart, CartMethods, VAT};
+use purchase_microservice::promotion::{Promotion, PromotionKind};
+use purchase_microservice::purchase::Purchase;
+use std::convert::TryFrom;
+use uuid::Uuid;
+
+fn buy_3_pay_2() -> Promotion {
+  Promotion {
+    id: Uuid::new_v4(),
+    name: "3 = 2".to_string(),
+    kind: PromotionKind::BuyXPayY {
+      sku: 1,
+      buy: 3,
+      pay: 2,
+    },
+    valid_from: Utc::now() - Duration::hours(2),
+    valid_till: Utc::now() + Duration::hours(1),
+    ..Promotion::default()
+  }
+}
+
+fn cart_with(promotion: &Promotion) -> Cart {
+  let mut cart = Cart::new(1, None, 1);
+  cart.set_promotions(vec![promotion.clone()]);
+  cart.add_sku(1, 3, "Test".to_string(), VAT::_27, 1000, 1270);
+  cart
+}
+
+#[test]
+fn removed_promotion_is_not_applied_anymore() {
+  let promotion = buy_3_pay_2();
+  let mut cart = cart_with(&promotion);
+  assert_eq!(cart.promotion_lines.len(), 1);
+  assert_eq!(cart.total_gross, 2540);
+
+  cart.remove_promotion(promotion.id);
+  assert!(cart.promotion_lines.is_empty());
+  assert_eq!(cart.total_gross, 3810);
+}
+
+#[test]
+fn new_promotion_applies_after_refresh_only() {
+  let mut cart = Cart::new(1, None, 1);
+  cart.add_sku(1, 3, "Test".to_string(), VAT::_27, 1000, 1270);
+  assert!(cart.promotion_lines.is_empty());
+
+  cart.set_promotions(vec![buy_3_pay_2()]);
+  assert_eq!(cart.promotion_lines.len(), 1);
+}
+
+#[test]
+fn restored_cart_keeps_the_promotions_of_the_sale() {
+  let promotion = buy_3_pay_2();
+  let cart = cart_with(&promotion);
+  let mut purchase: Purchase = cart.into();
+  assert_eq!(purchase.promotions.len(), 1);
+
+  // Sold an hour ago, the promotion has expired since
+  purchase.promotion_time = Utc::now() - Duration::hours(1);
+  purchase.promotions[0].valid_till = Utc::now() - Duration::minutes(30);
+
+  let mut restored = Cart::try_from(purchase.clone()).unwrap();
+  assert_eq!(restored.promotion_lines.len(), 1);
+  assert_eq!(restored.total_gross, purchase.total_gross);
+
+  // Deleting the promotion does not change the restored sale
+  restored.remove_promotion(promotion.id);
+  assert_eq!(restored.promotion_lines.len(), 1);
+  assert_eq!(restored.total_gross, purchase.total_gross);
+}
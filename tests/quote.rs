@@ -0,0 +1,42 @@
+// Quote conversion
+
+use chrono::{Duration, Utc};
+use purchase_microservice::cart::{Cart, CartMethods, UplInfoObject, UplKind, VAT};
+use purchase_microservice::quote::Quote;
+
+fn cart() -> Cart {
+  let mut cart = Cart::new(1, None, 1);
+  cart.add_sku(1, 2, "Test".to_string(), VAT::_27, 1000, 1270);
+  cart
+}
+
+#[test]
+fn quote_is_converted_only_when_marked() {
+  let mut quote = Quote::new(cart(), "1".to_string(), Utc::now() + Duration::days(1), 1).unwrap();
+  let new_cart = quote.to_cart(1, None, None, 1).unwrap();
+  assert!(quote.is_valid());
+  assert_eq!(new_cart.shopping_list[0].piece, 2);
+
+  quote.set_converted(new_cart.id).unwrap();
+  assert!(!quote.is_valid());
+  assert!(quote.to_cart(1, None, None, 1).is_err());
+  assert!(quote.set_converted(new_cart.id).is_err());
+}
+
+#[test]
+fn quote_cannot_contain_unique_upls() {
+  let mut cart = cart();
+  cart
+    .add_upl(UplInfoObject {
+      upl_id: "1".to_string(),
+      kind: UplKind::Sku { sku: 2, piece: 1 },
+      name: "Depreciated".to_string(),
+      retail_net_price: 500,
+      vat: VAT::_27,
+      retail_gross_price: 635,
+      depreciated: true,
+      ..UplInfoObject::default()
+    })
+    .unwrap();
+  assert!(Quote::new(cart, "1".to_string(), Utc::now() + Duration::days(1), 1).is_err());
+}
@@ -0,0 +1,61 @@
+// Property tests of the refund amounts
+// returning every UPL must refund exactly what the customer paid
+
+use proptest::prelude::*;
+use purchase_microservice::cart::{Cart, CartMethods, UplInfoObject, UplKind, VAT};
+use purchase_microservice::purchase::{Purchase, PurchaseExt};
+use purchase_microservice::refund::Refund;
+
+fn vat() -> impl Strategy<Value = VAT> {
+  prop_oneof![
+    Just(VAT::AAM),
+    Just(VAT::_5),
+    Just(VAT::_18),
+    Just(VAT::_27),
+  ]
+}
+
+proptest! {
+  #[test]
+  fn refunds_add_up_to_the_purchase(
+    lines in prop::collection::vec((1u32..100_000, 1u32..10, vat()), 1..10),
+    percentage in 0u32..50
+  ) {
+    let mut cart = Cart::new(1, None, 1);
+    let mut upl_ids: Vec<String> = Vec::new();
+    // SKU 0 means no SKU, the test SKUs start from 1
+    for (sku, (unit_price_net, piece, vat)) in lines.iter().enumerate() {
+      for p in 0..*piece {
+        let upl_id = format!("{}-{}", sku, p);
+        cart
+          .add_upl(UplInfoObject {
+            upl_id: upl_id.to_string(),
+            kind: UplKind::Sku { sku: sku as u32 + 1, piece: 1 },
+            name: "Test".to_string(),
+            retail_net_price: *unit_price_net,
+            vat: *vat,
+            retail_gross_price: vat.gross_of(*unit_price_net),
+            ..UplInfoObject::default()
+          })
+          .unwrap();
+        upl_ids.push(upl_id);
+      }
+    }
+    if percentage > 0 {
+      cart.add_commitment(uuid::Uuid::new_v4(), percentage).unwrap();
+    }
+    let mut purchase: Purchase = cart.into();
+
+    let mut refunded_net = 0;
+    let mut refunded_gross = 0;
+    for upl_id in upl_ids {
+      let refund = Refund::new(&purchase, vec![upl_id], Vec::new(), 1).unwrap();
+      prop_assert_eq!(refund.total_net + refund.total_vat, refund.total_gross);
+      refunded_net += refund.total_net;
+      refunded_gross += refund.total_gross;
+      purchase.add_refund(&refund).unwrap();
+    }
+    prop_assert_eq!(refunded_net, purchase.total_net);
+    prop_assert_eq!(refunded_gross, purchase.total_gross);
+  }
+}
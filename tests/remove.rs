@@ -0,0 +1,68 @@
+// Removal of open carts
+// paid or point burned carts cannot be abandoned
+
+use purchase_microservice::abandoned::AbandonedCart;
+use purchase_microservice::cart::{Cart, CartMethods, Payment, UplInfoObject, UplKind, VAT};
+use purchase_microservice::error::DomainError;
+use uuid::Uuid;
+
+#[test]
+fn cart_with_payment_is_not_removable() {
+  let mut cart = Cart::new(1, None, 1);
+  cart.add_payment(Payment {
+    payment_id: "1".to_string(),
+    amount: 1000,
+    ..Payment::default()
+  });
+  assert!(matches!(
+    cart.check_removable(),
+    Err(DomainError::HasPayments)
+  ));
+
+  // Voided payment does not block it anymore
+  cart
+    .void_payment("1".to_string(), "2".to_string(), "Test".to_string(), 1)
+    .unwrap();
+  assert!(cart.check_removable().is_ok());
+}
+
+#[test]
+fn cart_with_burned_points_is_not_removable() {
+  let mut cart = Cart::new(1, None, 1);
+  let account_id = Uuid::new_v4();
+  cart.burn_points(account_id, Uuid::new_v4(), 100).unwrap();
+  assert!(matches!(
+    cart.check_removable(),
+    Err(DomainError::HasBurnedPoints)
+  ));
+
+  // Given back points do not block it anymore
+  cart.burn_points(account_id, Uuid::new_v4(), -100).unwrap();
+  assert!(cart.check_removable().is_ok());
+}
+
+#[test]
+fn removed_cart_keeps_its_upls_to_release() {
+  let mut cart = Cart::new(1, None, 1);
+  cart
+    .add_upl(UplInfoObject {
+      upl_id: "1".to_string(),
+      kind: UplKind::Sku { sku: 1, piece: 1 },
+      name: "Test".to_string(),
+      retail_net_price: 1000,
+      vat: VAT::_27,
+      retail_gross_price: 1270,
+      ..UplInfoObject::default()
+    })
+    .unwrap();
+  let mut abandoned = AbandonedCart::new(cart, "Test".to_string(), 2);
+  assert_eq!(abandoned.upls_to_release, vec!["1".to_string()]);
+  assert!(abandoned.has_upls_to_release());
+
+  abandoned.set_upls_released(3).unwrap();
+  assert!(!abandoned.has_upls_to_release());
+  assert!(matches!(
+    abandoned.set_upls_released(3),
+    Err(DomainError::UplsAlreadyReleased)
+  ));
+}
@@ -0,0 +1,76 @@
+// Restore of a closed purchase
+// payments and burned points are reversed on the purchase
+
+use purchase_microservice::cart::Cart;
+use purchase_microservice::error::DomainError;
+use purchase_microservice::i18n::{receipt_texts, Locale};
+use purchase_microservice::purchase::{
+  LoyaltyTransaction, Payment, Purchase, PurchaseExt, UplInfoObject, UplKind,
+};
+use std::convert::TryFrom;
+use uuid::Uuid;
+
+#[test]
+fn reversed_points_get_new_transactions() {
+  let burn = LoyaltyTransaction {
+    loyalty_account_id: Uuid::new_v4(),
+    transaction_id: Uuid::new_v4(),
+    burned_points: 100,
+    ..LoyaltyTransaction::default()
+  };
+  let mut purchase = Purchase {
+    burned_points: vec![burn.clone()],
+    burned_loyalty_points: 100,
+    ..Purchase::default()
+  };
+  purchase.set_restored(Uuid::new_v4(), 1).unwrap();
+
+  assert_eq!(purchase.burned_points.len(), 2);
+  let reversal = &purchase.burned_points[1];
+  assert!(reversal.transaction_id != burn.transaction_id);
+  assert_eq!(reversal.reversed_transaction_id, Some(burn.transaction_id));
+  assert_eq!(reversal.loyalty_account_id, burn.loyalty_account_id);
+  assert_eq!(reversal.burned_points, -100);
+  assert_eq!(purchase.burned_loyalty_points, 0);
+}
+
+#[test]
+fn restore_fails_if_a_upl_cannot_be_put_back() {
+  let upl = UplInfoObject {
+    upl_id: "1".to_string(),
+    kind: UplKind::Sku { sku: 1, piece: 1 },
+    name: "Test".to_string(),
+    retail_net_price: 1000,
+    vat: "27".to_string(),
+    retail_gross_price: 1270,
+    ..UplInfoObject::default()
+  };
+  let purchase = Purchase {
+    upl_info_objects: vec![upl.clone(), upl],
+    ..Purchase::default()
+  };
+  match Cart::try_from(purchase) {
+    Err(DomainError::UplAlreadyInCart { upl_id }) => assert_eq!(upl_id, "1"),
+    _ => panic!("restore must fail on the second UPL"),
+  }
+}
+
+#[test]
+fn reversed_payment_reason_is_localized_on_display() {
+  let mut purchase = Purchase {
+    payments: vec![Payment {
+      payment_id: "1".to_string(),
+      amount: 1270,
+      ..Payment::default()
+    }],
+    payable: 1270,
+    ..Purchase::default()
+  };
+  purchase.set_restored(Uuid::new_v4(), 1).unwrap();
+
+  let reason = &purchase.payments[1].reversal.as_ref().unwrap().reason;
+  assert_eq!(
+    receipt_texts(&purchase, Locale::En).get(reason),
+    Some(&"Restored".to_string())
+  );
+}
@@ -0,0 +1,69 @@
+// Payment voids and reversals on open carts
+// every reversal is a negative payment, the balance follows it
+
+use purchase_microservice::cart::{
+  Cart, CartMethods, Payment, PaymentKind, UplInfoObject, UplKind, VAT,
+};
+use purchase_microservice::error::DomainError;
+
+fn paid_cart() -> Cart {
+  let mut cart = Cart::new(1, None, 1);
+  cart.set_payment(PaymentKind::Card);
+  cart
+    .add_upl(UplInfoObject {
+      upl_id: "1".to_string(),
+      kind: UplKind::Sku { sku: 1, piece: 1 },
+      name: "Test".to_string(),
+      retail_net_price: 1000,
+      vat: VAT::_27,
+      retail_gross_price: 1270,
+      ..UplInfoObject::default()
+    })
+    .unwrap();
+  cart.add_payment(Payment {
+    payment_id: "1".to_string(),
+    amount: 1270,
+    kind: PaymentKind::Card,
+    ..Payment::default()
+  });
+  cart
+}
+
+#[test]
+fn reversal_recomputes_the_balance() {
+  let mut cart = paid_cart();
+  assert_eq!(cart.get_balance(), 0);
+
+  cart
+    .reverse_payment("1".to_string(), "2".to_string(), 270, "Test".to_string(), 1)
+    .unwrap();
+  assert_eq!(cart.get_balance(), 270);
+  assert_eq!(cart.get_payment_reversible_amount("1").unwrap(), 1000);
+  assert!(matches!(
+    cart.reverse_payment(
+      "1".to_string(),
+      "3".to_string(),
+      1001,
+      "Test".to_string(),
+      1
+    ),
+    Err(DomainError::ReversalAmountOutOfRange { max: 1000 })
+  ));
+}
+
+#[test]
+fn void_reverses_the_remaining_amount() {
+  let mut cart = paid_cart();
+  cart
+    .reverse_payment("1".to_string(), "2".to_string(), 270, "Test".to_string(), 1)
+    .unwrap();
+  cart
+    .void_payment("1".to_string(), "3".to_string(), "Test".to_string(), 1)
+    .unwrap();
+  assert_eq!(cart.get_balance(), 1270);
+  assert_eq!(cart.payments[2].amount, -1000);
+  assert!(matches!(
+    cart.void_payment("1".to_string(), "4".to_string(), "Test".to_string(), 1),
+    Err(DomainError::PaymentAlreadyReversed { .. })
+  ));
+}
@@ -0,0 +1,73 @@
+// Close validation of the payments
+// every payment method must be settled,
+// only transfer can leave the remaining part open
+
+use purchase_microservice::cart::{
+  Cart, CartMethods, Customer, DocumentKind, Payment, PaymentKind, UplInfoObject, UplKind, VAT,
+};
+
+fn cart(payment_kind: PaymentKind) -> Cart {
+  let mut cart = Cart::new(1, None, 1);
+  cart
+    .add_upl(UplInfoObject {
+      upl_id: "1".to_string(),
+      kind: UplKind::Sku { sku: 1, piece: 1 },
+      name: "Test".to_string(),
+      retail_net_price: 1000,
+      vat: VAT::_27,
+      retail_gross_price: 1270,
+      ..UplInfoObject::default()
+    })
+    .unwrap();
+  cart.add_customer(Some(Customer {
+    id: 1,
+    ..Customer::default()
+  }));
+  cart.set_document(DocumentKind::Invoice);
+  cart.set_payment(payment_kind);
+  cart
+}
+
+fn pay(cart: &mut Cart, kind: PaymentKind, amount: i32) {
+  cart.add_payment(Payment {
+    payment_id: format!("{}", cart.payments.len()),
+    amount,
+    kind,
+    ..Payment::default()
+  });
+}
+
+fn codes(cart: &Cart) -> Vec<String> {
+  cart.validate_close().iter().map(|i| i.code()).collect()
+}
+
+#[test]
+fn transfer_leaves_the_remaining_part_open() {
+  let mut cart = cart(PaymentKind::Transfer);
+  pay(&mut cart, PaymentKind::Card, 270);
+  assert!(codes(&cart).is_empty());
+}
+
+#[test]
+fn transfer_cannot_be_overpaid() {
+  let mut cart = cart(PaymentKind::Transfer);
+  pay(&mut cart, PaymentKind::Card, 2000);
+  assert_eq!(codes(&cart), vec!["PAYMENT_OVERPAID".to_string()]);
+}
+
+#[test]
+fn card_remainder_must_be_paid() {
+  let mut cart = cart(PaymentKind::Card);
+  pay(&mut cart, PaymentKind::Cash, 270);
+  assert_eq!(codes(&cart), vec!["PAYMENT_UNBALANCED".to_string()]);
+  pay(&mut cart, PaymentKind::Card, 1000);
+  assert!(codes(&cart).is_empty());
+}
+
+#[test]
+fn method_cannot_be_paid_back_more_than_paid() {
+  let mut cart = cart(PaymentKind::Card);
+  pay(&mut cart, PaymentKind::Cash, -500);
+  pay(&mut cart, PaymentKind::Card, 1770);
+  assert_eq!(codes(&cart), vec!["PAYMENT_NEGATIVE".to_string()]);
+}
@@ -0,0 +1,57 @@
+// Undo of the last cart event
+
+use purchase_microservice::cart::{Cart, CartMethods, UplInfoObject, UplKind, VAT};
+use purchase_microservice::event::CartEventKind;
+
+fn upl(upl_id: &str) -> UplInfoObject {
+  UplInfoObject {
+    upl_id: upl_id.to_string(),
+    kind: UplKind::Sku { sku: 1, piece: 1 },
+    name: "Test".to_string(),
+    retail_net_price: 1000,
+    vat: VAT::_27,
+    retail_gross_price: 1270,
+    ..UplInfoObject::default()
+  }
+}
+
+fn add_upl(cart: &mut Cart, upl_id: &str) {
+  cart
+    .record(
+      1,
+      CartEventKind::AddUpl {
+        upl_id: upl_id.to_string(),
+      },
+      |c| c.add_upl(upl(upl_id)).map(|_| ()),
+    )
+    .unwrap();
+}
+
+#[test]
+fn undo_add_upl_removes_the_sku() {
+  let mut cart = Cart::new(1, None, 1);
+  add_upl(&mut cart, "1");
+  cart.undo_last(1, None).unwrap();
+  assert!(cart.shopping_list.is_empty());
+  assert!(cart.upls_sku.is_empty());
+  assert!(cart.validate_close().is_empty());
+}
+
+#[test]
+fn undo_add_upl_keeps_the_other_pieces() {
+  let mut cart = Cart::new(1, None, 1);
+  add_upl(&mut cart, "1");
+  add_upl(&mut cart, "2");
+  cart.undo_last(1, None).unwrap();
+  assert_eq!(cart.shopping_list[0].piece, 1);
+  assert_eq!(cart.total_gross, 1270);
+  // Still unpaid, but the list and the UPLs agree
+  assert_eq!(
+    cart
+      .validate_close()
+      .iter()
+      .map(|i| i.code())
+      .collect::<Vec<String>>(),
+    vec!["PAYMENT_UNBALANCED".to_string()]
+  );
+}
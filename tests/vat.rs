@@ -0,0 +1,157 @@
+// Property tests of the integer VAT math
+// net + VAT = gross must hold for every amount and every line
+
+use proptest::prelude::*;
+use purchase_microservice::cart::{Cart, CartMethods, VAT};
+use purchase_microservice::purchase::{Purchase, PurchaseExt};
+use purchase_microservice::rounding::{allocate, div_round, percent_of};
+
+fn vat() -> impl Strategy<Value = VAT> {
+  prop_oneof![
+    Just(VAT::AAM),
+    Just(VAT::FAD),
+    Just(VAT::TAM),
+    Just(VAT::_5),
+    Just(VAT::_18),
+    Just(VAT::_27),
+  ]
+}
+
+proptest! {
+  #[test]
+  fn gross_is_net_plus_vat(net in 0u32..100_000_000, vat in vat()) {
+    prop_assert_eq!(vat.gross_of(net), net + vat.vat_of(net));
+    prop_assert_eq!(net * vat, vat.gross_of(net));
+  }
+
+  #[test]
+  fn gross_is_exact_within_half_huf(net in 0u32..100_000_000, vat in vat()) {
+    // |gross - net * (100 + rate) / 100| <= 0.5
+    let exact = net as i64 * (100 + vat.rate() as i64);
+    let gross = vat.gross_of(net) as i64 * 100;
+    prop_assert!((gross - exact).abs() <= 50);
+  }
+
+  #[test]
+  fn net_of_gross_of_is_identity(net in 0u32..100_000_000, vat in vat()) {
+    prop_assert_eq!(vat.net_of(vat.gross_of(net)), net);
+  }
+
+  #[test]
+  fn net_of_is_exact_within_half_huf(gross in 0u32..100_000_000, vat in vat()) {
+    // |net - gross * 100 / (100 + rate)| <= 0.5
+    let net = vat.net_of(gross) as i64;
+    let base = 100 + vat.rate() as i64;
+    prop_assert!((2 * (net * base - gross as i64 * 100)).abs() <= base);
+  }
+
+  #[test]
+  fn div_round_is_half_up(numerator in 0u64..u32::MAX as u64, denominator in 1u64..10_000) {
+    let res = div_round(numerator, denominator);
+    prop_assert!(2 * res * denominator + denominator > 2 * numerator);
+    prop_assert!(2 * res * denominator <= 2 * numerator + denominator);
+  }
+
+  #[test]
+  fn percent_of_never_exceeds_amount(amount in 0u32..100_000_000, percentage in 0u32..=100) {
+    prop_assert!(percent_of(amount, percentage) <= amount);
+  }
+
+  #[test]
+  fn allocate_adds_up(amount in 0u32..10_000_000, weights in prop::collection::vec(0u32..1_000_000, 1..10)) {
+    let shares = allocate(amount, &weights);
+    prop_assert_eq!(shares.len(), weights.len());
+    prop_assert_eq!(shares.iter().sum::<u32>(), amount);
+  }
+
+  #[test]
+  fn cart_lines_add_up(
+    lines in prop::collection::vec((1u32..100_000, 1u32..100, vat()), 1..20)
+  ) {
+    let mut cart = Cart::new(1, None, 1);
+    for (sku, (unit_price_net, piece, vat)) in lines.iter().enumerate() {
+      cart.add_sku(
+        sku as u32,
+        *piece,
+        "Test".to_string(),
+        *vat,
+        *unit_price_net,
+        vat.gross_of(*unit_price_net),
+      );
+    }
+    for i in &cart.shopping_list {
+      prop_assert_eq!(i.total_price_net + i.total_price_vat, i.total_price_gross);
+      prop_assert_eq!(i.total_price_gross, i.vat.gross_of(i.total_price_net));
+    }
+    prop_assert_eq!(cart.total_net + cart.total_vat, cart.total_gross);
+    prop_assert_eq!(
+      cart.total_gross,
+      cart.shopping_list.iter().map(|i| i.total_price_gross).sum::<u32>()
+    );
+    for t in &cart.vat_totals {
+      prop_assert_eq!(t.total_net + t.total_vat, t.total_gross);
+    }
+    prop_assert_eq!(cart.vat_totals.iter().map(|t| t.total_net).sum::<u32>(), cart.total_net);
+    prop_assert_eq!(cart.vat_totals.iter().map(|t| t.total_gross).sum::<u32>(), cart.total_gross);
+  }
+
+  #[test]
+  fn commitment_discount_is_split_by_vat(
+    lines in prop::collection::vec((1u32..100_000, 1u32..100, vat()), 1..20),
+    percentage in 1u32..50
+  ) {
+    let mut cart = Cart::new(1, None, 1);
+    for (sku, (unit_price_net, piece, vat)) in lines.iter().enumerate() {
+      cart.add_sku(
+        sku as u32,
+        *piece,
+        "Test".to_string(),
+        *vat,
+        *unit_price_net,
+        vat.gross_of(*unit_price_net),
+      );
+    }
+    let items_gross = cart.get_items_total_gross();
+    cart.add_commitment(uuid::Uuid::new_v4(), percentage).unwrap();
+    // Whole discount is taken from the groups
+    prop_assert_eq!(cart.total_gross, items_gross - cart.commitment_discount_value);
+    prop_assert_eq!(cart.vat_totals.iter().map(|t| t.total_net).sum::<u32>(), cart.total_net);
+    prop_assert_eq!(cart.vat_totals.iter().map(|t| t.total_gross).sum::<u32>(), cart.total_gross);
+    for t in &cart.vat_totals {
+      prop_assert_eq!(t.total_net + t.total_vat, t.total_gross);
+      // Exempt groups never get VAT by the discount
+      if t.vat.rate() == 0 {
+        prop_assert_eq!(t.total_vat, 0);
+      }
+    }
+  }
+
+  #[test]
+  fn purchase_vat_totals_match_the_cart(
+    lines in prop::collection::vec((1u32..100_000, 1u32..100, vat()), 1..20),
+    percentage in 1u32..50
+  ) {
+    let mut cart = Cart::new(1, None, 1);
+    for (sku, (unit_price_net, piece, vat)) in lines.iter().enumerate() {
+      cart.add_sku(
+        sku as u32,
+        *piece,
+        "Test".to_string(),
+        *vat,
+        *unit_price_net,
+        vat.gross_of(*unit_price_net),
+      );
+    }
+    cart.add_commitment(uuid::Uuid::new_v4(), percentage).unwrap();
+    let purchase: Purchase = cart.clone().into();
+    // Recomputed totals (as in migration) split the discount the same way
+    let totals = purchase.compute_vat_totals();
+    prop_assert_eq!(totals.len(), cart.vat_totals.len());
+    for (t, c) in totals.iter().zip(cart.vat_totals.iter()) {
+      prop_assert_eq!(t.vat, c.vat);
+      prop_assert_eq!(t.total_gross, c.total_gross);
+    }
+    prop_assert_eq!(totals.iter().map(|t| t.total_net).sum::<u32>(), purchase.total_net);
+    prop_assert_eq!(totals.iter().map(|t| t.total_gross).sum::<u32>(), purchase.total_gross);
+  }
+}
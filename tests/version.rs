@@ -0,0 +1,87 @@
+// Cart version for optimistic concurrency
+// every recorded modification is one new version
+
+use purchase_microservice::cart::{Cart, CartMethods, UplInfoObject, UplKind, VAT};
+use purchase_microservice::error::DomainError;
+use purchase_microservice::event::CartEventKind;
+use purchase_microservice::i18n::Locale;
+use purchase_microservice::prelude::ServiceError;
+use tonic::Code;
+
+fn upl(upl_id: &str) -> UplInfoObject {
+  UplInfoObject {
+    upl_id: upl_id.to_string(),
+    kind: UplKind::Sku { sku: 1, piece: 1 },
+    name: "Test".to_string(),
+    retail_net_price: 1000,
+    vat: VAT::_27,
+    retail_gross_price: 1270,
+    ..UplInfoObject::default()
+  }
+}
+
+#[test]
+fn version_is_bumped_once_per_event() {
+  let mut cart = Cart::new(1, None, 1);
+  let version = cart.version;
+  cart
+    .record(
+      1,
+      CartEventKind::AddUpl {
+        upl_id: "1".to_string(),
+      },
+      |c| c.add_upl(upl("1")).map(|_| ()),
+    )
+    .unwrap();
+  assert_eq!(cart.version, version + 1);
+
+  cart.undo_last(1, None).unwrap();
+  assert_eq!(cart.version, version + 2);
+}
+
+#[test]
+fn refused_modification_keeps_the_version() {
+  let mut cart = Cart::new(1, None, 1);
+  cart.add_upl(upl("1")).unwrap();
+  let version = cart.version;
+  let res = cart.record(
+    1,
+    CartEventKind::AddUpl {
+      upl_id: "1".to_string(),
+    },
+    |c| c.add_upl(upl("1")).map(|_| ()),
+  );
+  assert!(res.is_err());
+  assert_eq!(cart.version, version);
+  assert!(cart.events.is_empty());
+}
+
+#[test]
+fn stale_version_is_a_conflict() {
+  let mut cart = Cart::new(1, None, 1);
+  cart
+    .record(
+      1,
+      CartEventKind::AddUpl {
+        upl_id: "1".to_string(),
+      },
+      |c| c.add_upl(upl("1")).map(|_| ()),
+    )
+    .unwrap();
+  assert!(cart.check_version(0).is_ok());
+  assert!(cart.check_version(2).is_ok());
+  match cart.check_version(1) {
+    Err(e) => {
+      assert!(matches!(
+        e,
+        DomainError::VersionConflict {
+          expected: 1,
+          actual: 2
+        }
+      ));
+      let status = ServiceError::from(e).into_status(Locale::En);
+      assert_eq!(status.code(), Code::Aborted);
+    }
+    Ok(_) => panic!("stale version must be refused"),
+  }
+}